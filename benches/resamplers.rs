@@ -2,36 +2,67 @@ use criterion::{criterion_group, criterion_main, Criterion};
 extern crate rubato;
 
 use rubato::{
-    FftFixedIn, FftFixedOut, InterpolationParameters, InterpolationType, Resampler, SincFixedIn,
-    WindowFunction,
+    DotProductKernel, FastFixedIn, FftFilterParameters, FftFixedIn, FftFixedOut,
+    InterpolationParameters, InterpolationType, Resampler, SincFixedIn, WindowFunction,
 };
 
 fn bench_fftfixedin(c: &mut Criterion) {
     let chunksize = 1024;
     let mut resampler = FftFixedIn::<f64>::new(44100, 192000, 1024, 2, 1);
-    let mut waveform = vec![vec![0.0 as f64; chunksize]; 1];
+    let waveform = vec![vec![0.0f64; chunksize]; 1];
+    let mut waveform_out = vec![vec![0.0f64; resampler.output_frames_max()]; 1];
     c.bench_function("FftFixedIn", |b| {
         b.iter(|| {
-            let _resampled = resampler.process(&mut waveform).unwrap();
+            let _resampled = resampler
+                .process_into_buffer(&waveform, &mut waveform_out, None)
+                .unwrap();
         })
     });
 }
 
 fn bench_fftfixedout(c: &mut Criterion) {
     let mut resampler = FftFixedOut::<f64>::new(44100, 192000, 4096, 2, 1);
+    let mut waveform = vec![Vec::with_capacity(4096); 1];
+    let mut waveform_out = vec![vec![0.0f64; resampler.output_frames_max()]; 1];
     c.bench_function("FftFixedOut", |b| {
         b.iter(|| {
             let needed = resampler.nbr_frames_needed();
-            let mut waveform = vec![vec![0.0 as f64; needed]; 1];
-            let _resampled = resampler.process(&mut waveform).unwrap();
+            waveform[0].resize(needed, 0.0f64);
+            let _resampled = resampler
+                .process_into_buffer(&waveform, &mut waveform_out, None)
+                .unwrap();
         })
     });
 }
 
+fn bench_fftfixedin_long_filter(c: &mut Criterion) {
+    let chunksize = 64;
+    for filter_len in [64, 1024].iter() {
+        let params = FftFilterParameters {
+            filter_len: Some(*filter_len),
+            ..Default::default()
+        };
+        let mut resampler =
+            FftFixedIn::<f64>::new_with_parameters(48000, 96000, chunksize, 1, 1, params);
+        let waveform = vec![vec![0.0f64; chunksize]; 1];
+        let mut waveform_out = vec![vec![0.0f64; resampler.output_frames_max()]; 1];
+        c.bench_function(
+            &format!("FftFixedIn 64 frames, filter {}", filter_len),
+            |b| {
+                b.iter(|| {
+                    let _resampled = resampler
+                        .process_into_buffer(&waveform, &mut waveform_out, None)
+                        .unwrap();
+                })
+            },
+        );
+    }
+}
+
 fn bench_sincfixedin(c: &mut Criterion) {
     let chunksize = 1024;
     let sinc_len = 256;
-    let f_cutoff = 0.9473371669037001;
+    let f_cutoff = 0.947_337_2;
     let params = InterpolationParameters {
         sinc_len,
         f_cutoff,
@@ -39,11 +70,14 @@ fn bench_sincfixedin(c: &mut Criterion) {
         oversampling_factor: 256,
         window: WindowFunction::BlackmanHarris2,
     };
-    let mut resampler = SincFixedIn::<f64>::new(192000 as f64 / 44100 as f64, params, chunksize, 1);
-    let mut waveform = vec![vec![0.0 as f64; chunksize]; 1];
+    let mut resampler = SincFixedIn::<f64>::new(192000.0 / 44100.0, params, chunksize, 1);
+    let waveform = vec![vec![0.0f64; chunksize]; 1];
+    let mut waveform_out = vec![vec![0.0f64; resampler.output_frames_max()]; 1];
     c.bench_function("SincFixedIn async", |b| {
         b.iter(|| {
-            let _resampled = resampler.process(&mut waveform).unwrap();
+            let _resampled = resampler
+                .process_into_buffer(&waveform, &mut waveform_out, None)
+                .unwrap();
         })
     });
 }
@@ -51,7 +85,7 @@ fn bench_sincfixedin(c: &mut Criterion) {
 fn bench_sincfixedin_sync(c: &mut Criterion) {
     let chunksize = 1024;
     let sinc_len = 256;
-    let f_cutoff = 0.9473371669037001;
+    let f_cutoff = 0.947_337_2;
     let params = InterpolationParameters {
         sinc_len,
         f_cutoff,
@@ -59,20 +93,123 @@ fn bench_sincfixedin_sync(c: &mut Criterion) {
         oversampling_factor: 640,
         window: WindowFunction::BlackmanHarris2,
     };
-    let mut resampler = SincFixedIn::<f64>::new(192000 as f64 / 44100 as f64, params, chunksize, 1);
-    let mut waveform = vec![vec![0.0 as f64; chunksize]; 1];
+    let mut resampler = SincFixedIn::<f64>::new(192000.0 / 44100.0, params, chunksize, 1);
+    let waveform = vec![vec![0.0f64; chunksize]; 1];
+    let mut waveform_out = vec![vec![0.0f64; resampler.output_frames_max()]; 1];
     c.bench_function("SincFixedIn sync", |b| {
         b.iter(|| {
-            let _resampled = resampler.process(&mut waveform).unwrap();
+            let _resampled = resampler
+                .process_into_buffer(&waveform, &mut waveform_out, None)
+                .unwrap();
+        })
+    });
+}
+
+fn bench_fastfixedin(c: &mut Criterion) {
+    let chunksize = 1024;
+    let mut resampler = FastFixedIn::<f64>::new(
+        192000.0 / 44100.0,
+        1.1,
+        InterpolationType::Cubic,
+        chunksize,
+        1,
+    );
+    let waveform = vec![vec![0.0f64; chunksize]; 1];
+    let mut waveform_out = vec![vec![0.0f64; resampler.output_frames_max()]; 1];
+    c.bench_function("FastFixedIn cubic", |b| {
+        b.iter(|| {
+            let _resampled = resampler
+                .process_into_buffer(&waveform, &mut waveform_out, None)
+                .unwrap();
+        })
+    });
+}
+
+fn bench_sincfixedin_multichannel(c: &mut Criterion) {
+    let chunksize = 1024;
+    let channels = 32;
+    let params = InterpolationParameters {
+        sinc_len: 256,
+        f_cutoff: 0.947_337_2,
+        interpolation: InterpolationType::Cubic,
+        oversampling_factor: 256,
+        window: WindowFunction::BlackmanHarris2,
+    };
+    let mut resampler = SincFixedIn::<f32>::new(48000.0 / 44100.0, params, chunksize, channels);
+    let waveform = vec![vec![0.0f32; chunksize]; channels];
+    let mut waveform_out = vec![vec![0.0f32; resampler.output_frames_max()]; channels];
+    c.bench_function("SincFixedIn 32 channels", |b| {
+        b.iter(|| {
+            let _resampled = resampler
+                .process_into_buffer(&waveform, &mut waveform_out, None)
+                .unwrap();
+        })
+    });
+}
+
+fn bench_fftfixedin_multichannel(c: &mut Criterion) {
+    let chunksize = 1024;
+    let channels = 32;
+    let mut resampler = FftFixedIn::<f32>::new(44100, 48000, chunksize, 2, channels);
+    let waveform = vec![vec![0.0f32; chunksize]; channels];
+    let mut waveform_out = vec![vec![0.0f32; resampler.output_frames_max()]; channels];
+    c.bench_function("FftFixedIn 32 channels", |b| {
+        b.iter(|| {
+            let _resampled = resampler
+                .process_into_buffer(&waveform, &mut waveform_out, None)
+                .unwrap();
         })
     });
 }
 
+macro_rules! bench_sinc_kernels {
+    ($fn_name:ident, $ft:ty, $desc:expr) => {
+        fn $fn_name(c: &mut Criterion) {
+            let chunksize = 1024;
+            let kernels = [
+                DotProductKernel::Portable,
+                DotProductKernel::Sse,
+                DotProductKernel::Avx,
+                DotProductKernel::AvxFma,
+            ];
+            for kernel in kernels.iter().filter(|kernel| kernel.is_available()) {
+                let params = InterpolationParameters {
+                    sinc_len: 256,
+                    f_cutoff: 0.947_337_2,
+                    interpolation: InterpolationType::Cubic,
+                    oversampling_factor: 256,
+                    window: WindowFunction::BlackmanHarris2,
+                };
+                let mut resampler =
+                    SincFixedIn::<$ft>::new(192000.0 / 44100.0, params, chunksize, 1);
+                resampler.set_dot_product_kernel(*kernel).unwrap();
+                let waveform = vec![vec![0.0 as $ft; chunksize]; 1];
+                let mut waveform_out = vec![vec![0.0 as $ft; resampler.output_frames_max()]; 1];
+                c.bench_function(&format!("SincFixedIn {} {:?}", $desc, kernel), |b| {
+                    b.iter(|| {
+                        let _resampled = resampler
+                            .process_into_buffer(&waveform, &mut waveform_out, None)
+                            .unwrap();
+                    })
+                });
+            }
+        }
+    };
+}
+bench_sinc_kernels!(bench_sinc_kernels_32, f32, "f32");
+bench_sinc_kernels!(bench_sinc_kernels_64, f64, "f64");
+
 criterion_group!(
     benches,
     bench_fftfixedin,
+    bench_fftfixedin_long_filter,
     bench_sincfixedin,
     bench_sincfixedin_sync,
+    bench_sinc_kernels_32,
+    bench_sinc_kernels_64,
+    bench_sincfixedin_multichannel,
+    bench_fftfixedin_multichannel,
+    bench_fastfixedin,
     bench_fftfixedout
 );
 
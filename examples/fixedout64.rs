@@ -1,5 +1,20 @@
+//! A resampler app that reads a raw file of little-endian 64 bit floats, and writes the output in the same format.
+//! This version takes a varying number of input samples per chunk, and outputs a fixed number of samples.
+//! The command line arguments are input filename, output filename, input samplerate, output samplerate, number of channels
+//! To resample the file `sine_f64_2ch.raw` from 44.1kHz to 192kHz, and assuming the file has two channels, the command is:
+//! ```
+//! cargo run --release --example fixedout64 sine_f64_2ch.raw test.raw 44100 192000 2
+//! ```
+//! There are two helper python scripts for testing. `makesineraw.py` simply writes a stereo file
+//! with a 1 second long 1kHz tone (at 44.1kHz). This script takes no aruments. Modify as needed to create other test files.
+//! To analyze the result, use the `analyze_result.py` script. This takes three arguments: number of channels, samplerate, and number of bits per sample (32 or 64).
+//! Example, to analyze the file created above:
+//! ```
+//! python examples/analyze_result.py test.raw 2 192000 64
+//! ```
+
 extern crate rubato;
-use rubato::{InterpolationParameters, InterpolationType, Resampler, SincFixedOut, WindowFunction};
+use rubato::{InterpolationParameters, QualityTarget, Resampler, SincFixedOut};
 use std::convert::TryInto;
 use std::env;
 use std::fs::File;
@@ -12,21 +27,6 @@ extern crate log;
 use env_logger::Builder;
 use log::LevelFilter;
 
-///! A resampler app that reads a raw file of little-endian 64 bit floats, and writes the output in the same format.
-///! This version takes a varying number of input samples per chunk, and outputs a fixed number of samples.
-///! The command line arguments are input filename, output filename, input samplerate, output samplerate, number of channels
-///! To resample the file `sine_f64_2ch.raw` from 44.1kHz to 192kHz, and assuming the file has two channels, the command is:
-///! ```
-///! cargo run --release --example fixedout64 sine_f64_2ch.raw test.raw 44100 192000 2
-///! ```
-///! There are two helper python scripts for testing. `makesineraw.py` simply writes a stereo file
-///! with a 1 second long 1kHz tone (at 44.1kHz). This script takes no aruments. Modify as needed to create other test files.
-///! To analyze the result, use the `analyze_result.py` script. This takes three arguments: number of channels, samplerate, and number of bits per sample (32 or 64).
-///! Example, to analyze the file created above:
-///! ```
-///! python examples/analyze_result.py test.raw 2 192000 64
-///! ```
-
 fn read_frames<R: Read + Seek>(inbuffer: &mut R, nbr: usize, channels: usize) -> Vec<Vec<f64>> {
     let mut buffer = vec![0u8; 8];
     let mut wfs = Vec::with_capacity(channels);
@@ -36,7 +36,7 @@ fn read_frames<R: Read + Seek>(inbuffer: &mut R, nbr: usize, channels: usize) ->
     let mut value: f64;
     for _frame in 0..nbr {
         for wf in wfs.iter_mut().take(channels) {
-            if inbuffer.read(&mut buffer).unwrap() < 8 {
+            if inbuffer.read_exact(&mut buffer).is_err() {
                 return wfs;
             }
             value = f64::from_le_bytes(buffer.as_slice().try_into().unwrap()) as f64;
@@ -50,10 +50,10 @@ fn read_frames<R: Read + Seek>(inbuffer: &mut R, nbr: usize, channels: usize) ->
 fn write_frames<W: Write + Seek>(waves: Vec<Vec<f64>>, outbuffer: &mut W, channels: usize) {
     let nbr = waves[0].len();
     for frame in 0..nbr {
-        for chan in 0..channels {
-            let value64 = waves[chan][frame];
+        for wave in waves.iter().take(channels) {
+            let value64 = wave[frame];
             let bytes = value64.to_le_bytes();
-            outbuffer.write(&bytes).unwrap();
+            outbuffer.write_all(&bytes).unwrap();
         }
     }
 }
@@ -111,60 +111,9 @@ fn main() {
 
     let f_ratio = fs_out as f64 / fs_in as f64;
 
-    // Fast for async
-    //let sinc_len = 64;
-    //let f_cutoff = 0.9156021241005041; //1.0 /(1.0 + std::f32::consts::PI/sinc_len as f32);
-    //let params = InterpolationParameters {
-    //    sinc_len,
-    //    f_cutoff,
-    //    interpolation: InterpolationType::Linear,
-    //    oversampling_factor: 1024,
-    //    window: WindowFunction::Hann2,
-    //};
-
-    // Balanced for sync for 44100 -> 96000 etc
-    //let sinc_len = 128;
-    //let f_cutoff = 0.925914648491266;
-    //let params = InterpolationParameters {
-    //    sinc_len,
-    //    f_cutoff,
-    //    interpolation: InterpolationType::Nearest,
-    //    oversampling_factor: 320,
-    //    window: WindowFunction::Blackman2,
-    //};
-
-    // Balanced for async
-    //let sinc_len = 128;
-    //let f_cutoff = 0.925914648491266;
-    //let params = InterpolationParameters {
-    //    sinc_len,
-    //    f_cutoff,
-    //    interpolation: InterpolationType::Linear,
-    //    oversampling_factor: 2048,
-    //    window: WindowFunction::Blackman2,
-    //};
-    //
-    //// Best for sync for 44100 -> 96000 etc
-    let sinc_len = 256;
-    let f_cutoff = 0.9473371669037001;
-    let params = InterpolationParameters {
-        sinc_len,
-        f_cutoff,
-        interpolation: InterpolationType::Nearest,
-        oversampling_factor: 320,
-        window: WindowFunction::BlackmanHarris2,
-    };
-
-    // Best for async
-    //let sinc_len = 256;
-    //let f_cutoff = 0.9473371669037001;
-    //let params = InterpolationParameters {
-    //    sinc_len,
-    //    f_cutoff,
-    //    interpolation: InterpolationType::Cubic,
-    //    oversampling_factor: 256,
-    //    window: WindowFunction::BlackmanHarris2,
-    //};
+    // Parameters for the best quality. Since the ratio is not changed while resampling,
+    // they are chosen for synchronous resampling when possible.
+    let params = InterpolationParameters::from_quality(QualityTarget::Best, fs_in, fs_out, false);
 
     let mut resampler = SincFixedOut::<f64>::new(f_ratio, params, 1024, channels);
 
@@ -0,0 +1,290 @@
+use crate::{ResampleError, ResampleResult, Resampler};
+use num_traits::Float;
+use std::marker::PhantomData;
+
+/// A struct holding the parameters for drift compensation.
+#[derive(Debug, Clone)]
+pub struct DriftCompensationParameters {
+    /// The wanted level of the observed buffer, in frames.
+    pub target_level: f64,
+    /// Proportional gain, the change of the relative resample ratio per frame of level error.
+    /// Start at about 0.01 / chunk_size.
+    pub proportional_gain: f64,
+    /// Integral gain, the change of the relative resample ratio per frame of accumulated level error.
+    /// This removes the remaining level error when the clocks differ.
+    /// Start at about 1/1000 of the proportional gain.
+    pub integral_gain: f64,
+    /// Coefficient of the low-pass filter that smooths the level observations.
+    /// Must be larger than zero and at most 1.0, where 1.0 means no filtering.
+    /// Lower values give a smoother ratio, but a slower reaction. Start at 0.02.
+    pub filter_coefficient: f64,
+    /// The largest allowed relative change of the resample ratio, for example 0.001 for 0.1%.
+    /// This must be within the range given when creating the resampler.
+    pub max_adjustment: f64,
+}
+
+/// A wrapper that steers the ratio of an asynchronous resampler to compensate
+/// for the drift between two clocks, for example when bridging two sound cards.
+///
+/// The level of a buffer between the two clock domains is observed once per chunk,
+/// either the buffer that feeds the resampler or the one that it writes to.
+/// A level above the target means that the resampler produces too many frames
+/// for the input it consumes, and the ratio is lowered.
+/// A difference between timestamps of the two clocks can be used in the same way,
+/// when converted to frames.
+/// The observations are low-pass filtered and drive a PI controller,
+/// that sets the relative resample ratio with a ramp over the next chunk.
+pub struct DriftCompensator<T, R> {
+    resampler: R,
+    parameters: DriftCompensationParameters,
+    filtered_level: Option<f64>,
+    integral: f64,
+    ratio_relative: f64,
+    _phantom: PhantomData<T>,
+}
+
+impl<T: Float, R: Resampler<T>> DriftCompensator<T, R> {
+    /// Create a new DriftCompensator wrapping the given resampler.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any of the parameters is invalid, see `try_new`.
+    pub fn new(resampler: R, parameters: DriftCompensationParameters) -> Self {
+        Self::try_new(resampler, parameters).unwrap()
+    }
+
+    /// Create a new DriftCompensator, checking that the parameters are valid.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidParameter` error if the filter coefficient is not larger than zero
+    /// and at most 1.0, or if any of the gains or the maximum adjustment is negative.
+    pub fn try_new(resampler: R, parameters: DriftCompensationParameters) -> ResampleResult<Self> {
+        if !(parameters.filter_coefficient > 0.0 && parameters.filter_coefficient <= 1.0) {
+            return Err(ResampleError::invalid_parameter(
+                "filter_coefficient",
+                "must be larger than zero and at most 1.0",
+            ));
+        }
+        if !(parameters.proportional_gain.is_finite() && parameters.proportional_gain >= 0.0) {
+            return Err(ResampleError::invalid_parameter(
+                "proportional_gain",
+                "must be a finite number that is not negative",
+            ));
+        }
+        if !(parameters.integral_gain.is_finite() && parameters.integral_gain >= 0.0) {
+            return Err(ResampleError::invalid_parameter(
+                "integral_gain",
+                "must be a finite number that is not negative",
+            ));
+        }
+        if !(parameters.max_adjustment >= 0.0 && parameters.max_adjustment < 1.0) {
+            return Err(ResampleError::invalid_parameter(
+                "max_adjustment",
+                "must not be negative, and less than 1.0",
+            ));
+        }
+        debug!("Create new DriftCompensator, parameters: {:?}", parameters);
+        Ok(DriftCompensator {
+            resampler,
+            parameters,
+            filtered_level: None,
+            integral: 0.0,
+            ratio_relative: 1.0,
+            _phantom: PhantomData,
+        })
+    }
+
+    /// Give a new observation of the buffer level, in frames, and update the resample ratio.
+    /// This should be called once per chunk, before processing it.
+    /// Returns the new ratio, relative to the original ratio of the resampler.
+    /// # Errors
+    ///
+    /// Returns an error if the wrapped resampler does not accept the new ratio,
+    /// for example a `RatioOutOfBounds` error if `max_adjustment` is larger than
+    /// the range given when creating the resampler.
+    pub fn update_buffer_level(&mut self, level: f64) -> ResampleResult<f64> {
+        let coefficient = self.parameters.filter_coefficient;
+        let filtered = match self.filtered_level {
+            Some(filtered) => filtered + coefficient * (level - filtered),
+            None => level,
+        };
+        self.filtered_level = Some(filtered);
+        let error = filtered - self.parameters.target_level;
+        let max_adjustment = self.parameters.max_adjustment;
+
+        // limit the integral term to the allowed adjustment, to avoid windup
+        self.integral += error;
+        if self.parameters.integral_gain > 0.0 {
+            let max_integral = max_adjustment / self.parameters.integral_gain;
+            self.integral = self.integral.max(-max_integral).min(max_integral);
+        }
+        let adjustment = (self.parameters.proportional_gain * error
+            + self.parameters.integral_gain * self.integral)
+            .max(-max_adjustment)
+            .min(max_adjustment);
+        let ratio_relative = 1.0 - adjustment;
+        trace!(
+            "Buffer level {}, filtered {}, new relative ratio {}",
+            level,
+            filtered,
+            ratio_relative
+        );
+        self.resampler
+            .set_resample_ratio_relative_ramped(ratio_relative)?;
+        self.ratio_relative = ratio_relative;
+        Ok(ratio_relative)
+    }
+
+    /// Get the current resample ratio, relative to the original ratio of the resampler.
+    pub fn ratio_relative(&self) -> f64 {
+        self.ratio_relative
+    }
+
+    /// Reset the controller and the wrapped resampler to the state they had when created.
+    pub fn reset(&mut self) {
+        self.filtered_level = None;
+        self.integral = 0.0;
+        self.ratio_relative = 1.0;
+        self.resampler.reset();
+    }
+
+    /// Get a reference to the wrapped resampler.
+    pub fn resampler(&self) -> &R {
+        &self.resampler
+    }
+
+    /// Get a mutable reference to the wrapped resampler, for processing audio.
+    pub fn resampler_mut(&mut self) -> &mut R {
+        &mut self.resampler
+    }
+
+    /// Consume the wrapper and return the wrapped resampler.
+    pub fn into_inner(self) -> R {
+        self.resampler
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        DriftCompensationParameters, DriftCompensator, InterpolationParameters, InterpolationType,
+        ResampleError, Resampler, SincFixedOut, WindowFunction,
+    };
+
+    /// Simulate a capture device with a clock that is `drift` faster than the playback clock.
+    /// The captured frames arrive in bursts to a buffer, and the resampler takes its input
+    /// from the buffer and produces one chunk for each period of the playback device.
+    /// Returns the buffer levels and relative ratios, observed once per chunk.
+    fn simulate_two_clocks(drift: f64, nbr_chunks: usize) -> (Vec<f64>, Vec<f64>) {
+        let chunk_size = 128;
+        let burst = 48;
+        let params = InterpolationParameters {
+            sinc_len: 16,
+            f_cutoff: 0.9,
+            interpolation: InterpolationType::Nearest,
+            oversampling_factor: 16,
+            window: WindowFunction::BlackmanHarris2,
+        };
+        let resampler = SincFixedOut::<f32>::new_with_ratio_range(1.0, 1.01, params, chunk_size, 1);
+        let target_level = 400.0;
+        let mut compensator = DriftCompensator::new(
+            resampler,
+            DriftCompensationParameters {
+                target_level,
+                proportional_gain: 0.01 / chunk_size as f64,
+                integral_gain: 0.00001 / chunk_size as f64,
+                filter_coefficient: 0.02,
+                max_adjustment: 0.005,
+            },
+        );
+        let mut captured = target_level as usize;
+        let mut consumed = 0;
+        let mut levels = Vec::new();
+        let mut ratios = Vec::new();
+        for chunk in 1..=nbr_chunks {
+            // frames captured during the playback of the chunks so far, delivered in bursts
+            let capture_time = (chunk * chunk_size) as f64 * (1.0 + drift);
+            while ((captured - target_level as usize + burst) as f64) <= capture_time {
+                captured += burst;
+            }
+            let level = (captured - consumed) as f64;
+            levels.push(level);
+            ratios.push(compensator.update_buffer_level(level).unwrap());
+            let frames = compensator.resampler().nbr_frames_needed();
+            assert!(frames <= captured - consumed, "buffer underrun");
+            consumed += frames;
+            let wave_in = vec![0.0f32; frames];
+            compensator.resampler_mut().process(&[&wave_in]).unwrap();
+        }
+        (levels, ratios)
+    }
+
+    #[test]
+    fn compensate_drift() {
+        for drift in [0.0005, -0.0005, 0.0].iter() {
+            let (levels, ratios) = simulate_two_clocks(*drift, 8000);
+            // the ratio must settle at the ratio between the clocks,
+            // and the level must return to the target
+            let expected_ratio = 1.0 / (1.0 + drift);
+            let mean_level = levels[6000..].iter().sum::<f64>() / 2000.0;
+            let mean_ratio = ratios[6000..].iter().sum::<f64>() / 2000.0;
+            assert!((mean_ratio - expected_ratio).abs() < 2.0e-6);
+            assert!((mean_level - 400.0).abs() < 5.0);
+            // and the ratio must be smooth after settling
+            let max_step = ratios[6000..]
+                .windows(2)
+                .map(|pair| (pair[1] - pair[0]).abs())
+                .fold(0.0, f64::max);
+            assert!(max_step < 1.0e-4);
+        }
+    }
+
+    #[test]
+    fn reset_compensator() {
+        let params = InterpolationParameters {
+            sinc_len: 16,
+            f_cutoff: 0.9,
+            interpolation: InterpolationType::Nearest,
+            oversampling_factor: 16,
+            window: WindowFunction::BlackmanHarris2,
+        };
+        let resampler = SincFixedOut::<f64>::new_with_ratio_range(1.0, 1.01, params, 128, 1);
+        let frames = resampler.nbr_frames_needed();
+        let mut compensator = DriftCompensator::new(
+            resampler,
+            DriftCompensationParameters {
+                target_level: 400.0,
+                proportional_gain: 0.001,
+                integral_gain: 0.0,
+                filter_coefficient: 1.0,
+                max_adjustment: 0.005,
+            },
+        );
+        let ratio = compensator.update_buffer_level(402.0).unwrap();
+        assert!((ratio - 0.998).abs() < 1.0e-12);
+        let ratio = compensator.update_buffer_level(500.0).unwrap();
+        assert!((ratio - 0.995).abs() < 1.0e-12);
+        compensator.reset();
+        assert_eq!(compensator.ratio_relative(), 1.0);
+        assert_eq!(compensator.resampler().nbr_frames_needed(), frames);
+
+        let resampler = compensator.into_inner();
+        let result = DriftCompensator::try_new(
+            resampler,
+            DriftCompensationParameters {
+                target_level: 400.0,
+                proportional_gain: 0.001,
+                integral_gain: 0.0,
+                filter_coefficient: 0.0,
+                max_adjustment: 0.005,
+            },
+        );
+        match result {
+            Err(ResampleError::InvalidParameter { name, .. }) => {
+                assert_eq!(name, "filter_coefficient")
+            }
+            _ => panic!("expected an InvalidParameter error"),
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use std::error;
+use std::fmt;
+
+/// The error type returned by the resamplers.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResampleError {
+    /// The input does not have the number of channels the resampler was created for.
+    WrongNumberOfInputChannels { expected: usize, actual: usize },
+    /// The output does not have the number of channels the resampler was created for.
+    WrongNumberOfOutputChannels { expected: usize, actual: usize },
+    /// The active channels mask does not have the number of channels the resampler was created for.
+    WrongNumberOfMaskChannels { expected: usize, actual: usize },
+    /// A channel of the input does not have the number of frames given by "nbr_frames_needed",
+    /// or for a partial chunk, has more frames than that.
+    /// For `process_all`, a channel has a different number of frames than the first one.
+    WrongNumberOfInputFrames {
+        channel: usize,
+        expected: usize,
+        actual: usize,
+    },
+    /// A channel of the output buffer is shorter than given by "output_frames_max".
+    InsufficientOutputBufferSize {
+        channel: usize,
+        expected: usize,
+        actual: usize,
+    },
+    /// An interleaved input does not have the number of samples needed,
+    /// or for a partial chunk, has too many samples or is not a whole number of frames.
+    WrongNumberOfInputSamples { expected: usize, actual: usize },
+    /// An interleaved output buffer is too short.
+    InsufficientOutputSamples { expected: usize, actual: usize },
+    /// The requested resample ratio is outside the range supported by the resampler.
+    RatioOutOfBounds { provided: f64, min: f64, max: f64 },
+    /// The operation is not possible with a synchronous resampler.
+    SyncNotAdjustable,
+    /// A parameter given when creating a resampler is not valid.
+    InvalidParameter { name: &'static str, reason: String },
+}
+
+impl fmt::Display for ResampleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResampleError::WrongNumberOfInputChannels { expected, actual } => write!(
+                f,
+                "Wrong number of channels in input, expected {}, got {}",
+                expected, actual
+            ),
+            ResampleError::WrongNumberOfOutputChannels { expected, actual } => write!(
+                f,
+                "Wrong number of channels in output, expected {}, got {}",
+                expected, actual
+            ),
+            ResampleError::WrongNumberOfMaskChannels { expected, actual } => write!(
+                f,
+                "Wrong number of channels in mask, expected {}, got {}",
+                expected, actual
+            ),
+            ResampleError::WrongNumberOfInputFrames {
+                channel,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Wrong number of frames in input channel {}, expected {}, got {}",
+                channel, expected, actual
+            ),
+            ResampleError::InsufficientOutputBufferSize {
+                channel,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Output buffer for channel {} too small, expected at least {} frames, got {}",
+                channel, expected, actual
+            ),
+            ResampleError::WrongNumberOfInputSamples { expected, actual } => write!(
+                f,
+                "Wrong number of samples in input, expected {}, got {}",
+                expected, actual
+            ),
+            ResampleError::InsufficientOutputSamples { expected, actual } => write!(
+                f,
+                "Output buffer too small, expected at least {} samples, got {}",
+                expected, actual
+            ),
+            ResampleError::RatioOutOfBounds { provided, min, max } => write!(
+                f,
+                "Resample ratio {} is out of bounds, must be between {} and {}",
+                provided, min, max
+            ),
+            ResampleError::SyncNotAdjustable => {
+                write!(f, "Not possible to adjust a synchronous resampler")
+            }
+            ResampleError::InvalidParameter { name, reason } => {
+                write!(f, "Invalid value for parameter {}: {}", name, reason)
+            }
+        }
+    }
+}
+
+impl error::Error for ResampleError {}
+
+impl ResampleError {
+    /// Helper for creating an `InvalidParameter` error.
+    pub(crate) fn invalid_parameter(name: &'static str, reason: &str) -> Self {
+        ResampleError::InvalidParameter {
+            name,
+            reason: reason.to_owned(),
+        }
+    }
+}
+
+/// Result type returned by the resamplers.
+pub type ResampleResult<T> = Result<T, ResampleError>;
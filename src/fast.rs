@@ -0,0 +1,848 @@
+use crate::interpolation::*;
+use crate::{
+    channel_is_active, copy_and_pad, input_channel, nbr_input_frames, validate_buffers,
+    validate_ratio_parameters, InterpolationType, ResampleError, ResampleResult, Resampler,
+};
+use num_traits::Float;
+
+/// The number of input frames kept from the previous chunk.
+/// This is enough for the points needed by any of the interpolation types.
+const HISTORY_LEN: usize = 8;
+
+/// An asynchronous resampler that accepts a fixed number of audio frames for input
+/// and returns a variable number of frames.
+///
+/// The new samples are calculated by interpolating directly between the input samples,
+/// without any sinc interpolation. This makes it much faster than `SincFixedIn`,
+/// but there is no anti-aliasing filter. This is fine for signals that have little
+/// content near the Nyquist frequency, like voice prompts or control signals,
+/// but gives audible artefacts for full bandwidth audio.
+/// When downsampling, any content above the new Nyquist frequency is aliased.
+///
+/// The interpolation is selected with `InterpolationType`.
+/// `Nearest` simply picks the nearest input sample, `Linear` interpolates between the two nearest,
+/// while `Cubic` and `Hermite` use the four nearest and `Quintic` the six nearest samples.
+pub struct FastFixedIn<T> {
+    nbr_channels: usize,
+    chunk_size: usize,
+    last_index: f64,
+    current_buffer_fill: usize,
+    resample_ratio: f64,
+    target_ratio: f64,
+    resample_ratio_original: f64,
+    max_resample_ratio_relative: f64,
+    interpolation: InterpolationType,
+    buffer: Vec<Vec<T>>,
+}
+
+/// An asynchronous resampler that returns a fixed number of audio frames.
+/// The number of input frames required is given by the frames_needed function.
+///
+/// Like `FastFixedIn`, this interpolates directly between the input samples
+/// without any sinc interpolation or anti-aliasing filter.
+pub struct FastFixedOut<T> {
+    nbr_channels: usize,
+    chunk_size: usize,
+    needed_input_size: usize,
+    last_index: f64,
+    current_buffer_fill: usize,
+    resample_ratio: f64,
+    target_ratio: f64,
+    resample_ratio_original: f64,
+    max_resample_ratio_relative: f64,
+    interpolation: InterpolationType,
+    buffer: Vec<Vec<T>>,
+}
+
+/// Check that a new resample ratio is within the range given when creating the resampler.
+fn check_ratio_bounds(
+    resample_ratio_original: f64,
+    max_resample_ratio_relative: f64,
+    new_ratio: f64,
+) -> ResampleResult<()> {
+    let min = resample_ratio_original / max_resample_ratio_relative;
+    let max = resample_ratio_original * max_resample_ratio_relative;
+    if new_ratio >= min && new_ratio <= max {
+        Ok(())
+    } else {
+        Err(ResampleError::RatioOutOfBounds {
+            provided: new_ratio,
+            min,
+            max,
+        })
+    }
+}
+
+/// Resample a single channel from the buffer `buf`,
+/// writing one new sample to each element of `wave_out`.
+/// The first new sample is calculated at time `idx + t_ratio`,
+/// and the step is increased by `t_ratio_increment` after each sample.
+fn resample_channel<T: Float>(
+    interpolation: &InterpolationType,
+    buf: &[T],
+    wave_out: &mut [T],
+    mut idx: f64,
+    mut t_ratio: f64,
+    t_ratio_increment: f64,
+) {
+    let offset = HISTORY_LEN as isize;
+    match interpolation {
+        InterpolationType::Quintic => {
+            let mut points = [T::zero(); 6];
+            let mut nearest = [(0isize, 0isize); 6];
+            for sample in wave_out.iter_mut() {
+                idx += t_ratio;
+                t_ratio += t_ratio_increment;
+                get_nearest_times_6(idx, 1, &mut nearest);
+                let frac = T::from(idx - idx.floor()).unwrap();
+                for (n, p) in nearest.iter().zip(points.iter_mut()) {
+                    *p = buf[(n.0 + offset) as usize];
+                }
+                *sample = interp_quintic(frac, &points);
+            }
+        }
+        InterpolationType::Cubic | InterpolationType::Hermite => {
+            let mut points = [T::zero(); 4];
+            let mut nearest = [(0isize, 0isize); 4];
+            for sample in wave_out.iter_mut() {
+                idx += t_ratio;
+                t_ratio += t_ratio_increment;
+                get_nearest_times_4(idx, 1, &mut nearest);
+                let frac = T::from(idx - idx.floor()).unwrap();
+                for (n, p) in nearest.iter().zip(points.iter_mut()) {
+                    *p = buf[(n.0 + offset) as usize];
+                }
+                *sample = match interpolation {
+                    InterpolationType::Hermite => interp_hermite(frac, &points),
+                    _ => interp_cubic(frac, &points),
+                };
+            }
+        }
+        InterpolationType::Linear => {
+            let mut points = [T::zero(); 2];
+            let mut nearest = [(0isize, 0isize); 2];
+            for sample in wave_out.iter_mut() {
+                idx += t_ratio;
+                t_ratio += t_ratio_increment;
+                get_nearest_times_2(idx, 1, &mut nearest);
+                let frac = T::from(idx - idx.floor()).unwrap();
+                for (n, p) in nearest.iter().zip(points.iter_mut()) {
+                    *p = buf[(n.0 + offset) as usize];
+                }
+                *sample = interp_lin(frac, &points);
+            }
+        }
+        InterpolationType::Nearest => {
+            for sample in wave_out.iter_mut() {
+                idx += t_ratio;
+                t_ratio += t_ratio_increment;
+                let nearest = get_nearest_time(idx, 1);
+                *sample = buf[(nearest.0 + offset) as usize];
+            }
+        }
+    }
+}
+
+impl<T: Float> FastFixedIn<T> {
+    /// Create a new FastFixedIn
+    ///
+    /// Parameters are:
+    /// - `resample_ratio`: Ratio between output and input sample rates.
+    /// - `max_resample_ratio_relative`: Maximum ratio that the resample ratio can be changed by,
+    ///   relative to `resample_ratio`. Must be at least 1.0.
+    /// - `interpolation`: Interpolation type, see `InterpolationType`
+    /// - `chunk_size`: size of input data in frames
+    /// - `nbr_channels`: number of channels in input/output
+    ///
+    /// # Panics
+    ///
+    /// Panics if any of the parameters is invalid, see `try_new`.
+    pub fn new(
+        resample_ratio: f64,
+        max_resample_ratio_relative: f64,
+        interpolation: InterpolationType,
+        chunk_size: usize,
+        nbr_channels: usize,
+    ) -> Self {
+        Self::try_new(
+            resample_ratio,
+            max_resample_ratio_relative,
+            interpolation,
+            chunk_size,
+            nbr_channels,
+        )
+        .unwrap()
+    }
+
+    /// Create a new FastFixedIn, checking that the parameters are valid.
+    /// Takes the same parameters as `new`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidParameter` error if the resample ratio is not a positive number,
+    /// if the maximum relative resample ratio is less than 1.0,
+    /// or if the chunk size or number of channels is zero.
+    pub fn try_new(
+        resample_ratio: f64,
+        max_resample_ratio_relative: f64,
+        interpolation: InterpolationType,
+        chunk_size: usize,
+        nbr_channels: usize,
+    ) -> ResampleResult<Self> {
+        validate_ratio_parameters(
+            resample_ratio,
+            max_resample_ratio_relative,
+            chunk_size,
+            nbr_channels,
+        )?;
+        debug!(
+            "Create new FastFixedIn, ratio: {}, max relative ratio: {}, chunk_size: {}, channels: {}, interpolation: {:?}",
+            resample_ratio, max_resample_ratio_relative, chunk_size, nbr_channels, interpolation
+        );
+        let buffer = vec![vec![T::zero(); chunk_size + HISTORY_LEN]; nbr_channels];
+        Ok(FastFixedIn {
+            nbr_channels,
+            chunk_size,
+            last_index: -1.0 / resample_ratio,
+            current_buffer_fill: chunk_size,
+            resample_ratio,
+            target_ratio: resample_ratio,
+            resample_ratio_original: resample_ratio,
+            max_resample_ratio_relative,
+            interpolation,
+            buffer,
+        })
+    }
+
+    /// Set a new resample ratio, that is either used directly or ramped to during the next chunk.
+    fn update_resample_ratio(&mut self, new_ratio: f64, ramp: bool) -> ResampleResult<()> {
+        trace!("Change resample ratio to {}, ramp: {}", new_ratio, ramp);
+        check_ratio_bounds(
+            self.resample_ratio_original,
+            self.max_resample_ratio_relative,
+            new_ratio,
+        )?;
+        self.target_ratio = new_ratio;
+        if !ramp {
+            self.resample_ratio = new_ratio;
+        }
+        Ok(())
+    }
+
+    /// Resample `frames_in` frames of audio from input buffers that have already been validated.
+    /// Input channels that are shorter than `frames_in` are padded with zeros.
+    fn resample_chunk<Vin: AsRef<[T]>, Vout: AsMut<[T]>>(
+        &mut self,
+        wave_in: Option<&[Vin]>,
+        wave_out: &mut [Vout],
+        active_channels_mask: Option<&[bool]>,
+        frames_in: usize,
+    ) -> usize {
+        // the last new sample must have three input frames after it, for quintic interpolation
+        let end_idx = frames_in as f64 - 3.0;
+        //update buffer with new data
+        for chan in 0..self.nbr_channels {
+            if channel_is_active(active_channels_mask, chan) {
+                let buf = &mut self.buffer[chan];
+                buf.copy_within(
+                    self.current_buffer_fill..(self.current_buffer_fill + HISTORY_LEN),
+                    0,
+                );
+                copy_and_pad(
+                    input_channel(wave_in, chan),
+                    &mut buf[HISTORY_LEN..(HISTORY_LEN + frames_in)],
+                );
+            }
+        }
+        self.current_buffer_fill = frames_in;
+
+        // When ramping, the step is changed linearly so that it reaches
+        // the step of the target ratio after the expected number of output frames.
+        let t_ratio = 1.0 / self.resample_ratio;
+        let t_ratio_end = 1.0 / self.target_ratio;
+        let approximate_nbr_frames = (2.0 * frames_in as f64 / (t_ratio + t_ratio_end)).max(1.0);
+        let t_ratio_increment = (t_ratio_end - t_ratio) / approximate_nbr_frames;
+        let mut idx = self.last_index;
+        let mut t_ratio_current = t_ratio;
+        let mut nbr_frames = 0;
+        while idx + t_ratio_current < end_idx {
+            idx += t_ratio_current;
+            t_ratio_current += t_ratio_increment;
+            nbr_frames += 1;
+        }
+
+        for (chan, wav) in wave_out.iter_mut().enumerate() {
+            if channel_is_active(active_channels_mask, chan) {
+                resample_channel(
+                    &self.interpolation,
+                    &self.buffer[chan],
+                    &mut wav.as_mut()[..nbr_frames],
+                    self.last_index,
+                    t_ratio,
+                    t_ratio_increment,
+                );
+            }
+        }
+
+        // store last index for next iteration
+        self.last_index = idx - frames_in as f64;
+        self.resample_ratio = self.target_ratio;
+        trace!(
+            "Resampling, {} frames in, {} frames out",
+            frames_in,
+            nbr_frames,
+        );
+        nbr_frames
+    }
+}
+
+impl<T: Float> Resampler<T> for FastFixedIn<T> {
+    /// Resample a chunk of audio. The input length is fixed, and the output varies in length.
+    /// # Errors
+    ///
+    /// The function returns an error if the length of the input data is not equal
+    /// to the number of channels and chunk size defined when creating the instance,
+    /// or if the output buffers are shorter than given by "output_frames_max".
+    fn process_into_buffer<Vin: AsRef<[T]>, Vout: AsMut<[T]>>(
+        &mut self,
+        wave_in: &[Vin],
+        wave_out: &mut [Vout],
+        active_channels_mask: Option<&[bool]>,
+    ) -> ResampleResult<usize> {
+        validate_buffers(
+            Some(wave_in),
+            wave_out,
+            active_channels_mask,
+            self.nbr_channels,
+            self.chunk_size,
+            self.output_frames_max(),
+            false,
+        )?;
+        Ok(self.resample_chunk(
+            Some(wave_in),
+            wave_out,
+            active_channels_mask,
+            self.chunk_size,
+        ))
+    }
+
+    /// Resample a chunk of audio that may be shorter than the chunk size.
+    /// Only the given frames are consumed, and the output contains
+    /// the frames that correspond to them. If the channels have different lengths,
+    /// the shorter ones are padded with zeros to the length of the longest.
+    /// If `None` is given, a full chunk of zeros is processed.
+    /// # Errors
+    ///
+    /// The function returns an error if the input data does not have the number of channels
+    /// defined when creating the instance, if it is longer than the chunk size,
+    /// or if the output buffers are shorter than given by "output_frames_max".
+    fn process_partial_into_buffer<Vin: AsRef<[T]>, Vout: AsMut<[T]>>(
+        &mut self,
+        wave_in: Option<&[Vin]>,
+        wave_out: &mut [Vout],
+        active_channels_mask: Option<&[bool]>,
+    ) -> ResampleResult<usize> {
+        validate_buffers(
+            wave_in,
+            wave_out,
+            active_channels_mask,
+            self.nbr_channels,
+            self.chunk_size,
+            self.output_frames_max(),
+            true,
+        )?;
+        let frames_in = nbr_input_frames(wave_in, active_channels_mask, self.chunk_size);
+        Ok(self.resample_chunk(wave_in, wave_out, active_channels_mask, frames_in))
+    }
+
+    /// Update the resample ratio. The new value must be within the range given by
+    /// the `max_resample_ratio_relative` used when creating the resampler.
+    /// # Errors
+    ///
+    /// Returns a `RatioOutOfBounds` error if the new ratio is outside this range.
+    fn set_resample_ratio(&mut self, new_ratio: f64) -> ResampleResult<()> {
+        self.update_resample_ratio(new_ratio, false)
+    }
+
+    /// Update the resample ratio relative to the original one
+    fn set_resample_ratio_relative(&mut self, rel_ratio: f64) -> ResampleResult<()> {
+        let new_ratio = self.resample_ratio_original * rel_ratio;
+        self.set_resample_ratio(new_ratio)
+    }
+
+    /// Update the resample ratio, with the same limits as `set_resample_ratio`.
+    /// The ratio changes linearly over the output frames of the next chunk.
+    /// # Errors
+    ///
+    /// Returns a `RatioOutOfBounds` error if the new ratio is out of range.
+    fn set_resample_ratio_ramped(&mut self, new_ratio: f64) -> ResampleResult<()> {
+        self.update_resample_ratio(new_ratio, true)
+    }
+
+    /// Update the resample ratio relative to the original one, with a ramp
+    fn set_resample_ratio_relative_ramped(&mut self, rel_ratio: f64) -> ResampleResult<()> {
+        let new_ratio = self.resample_ratio_original * rel_ratio;
+        self.set_resample_ratio_ramped(new_ratio)
+    }
+
+    /// Query for the number of frames needed for the next call to "process".
+    /// Will always return the chunk_size defined when creating the instance.
+    fn nbr_frames_needed(&self) -> usize {
+        self.chunk_size
+    }
+
+    /// Query for the maximum number of frames that a call to "process" can require.
+    /// Will always return the chunk_size defined when creating the instance.
+    fn input_frames_max(&self) -> usize {
+        self.chunk_size
+    }
+
+    /// Query for the maximum number of frames that a call to "process" can return.
+    /// This takes into account that the resample ratio can be increased
+    /// up to the limit given by `max_resample_ratio_relative`.
+    fn output_frames_max(&self) -> usize {
+        (self.chunk_size as f64 * self.resample_ratio_original * self.max_resample_ratio_relative)
+            as usize
+            + 10
+    }
+
+    /// Query for the number of channels this resampler was created for.
+    fn nbr_channels(&self) -> usize {
+        self.nbr_channels
+    }
+
+    /// Get the current resample ratio.
+    /// During a ramp, this is the ratio at the start of the next chunk.
+    fn resample_ratio(&self) -> f64 {
+        self.resample_ratio
+    }
+
+    /// Get the delay of the resampler, in output frames.
+    /// The first output frame is calculated at the time of the first input frame,
+    /// so there is no delay.
+    fn output_delay(&self) -> f64 {
+        0.0
+    }
+
+    /// Reset the resampler to the state it had when it was created.
+    fn reset(&mut self) {
+        for buf in self.buffer.iter_mut() {
+            buf.iter_mut().for_each(|val| *val = T::zero());
+        }
+        self.last_index = -1.0 / self.resample_ratio_original;
+        self.current_buffer_fill = self.chunk_size;
+        self.resample_ratio = self.resample_ratio_original;
+        self.target_ratio = self.resample_ratio_original;
+    }
+}
+
+impl<T: Float> FastFixedOut<T> {
+    /// Create a new FastFixedOut
+    ///
+    /// Parameters are:
+    /// - `resample_ratio`: Ratio between output and input sample rates.
+    /// - `max_resample_ratio_relative`: Maximum ratio that the resample ratio can be changed by,
+    ///   relative to `resample_ratio`. Must be at least 1.0.
+    /// - `interpolation`: Interpolation type, see `InterpolationType`
+    /// - `chunk_size`: size of output data in frames
+    /// - `nbr_channels`: number of channels in input/output
+    ///
+    /// # Panics
+    ///
+    /// Panics if any of the parameters is invalid, see `try_new`.
+    pub fn new(
+        resample_ratio: f64,
+        max_resample_ratio_relative: f64,
+        interpolation: InterpolationType,
+        chunk_size: usize,
+        nbr_channels: usize,
+    ) -> Self {
+        Self::try_new(
+            resample_ratio,
+            max_resample_ratio_relative,
+            interpolation,
+            chunk_size,
+            nbr_channels,
+        )
+        .unwrap()
+    }
+
+    /// Create a new FastFixedOut, checking that the parameters are valid.
+    /// Takes the same parameters as `new`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidParameter` error if the resample ratio is not a positive number,
+    /// if the maximum relative resample ratio is less than 1.0,
+    /// or if the chunk size or number of channels is zero.
+    pub fn try_new(
+        resample_ratio: f64,
+        max_resample_ratio_relative: f64,
+        interpolation: InterpolationType,
+        chunk_size: usize,
+        nbr_channels: usize,
+    ) -> ResampleResult<Self> {
+        validate_ratio_parameters(
+            resample_ratio,
+            max_resample_ratio_relative,
+            chunk_size,
+            nbr_channels,
+        )?;
+        debug!(
+            "Create new FastFixedOut, ratio: {}, max relative ratio: {}, chunk_size: {}, channels: {}, interpolation: {:?}",
+            resample_ratio, max_resample_ratio_relative, chunk_size, nbr_channels, interpolation
+        );
+        let input_frames_max =
+            (chunk_size as f64 * max_resample_ratio_relative / resample_ratio).ceil() as usize + 4;
+        let buffer = vec![vec![T::zero(); input_frames_max + HISTORY_LEN]; nbr_channels];
+        let mut resampler = FastFixedOut {
+            nbr_channels,
+            chunk_size,
+            needed_input_size: 0,
+            last_index: -1.0 / resample_ratio,
+            current_buffer_fill: 0,
+            resample_ratio,
+            target_ratio: resample_ratio,
+            resample_ratio_original: resample_ratio,
+            max_resample_ratio_relative,
+            interpolation,
+            buffer,
+        };
+        resampler.needed_input_size = resampler.calculate_needed_input_size();
+        resampler.current_buffer_fill = resampler.needed_input_size;
+        Ok(resampler)
+    }
+
+    /// Set a new resample ratio, that is either used directly or ramped to during the next chunk.
+    fn update_resample_ratio(&mut self, new_ratio: f64, ramp: bool) -> ResampleResult<()> {
+        trace!("Change resample ratio to {}, ramp: {}", new_ratio, ramp);
+        check_ratio_bounds(
+            self.resample_ratio_original,
+            self.max_resample_ratio_relative,
+            new_ratio,
+        )?;
+        self.target_ratio = new_ratio;
+        if !ramp {
+            self.resample_ratio = new_ratio;
+        }
+        self.needed_input_size = self.calculate_needed_input_size();
+        Ok(())
+    }
+
+    /// Get the step between output frames at the start of the next chunk,
+    /// and the increment of the step for each output frame.
+    /// When ramping, the step reaches the step of the target ratio at the end of the chunk.
+    fn ramp_steps(&self) -> (f64, f64) {
+        let t_ratio = 1.0 / self.resample_ratio;
+        let t_ratio_end = 1.0 / self.target_ratio;
+        (t_ratio, (t_ratio_end - t_ratio) / self.chunk_size as f64)
+    }
+
+    /// Get the time of the last output frame of the next chunk.
+    fn last_frame_time(&self) -> f64 {
+        let (t_ratio, t_ratio_increment) = self.ramp_steps();
+        let mut idx = self.last_index;
+        let mut t_ratio_current = t_ratio;
+        for _ in 0..self.chunk_size {
+            idx += t_ratio_current;
+            t_ratio_current += t_ratio_increment;
+        }
+        idx
+    }
+
+    /// Calculate the number of input frames needed for the next chunk.
+    /// The last output frame needs three input frames after it, for quintic interpolation.
+    fn calculate_needed_input_size(&self) -> usize {
+        (self.last_frame_time().floor() as isize + 4).max(0) as usize
+    }
+
+    /// Resample a chunk of audio from input buffers that have already been validated.
+    /// Input channels that are shorter than the needed length are padded with zeros.
+    fn resample_chunk<Vin: AsRef<[T]>, Vout: AsMut<[T]>>(
+        &mut self,
+        wave_in: Option<&[Vin]>,
+        wave_out: &mut [Vout],
+        active_channels_mask: Option<&[bool]>,
+    ) {
+        //update buffer with new data
+        for chan in 0..self.nbr_channels {
+            if channel_is_active(active_channels_mask, chan) {
+                let buf = &mut self.buffer[chan];
+                buf.copy_within(
+                    self.current_buffer_fill..(self.current_buffer_fill + HISTORY_LEN),
+                    0,
+                );
+                copy_and_pad(
+                    input_channel(wave_in, chan),
+                    &mut buf[HISTORY_LEN..(HISTORY_LEN + self.needed_input_size)],
+                );
+            }
+        }
+        self.current_buffer_fill = self.needed_input_size;
+
+        let (t_ratio, t_ratio_increment) = self.ramp_steps();
+        let idx = self.last_frame_time();
+
+        for (chan, wav) in wave_out.iter_mut().enumerate() {
+            if channel_is_active(active_channels_mask, chan) {
+                resample_channel(
+                    &self.interpolation,
+                    &self.buffer[chan],
+                    &mut wav.as_mut()[..self.chunk_size],
+                    self.last_index,
+                    t_ratio,
+                    t_ratio_increment,
+                );
+            }
+        }
+
+        // store last index for next iteration
+        self.last_index = idx - self.current_buffer_fill as f64;
+        self.resample_ratio = self.target_ratio;
+        self.needed_input_size = self.calculate_needed_input_size();
+        trace!(
+            "Resampling, {} frames in, {} frames out. Next needed length: {} frames",
+            self.current_buffer_fill,
+            self.chunk_size,
+            self.needed_input_size,
+        );
+    }
+}
+
+impl<T: Float> Resampler<T> for FastFixedOut<T> {
+    /// Resample a chunk of audio. The required input length is provided by
+    /// the "nbr_frames_needed" function, and the output length is fixed.
+    /// # Errors
+    ///
+    /// The function returns an error if the length of the input data is not
+    /// equal to the number of channels defined when creating the instance,
+    /// and the number of audio frames given by "nbr_frames_needed",
+    /// or if the output buffers are shorter than the chunk size.
+    fn process_into_buffer<Vin: AsRef<[T]>, Vout: AsMut<[T]>>(
+        &mut self,
+        wave_in: &[Vin],
+        wave_out: &mut [Vout],
+        active_channels_mask: Option<&[bool]>,
+    ) -> ResampleResult<usize> {
+        validate_buffers(
+            Some(wave_in),
+            wave_out,
+            active_channels_mask,
+            self.nbr_channels,
+            self.needed_input_size,
+            self.chunk_size,
+            false,
+        )?;
+        self.resample_chunk(Some(wave_in), wave_out, active_channels_mask);
+        Ok(self.chunk_size)
+    }
+
+    /// Resample a chunk of audio that may be shorter than given by "nbr_frames_needed".
+    /// The input is padded with zeros to the needed length.
+    /// # Errors
+    ///
+    /// The function returns an error if the input data does not have the number of channels
+    /// defined when creating the instance, if it is longer than given by "nbr_frames_needed",
+    /// or if the output buffers are shorter than the chunk size.
+    fn process_partial_into_buffer<Vin: AsRef<[T]>, Vout: AsMut<[T]>>(
+        &mut self,
+        wave_in: Option<&[Vin]>,
+        wave_out: &mut [Vout],
+        active_channels_mask: Option<&[bool]>,
+    ) -> ResampleResult<usize> {
+        validate_buffers(
+            wave_in,
+            wave_out,
+            active_channels_mask,
+            self.nbr_channels,
+            self.needed_input_size,
+            self.chunk_size,
+            true,
+        )?;
+        self.resample_chunk(wave_in, wave_out, active_channels_mask);
+        Ok(self.chunk_size)
+    }
+
+    /// Update the resample ratio. The new value must be within the range given by
+    /// the `max_resample_ratio_relative` used when creating the resampler.
+    /// # Errors
+    ///
+    /// Returns a `RatioOutOfBounds` error if the new ratio is outside this range.
+    fn set_resample_ratio(&mut self, new_ratio: f64) -> ResampleResult<()> {
+        self.update_resample_ratio(new_ratio, false)
+    }
+
+    /// Update the resample ratio relative to the original one
+    fn set_resample_ratio_relative(&mut self, rel_ratio: f64) -> ResampleResult<()> {
+        let new_ratio = self.resample_ratio_original * rel_ratio;
+        self.set_resample_ratio(new_ratio)
+    }
+
+    /// Update the resample ratio, with the same limits as `set_resample_ratio`.
+    /// The ratio changes linearly over the output frames of the next chunk,
+    /// and "nbr_frames_needed" returns the number of input frames needed for the ramped chunk.
+    /// # Errors
+    ///
+    /// Returns a `RatioOutOfBounds` error if the new ratio is out of range.
+    fn set_resample_ratio_ramped(&mut self, new_ratio: f64) -> ResampleResult<()> {
+        self.update_resample_ratio(new_ratio, true)
+    }
+
+    /// Update the resample ratio relative to the original one, with a ramp
+    fn set_resample_ratio_relative_ramped(&mut self, rel_ratio: f64) -> ResampleResult<()> {
+        let new_ratio = self.resample_ratio_original * rel_ratio;
+        self.set_resample_ratio_ramped(new_ratio)
+    }
+
+    /// Query for the number of frames needed for the next call to "process".
+    fn nbr_frames_needed(&self) -> usize {
+        self.needed_input_size
+    }
+
+    /// Query for the maximum number of frames that a call to "process" can require.
+    /// This takes into account that the resample ratio can be decreased
+    /// down to the limit given by `max_resample_ratio_relative`.
+    fn input_frames_max(&self) -> usize {
+        (self.chunk_size as f64 * self.max_resample_ratio_relative / self.resample_ratio_original)
+            .ceil() as usize
+            + 4
+    }
+
+    /// Query for the maximum number of frames that a call to "process" can return.
+    /// Will always return the chunk_size defined when creating the instance.
+    fn output_frames_max(&self) -> usize {
+        self.chunk_size
+    }
+
+    /// Query for the number of channels this resampler was created for.
+    fn nbr_channels(&self) -> usize {
+        self.nbr_channels
+    }
+
+    /// Get the current resample ratio.
+    /// During a ramp, this is the ratio at the start of the next chunk.
+    fn resample_ratio(&self) -> f64 {
+        self.resample_ratio
+    }
+
+    /// Get the delay of the resampler, in output frames.
+    /// The first output frame is calculated at the time of the first input frame,
+    /// so there is no delay.
+    fn output_delay(&self) -> f64 {
+        0.0
+    }
+
+    /// Reset the resampler to the state it had when it was created.
+    fn reset(&mut self) {
+        for buf in self.buffer.iter_mut() {
+            buf.iter_mut().for_each(|val| *val = T::zero());
+        }
+        self.last_index = -1.0 / self.resample_ratio_original;
+        self.resample_ratio = self.resample_ratio_original;
+        self.target_ratio = self.resample_ratio_original;
+        self.needed_input_size = self.calculate_needed_input_size();
+        self.current_buffer_fill = self.needed_input_size;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{FastFixedIn, FastFixedOut, InterpolationType, ResampleError, Resampler};
+
+    /// Resample a slow sine with the given resampler, feeding it the number of frames it asks for,
+    /// and return the largest difference between the output and an ideal sine.
+    fn max_sine_error<R: Resampler<f64>>(resampler: &mut R, ratio: f64) -> f64 {
+        let freq = 0.01;
+        let mut output = Vec::new();
+        let mut frame = 0;
+        for _ in 0..20 {
+            let frames = resampler.nbr_frames_needed();
+            let wave: Vec<f64> = (frame..frame + frames)
+                .map(|n| (2.0 * std::f64::consts::PI * freq * n as f64).sin())
+                .collect();
+            frame += frames;
+            output.extend(resampler.process(&[wave]).unwrap().remove(0));
+        }
+        // skip the first frames, where the history is zeros
+        output
+            .iter()
+            .enumerate()
+            .skip(10)
+            .map(|(n, val)| {
+                let expected = (2.0 * std::f64::consts::PI * freq * n as f64 / ratio).sin();
+                (val - expected).abs()
+            })
+            .fold(0.0, f64::max)
+    }
+
+    #[test]
+    fn make_resampler_fi() {
+        let mut resampler = FastFixedIn::<f64>::new(1.2, 1.1, InterpolationType::Cubic, 1024, 2);
+        let waves = vec![vec![0.0f64; 1024]; 2];
+        let out = resampler.process(&waves).unwrap();
+        assert_eq!(out.len(), 2);
+        assert!(out[0].len() > 1150 && out[0].len() < 1250);
+    }
+
+    #[test]
+    fn make_resampler_fo() {
+        let mut resampler = FastFixedOut::<f32>::new(1.2, 1.1, InterpolationType::Cubic, 1024, 2);
+        let frames = resampler.nbr_frames_needed();
+        assert!(frames > 800 && frames < 900);
+        let waves = vec![vec![0.0f32; frames]; 2];
+        let out = resampler.process(&waves).unwrap();
+        assert_eq!(out.len(), 2);
+        assert_eq!(out[0].len(), 1024);
+    }
+
+    #[test]
+    fn interpolation_accuracy() {
+        let limits = [
+            (InterpolationType::Nearest, 0.04),
+            (InterpolationType::Linear, 1.0e-3),
+            (InterpolationType::Hermite, 1.0e-5),
+            (InterpolationType::Cubic, 1.0e-6),
+            (InterpolationType::Quintic, 1.0e-9),
+        ];
+        for (interpolation, limit) in limits.iter() {
+            for ratio in [1.2345, 0.789].iter() {
+                let mut resampler = FastFixedIn::<f64>::new(*ratio, 1.1, *interpolation, 256, 1);
+                assert!(max_sine_error(&mut resampler, *ratio) < *limit);
+                let mut resampler = FastFixedOut::<f64>::new(*ratio, 1.1, *interpolation, 256, 1);
+                assert!(max_sine_error(&mut resampler, *ratio) < *limit);
+            }
+        }
+    }
+
+    #[test]
+    fn change_ratio() {
+        let mut resampler = FastFixedOut::<f64>::new(1.0, 2.0, InterpolationType::Linear, 1024, 1);
+        assert_eq!(resampler.nbr_frames_needed(), 1027);
+        resampler.set_resample_ratio(0.5).unwrap();
+        assert_eq!(resampler.nbr_frames_needed(), 2051);
+        assert!(resampler.nbr_frames_needed() <= resampler.input_frames_max());
+        match resampler.set_resample_ratio(0.4) {
+            Err(ResampleError::RatioOutOfBounds { .. }) => {}
+            _ => panic!("expected a RatioOutOfBounds error"),
+        }
+        // a ramp from 0.5 to 2.0 consumes as many input frames as the average step
+        resampler.set_resample_ratio_ramped(2.0).unwrap();
+        let frames = resampler.nbr_frames_needed();
+        assert!(frames > 1270 && frames < 1290);
+        let wave = vec![0.0; frames];
+        resampler.process(&[wave]).unwrap();
+        assert_eq!(resampler.nbr_frames_needed(), 512);
+    }
+
+    #[test]
+    fn reset_fast() {
+        let mut resampler = FastFixedIn::<f64>::new(1.2, 1.1, InterpolationType::Quintic, 100, 1);
+        let wave: Vec<f64> = (0..100).map(|n| (0.1 * n as f64).sin()).collect();
+        let first = resampler.process(&[&wave]).unwrap();
+        resampler.set_resample_ratio(1.3).unwrap();
+        resampler.process(&[&wave]).unwrap();
+        resampler.reset();
+        let second = resampler.process(&[&wave]).unwrap();
+        assert_eq!(first, second);
+    }
+}
@@ -0,0 +1,348 @@
+use crate::Resampler;
+use crate::{ResampleError, ResampleResult};
+use num_traits::Float;
+
+/// A wrapper that lets any resampler process interleaved audio data.
+///
+/// The samples of all channels are stored in a single slice, frame by frame,
+/// like `[l0, r0, l1, r1, ...]` for a stereo signal.
+/// The wrapper keeps one set of channel buffers that is allocated when it's created,
+/// and no memory is allocated during processing.
+///
+/// Note that the data is still copied: each chunk is deinterleaved into the channel buffers
+/// before resampling, and the result is interleaved into the output afterwards.
+/// The wrapper only saves doing these two copies by hand.
+pub struct InterleavedResampler<T, R> {
+    resampler: R,
+    buffer_in: Vec<Vec<T>>,
+    buffer_out: Vec<Vec<T>>,
+}
+
+impl<T: Float, R: Resampler<T>> InterleavedResampler<T, R> {
+    /// Create a new InterleavedResampler wrapping the given resampler.
+    pub fn new(resampler: R) -> Self {
+        let nbr_channels = resampler.nbr_channels();
+        let frames_in = resampler.input_frames_max();
+        let frames_out = resampler.output_frames_max();
+        debug!(
+            "Create new InterleavedResampler, channels: {}, max frames in: {}, max frames out: {}",
+            nbr_channels, frames_in, frames_out
+        );
+        let buffer_in = (0..nbr_channels)
+            .map(|_| Vec::with_capacity(frames_in))
+            .collect();
+        let buffer_out = vec![vec![T::zero(); frames_out]; nbr_channels];
+        InterleavedResampler {
+            resampler,
+            buffer_in,
+            buffer_out,
+        }
+    }
+
+    /// Resample a chunk of interleaved audio, and write the interleaved result to `wave_out`.
+    /// The input must contain exactly "nbr_frames_needed" frames,
+    /// and the output must have room for at least "output_frames_max" frames.
+    /// Returns the number of frames that were written.
+    /// # Errors
+    ///
+    /// The function returns an error if the length of the input is not equal to the number of frames
+    /// needed times the number of channels, if the output is too short,
+    /// or if the wrapped resampler returns an error.
+    pub fn process_into_buffer(
+        &mut self,
+        wave_in: &[T],
+        wave_out: &mut [T],
+    ) -> ResampleResult<usize> {
+        let nbr_channels = self.buffer_in.len();
+        let frames_in = self.resampler.nbr_frames_needed();
+        if wave_in.len() != frames_in * nbr_channels {
+            return Err(ResampleError::WrongNumberOfInputSamples {
+                expected: frames_in * nbr_channels,
+                actual: wave_in.len(),
+            });
+        }
+        self.check_output_length(wave_out)?;
+        self.deinterleave_input(wave_in);
+        let frames_out =
+            self.resampler
+                .process_into_buffer(&self.buffer_in, &mut self.buffer_out, None)?;
+        self.interleave_output(wave_out, frames_out);
+        Ok(frames_out)
+    }
+
+    /// Resample a chunk of interleaved audio that may be shorter than a full chunk,
+    /// and write the interleaved result to `wave_out`.
+    /// The input may contain fewer frames than given by "nbr_frames_needed",
+    /// and the missing frames are filled with zeros.
+    /// If `None` is given, the entire chunk is filled with zeros.
+    /// Returns the number of frames that were written.
+    /// # Errors
+    ///
+    /// The function returns an error if the length of the input is larger than the number of frames
+    /// needed times the number of channels or not a whole number of frames, if the output is too short,
+    /// or if the wrapped resampler returns an error.
+    pub fn process_partial_into_buffer(
+        &mut self,
+        wave_in: Option<&[T]>,
+        wave_out: &mut [T],
+    ) -> ResampleResult<usize> {
+        let nbr_channels = self.buffer_in.len();
+        let frames_in = self.resampler.nbr_frames_needed();
+        if let Some(wave_in) = wave_in {
+            if wave_in.len() > frames_in * nbr_channels || wave_in.len() % nbr_channels != 0 {
+                return Err(ResampleError::WrongNumberOfInputSamples {
+                    expected: frames_in * nbr_channels,
+                    actual: wave_in.len(),
+                });
+            }
+            self.deinterleave_input(wave_in);
+        }
+        self.check_output_length(wave_out)?;
+        let buffer_in = if wave_in.is_some() {
+            Some(self.buffer_in.as_slice())
+        } else {
+            None
+        };
+        let frames_out =
+            self.resampler
+                .process_partial_into_buffer(buffer_in, &mut self.buffer_out, None)?;
+        self.interleave_output(wave_out, frames_out);
+        Ok(frames_out)
+    }
+
+    /// Resample a chunk of interleaved audio, and return the interleaved result in a new vector.
+    /// This allocates a new vector on every call, use `process_into_buffer` to avoid that.
+    pub fn process(&mut self, wave_in: &[T]) -> ResampleResult<Vec<T>> {
+        let nbr_channels = self.buffer_in.len();
+        let mut wave_out = vec![T::zero(); self.resampler.output_frames_max() * nbr_channels];
+        let frames_out = self.process_into_buffer(wave_in, &mut wave_out)?;
+        wave_out.truncate(frames_out * nbr_channels);
+        Ok(wave_out)
+    }
+
+    /// Resample a chunk of interleaved audio that may be shorter than a full chunk,
+    /// and return the interleaved result in a new vector. See `process_partial_into_buffer`.
+    pub fn process_partial(&mut self, wave_in: Option<&[T]>) -> ResampleResult<Vec<T>> {
+        let nbr_channels = self.buffer_in.len();
+        let mut wave_out = vec![T::zero(); self.resampler.output_frames_max() * nbr_channels];
+        let frames_out = self.process_partial_into_buffer(wave_in, &mut wave_out)?;
+        wave_out.truncate(frames_out * nbr_channels);
+        Ok(wave_out)
+    }
+
+    /// Resample a complete interleaved signal, and return the interleaved result in a new vector.
+    /// The output starts at the first frame, and has `round(input frames * resample_ratio())` frames,
+    /// see `Resampler::process_all`.
+    ///
+    /// # Errors
+    ///
+    /// The function returns an error if the length of the input is not a whole number of frames,
+    /// or if the wrapped resampler returns an error.
+    pub fn process_all(&mut self, wave_in: &[T]) -> ResampleResult<Vec<T>> {
+        let nbr_channels = self.buffer_in.len();
+        if wave_in.len() % nbr_channels != 0 {
+            return Err(ResampleError::WrongNumberOfInputSamples {
+                expected: wave_in.len() / nbr_channels * nbr_channels,
+                actual: wave_in.len(),
+            });
+        }
+        let channels_in: Vec<Vec<T>> = (0..nbr_channels)
+            .map(|chan| {
+                wave_in
+                    .iter()
+                    .skip(chan)
+                    .step_by(nbr_channels)
+                    .copied()
+                    .collect()
+            })
+            .collect();
+        let channels_out = self.resampler.process_all(&channels_in)?;
+        let nbr_frames = channels_out.first().map_or(0, |wav| wav.len());
+        let mut wave_out = Vec::with_capacity(nbr_frames * nbr_channels);
+        for frame in 0..nbr_frames {
+            wave_out.extend(channels_out.iter().map(|wav| wav[frame]));
+        }
+        Ok(wave_out)
+    }
+
+    /// Helper to check that an interleaved output buffer is long enough.
+    fn check_output_length(&self, wave_out: &[T]) -> ResampleResult<()> {
+        let samples_out = self.resampler.output_frames_max() * self.buffer_in.len();
+        if wave_out.len() < samples_out {
+            return Err(ResampleError::InsufficientOutputSamples {
+                expected: samples_out,
+                actual: wave_out.len(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Helper to copy interleaved input samples to the channel buffers.
+    fn deinterleave_input(&mut self, wave_in: &[T]) {
+        let nbr_channels = self.buffer_in.len();
+        for (chan, buf) in self.buffer_in.iter_mut().enumerate() {
+            buf.clear();
+            buf.extend(wave_in.iter().skip(chan).step_by(nbr_channels));
+        }
+    }
+
+    /// Helper to copy the first `frames_out` frames of the channel buffers to an interleaved output.
+    fn interleave_output(&self, wave_out: &mut [T], frames_out: usize) {
+        let nbr_channels = self.buffer_out.len();
+        for (chan, buf) in self.buffer_out.iter().enumerate() {
+            for (sample, value) in wave_out
+                .iter_mut()
+                .skip(chan)
+                .step_by(nbr_channels)
+                .zip(buf.iter().take(frames_out))
+            {
+                *sample = *value;
+            }
+        }
+    }
+
+    /// Query for the number of frames needed for the next call to "process".
+    pub fn nbr_frames_needed(&self) -> usize {
+        self.resampler.nbr_frames_needed()
+    }
+
+    /// Query for the maximum number of frames that a call to "process" can return.
+    pub fn output_frames_max(&self) -> usize {
+        self.resampler.output_frames_max()
+    }
+
+    /// Get the delay of the wrapped resampler, in output frames.
+    pub fn output_delay(&self) -> f64 {
+        self.resampler.output_delay()
+    }
+
+    /// Get a reference to the wrapped resampler.
+    pub fn resampler(&self) -> &R {
+        &self.resampler
+    }
+
+    /// Get a mutable reference to the wrapped resampler,
+    /// for example for updating the resample ratio.
+    pub fn resampler_mut(&mut self) -> &mut R {
+        &mut self.resampler
+    }
+
+    /// Consume the wrapper and return the wrapped resampler.
+    pub fn into_inner(self) -> R {
+        self.resampler
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{FftFixedIn, FftFixedOut, InterleavedResampler, ResampleError, Resampler};
+
+    #[test]
+    fn interleaved_matches_planar() {
+        let mut planar = FftFixedIn::<f64>::new(44100, 48000, 1024, 2, 2);
+        let mut interleaved =
+            InterleavedResampler::new(FftFixedIn::<f64>::new(44100, 48000, 1024, 2, 2));
+        let left: Vec<f64> = (0..1024).map(|n| (n as f64 * 0.01).sin()).collect();
+        let right: Vec<f64> = (0..1024).map(|n| (n as f64 * 0.03).cos()).collect();
+        let wave_in: Vec<f64> = left
+            .iter()
+            .zip(right.iter())
+            .flat_map(|(l, r)| vec![*l, *r])
+            .collect();
+        for _ in 0..3 {
+            let out_planar = planar.process(&[&left, &right]).unwrap();
+            let out_interleaved = interleaved.process(&wave_in).unwrap();
+            assert_eq!(out_interleaved.len(), 2 * out_planar[0].len());
+            for (n, frame) in out_interleaved.chunks(2).enumerate() {
+                assert_eq!(frame[0], out_planar[0][n]);
+                assert_eq!(frame[1], out_planar[1][n]);
+            }
+        }
+    }
+
+    #[test]
+    fn interleaved_varying_input() {
+        let mut resampler =
+            InterleavedResampler::new(FftFixedOut::<f32>::new(44100, 48000, 1024, 2, 2));
+        let mut wave_out = vec![0.0f32; 2 * resampler.output_frames_max()];
+        for _ in 0..3 {
+            let wave_in = vec![0.0f32; 2 * resampler.nbr_frames_needed()];
+            let frames = resampler
+                .process_into_buffer(&wave_in, &mut wave_out)
+                .unwrap();
+            assert_eq!(frames, 1024);
+        }
+        let wave_in = vec![0.0f32; 2 * resampler.nbr_frames_needed() + 1];
+        assert_eq!(
+            resampler.process_into_buffer(&wave_in, &mut wave_out),
+            Err(ResampleError::WrongNumberOfInputSamples {
+                expected: wave_in.len() - 1,
+                actual: wave_in.len()
+            })
+        );
+    }
+
+    #[test]
+    fn interleaved_partial() {
+        let mut planar = FftFixedIn::<f64>::new(44100, 48000, 1024, 2, 2);
+        let mut interleaved =
+            InterleavedResampler::new(FftFixedIn::<f64>::new(44100, 48000, 1024, 2, 2));
+        let left: Vec<f64> = (0..500).map(|n| (n as f64 * 0.01).sin()).collect();
+        let right: Vec<f64> = (0..500).map(|n| (n as f64 * 0.03).cos()).collect();
+        let wave_in: Vec<f64> = left
+            .iter()
+            .zip(right.iter())
+            .flat_map(|(l, r)| vec![*l, *r])
+            .collect();
+        let out_planar = planar.process_partial(Some(&[&left, &right])).unwrap();
+        let out_interleaved = interleaved.process_partial(Some(&wave_in)).unwrap();
+        assert_eq!(out_interleaved.len(), 2 * out_planar[0].len());
+        for (n, frame) in out_interleaved.chunks(2).enumerate() {
+            assert_eq!(frame[0], out_planar[0][n]);
+            assert_eq!(frame[1], out_planar[1][n]);
+        }
+        let out_planar = planar.process_partial::<Vec<f64>>(None).unwrap();
+        let out_interleaved = interleaved.process_partial(None).unwrap();
+        assert_eq!(out_interleaved.len(), 2 * out_planar[0].len());
+        for (n, frame) in out_interleaved.chunks(2).enumerate() {
+            assert_eq!(frame[0], out_planar[0][n]);
+            assert_eq!(frame[1], out_planar[1][n]);
+        }
+        assert_eq!(
+            interleaved.process_partial(Some(&wave_in[..3])),
+            Err(ResampleError::WrongNumberOfInputSamples {
+                expected: 2048,
+                actual: 3
+            })
+        );
+    }
+
+    #[test]
+    fn interleaved_process_all() {
+        let mut planar = FftFixedOut::<f64>::new(44100, 48000, 1024, 2, 2);
+        let mut interleaved =
+            InterleavedResampler::new(FftFixedOut::<f64>::new(44100, 48000, 1024, 2, 2));
+        let left: Vec<f64> = (0..5000).map(|n| (n as f64 * 0.01).sin()).collect();
+        let right: Vec<f64> = (0..5000).map(|n| (n as f64 * 0.03).cos()).collect();
+        let wave_in: Vec<f64> = left
+            .iter()
+            .zip(right.iter())
+            .flat_map(|(l, r)| vec![*l, *r])
+            .collect();
+        let out_planar = planar.process_all(&[&left, &right]).unwrap();
+        let out_interleaved = interleaved.process_all(&wave_in).unwrap();
+        assert_eq!(out_planar[0].len(), 5442);
+        assert_eq!(out_interleaved.len(), 2 * out_planar[0].len());
+        for (n, frame) in out_interleaved.chunks(2).enumerate() {
+            assert_eq!(frame[0], out_planar[0][n]);
+            assert_eq!(frame[1], out_planar[1][n]);
+        }
+        assert_eq!(
+            interleaved.process_all(&wave_in[..3]),
+            Err(ResampleError::WrongNumberOfInputSamples {
+                expected: 2,
+                actual: 3
+            })
+        );
+    }
+}
@@ -1,3 +1,5 @@
+use num_traits::Float;
+
 /// Get the two nearest time points for time t in format (index, subindex)
 pub fn get_nearest_times_2(t: f64, factor: isize, points: &mut [(isize, isize)]) {
     let mut index = t.floor() as isize;
@@ -15,19 +17,25 @@ pub fn get_nearest_times_2(t: f64, factor: isize, points: &mut [(isize, isize)])
 pub fn get_nearest_times_4(t: f64, factor: isize, points: &mut [(isize, isize)]) {
     let start = t.floor() as isize;
     let frac = ((t - t.floor()) * (factor as f64)).floor() as isize;
-    let mut index;
-    let mut subindex;
     for (idx, sub) in (-1..3).enumerate() {
-        index = start;
-        subindex = frac + sub;
-        if subindex < 0 {
-            subindex += factor;
-            index -= 1;
-        } else if subindex >= factor {
-            subindex -= factor;
-            index += 1;
-        }
-        points[idx] = (index, subindex);
+        let subindex = frac + sub;
+        points[idx] = (
+            start + subindex.div_euclid(factor),
+            subindex.rem_euclid(factor),
+        );
+    }
+}
+
+/// Get the six nearest time points for time t in format (index, subindex).
+pub fn get_nearest_times_6(t: f64, factor: isize, points: &mut [(isize, isize)]) {
+    let start = t.floor() as isize;
+    let frac = ((t - t.floor()) * (factor as f64)).floor() as isize;
+    for (idx, sub) in (-2..4).enumerate() {
+        let subindex = frac + sub;
+        points[idx] = (
+            start + subindex.div_euclid(factor),
+            subindex.rem_euclid(factor),
+        );
     }
 }
 
@@ -42,11 +50,63 @@ pub fn get_nearest_time(t: f64, factor: isize) -> (isize, isize) {
     (index, subindex)
 }
 
+/// Perform cubic polynomial interpolation to get value at x.
+/// Input points are assumed to be at x = -1, 0, 1, 2
+pub fn interp_cubic<T: Float>(x: T, yvals: &[T]) -> T {
+    let half = T::from(0.5).unwrap();
+    let third = T::from(1.0 / 3.0).unwrap();
+    let sixth = T::from(1.0 / 6.0).unwrap();
+    let a0 = yvals[1];
+    let a1 = -third * yvals[0] - half * yvals[1] + yvals[2] - sixth * yvals[3];
+    let a2 = half * (yvals[0] + yvals[2]) - yvals[1];
+    let a3 = half * (yvals[1] - yvals[2]) + sixth * (yvals[3] - yvals[0]);
+    a0 + a1 * x + a2 * x.powi(2) + a3 * x.powi(3)
+}
+
+/// Perform quintic polynomial interpolation to get value at x.
+/// Input points are assumed to be at x = -2, -1, 0, 1, 2, 3
+pub fn interp_quintic<T: Float>(x: T, yvals: &[T]) -> T {
+    let one = T::one();
+    let two = T::from(2.0).unwrap();
+    let three = T::from(3.0).unwrap();
+    let xp2 = x + two;
+    let xp1 = x + one;
+    let xm1 = x - one;
+    let xm2 = x - two;
+    let xm3 = x - three;
+    let w0 = -xp1 * x * xm1 * xm2 * xm3 / T::from(120.0).unwrap();
+    let w1 = xp2 * x * xm1 * xm2 * xm3 / T::from(24.0).unwrap();
+    let w2 = -xp2 * xp1 * xm1 * xm2 * xm3 / T::from(12.0).unwrap();
+    let w3 = xp2 * xp1 * x * xm2 * xm3 / T::from(12.0).unwrap();
+    let w4 = -xp2 * xp1 * x * xm1 * xm3 / T::from(24.0).unwrap();
+    let w5 = xp2 * xp1 * x * xm1 * xm2 / T::from(120.0).unwrap();
+    w0 * yvals[0] + w1 * yvals[1] + w2 * yvals[2] + w3 * yvals[3] + w4 * yvals[4] + w5 * yvals[5]
+}
+
+/// Perform cubic Hermite (Catmull-Rom) interpolation to get value at x.
+/// Input points are assumed to be at x = -1, 0, 1, 2
+pub fn interp_hermite<T: Float>(x: T, yvals: &[T]) -> T {
+    let half = T::from(0.5).unwrap();
+    let c0 = yvals[1];
+    let c1 = half * (yvals[2] - yvals[0]);
+    let c2 = yvals[0] - T::from(2.5).unwrap() * yvals[1] + T::from(2.0).unwrap() * yvals[2]
+        - half * yvals[3];
+    let c3 = half * (yvals[3] - yvals[0]) + T::from(1.5).unwrap() * (yvals[1] - yvals[2]);
+    ((c3 * x + c2) * x + c1) * x + c0
+}
+
+/// Linear interpolation between two points at x=0 and x=1
+pub fn interp_lin<T: Float>(x: T, yvals: &[T]) -> T {
+    (T::one() - x) * yvals[0] + x * yvals[1]
+}
+
 #[cfg(test)]
 mod tests {
     use crate::interpolation::get_nearest_time;
     use crate::interpolation::get_nearest_times_2;
     use crate::interpolation::get_nearest_times_4;
+    use crate::interpolation::get_nearest_times_6;
+    use crate::interpolation::{interp_cubic, interp_hermite, interp_lin, interp_quintic};
 
     #[test]
     fn get_nearest_2() {
@@ -90,10 +150,105 @@ mod tests {
         assert_eq!(times[3], (0, 1));
     }
 
+    #[test]
+    fn get_nearest_6() {
+        let t = 5.9f64;
+        let mut times = vec![(0isize, 0isize); 6];
+        get_nearest_times_6(t, 8, &mut times);
+        assert_eq!(times[0], (5, 5));
+        assert_eq!(times[1], (5, 6));
+        assert_eq!(times[2], (5, 7));
+        assert_eq!(times[3], (6, 0));
+        assert_eq!(times[4], (6, 1));
+        assert_eq!(times[5], (6, 2));
+    }
+
+    #[test]
+    fn get_nearest_6_neg() {
+        let t = -0.00001f64;
+        let mut times = vec![(0isize, 0isize); 6];
+        get_nearest_times_6(t, 2, &mut times);
+        assert_eq!(times[0], (-2, 1));
+        assert_eq!(times[1], (-1, 0));
+        assert_eq!(times[2], (-1, 1));
+        assert_eq!(times[3], (0, 0));
+        assert_eq!(times[4], (0, 1));
+        assert_eq!(times[5], (1, 0));
+    }
+
     #[test]
     fn get_nearest_single() {
         let t = 5.5f64;
         let time = get_nearest_time(t, 8);
         assert_eq!(time, (5, 4));
     }
+
+    #[test]
+    fn get_nearest_4_factor_one() {
+        let t = 5.9f64;
+        let mut times = vec![(0isize, 0isize); 4];
+        get_nearest_times_4(t, 1, &mut times);
+        assert_eq!(times[0], (4, 0));
+        assert_eq!(times[1], (5, 0));
+        assert_eq!(times[2], (6, 0));
+        assert_eq!(times[3], (7, 0));
+    }
+
+    #[test]
+    fn int_cubic() {
+        let yvals = vec![0.0f64, 2.0f64, 4.0f64, 6.0f64];
+        let interp = interp_cubic(0.5f64, &yvals);
+        assert_eq!(interp, 3.0f64);
+    }
+
+    #[test]
+    fn int_lin_32() {
+        let yvals = vec![1.0f32, 5.0f32];
+        let interp = interp_lin(0.25f32, &yvals);
+        assert_eq!(interp, 2.0f32);
+    }
+
+    #[test]
+    fn int_cubic_32() {
+        let yvals = vec![0.0f32, 2.0f32, 4.0f32, 6.0f32];
+        let interp = interp_cubic(0.5f32, &yvals);
+        assert_eq!(interp, 3.0f32);
+    }
+
+    #[test]
+    fn int_lin() {
+        let yvals = vec![1.0f64, 5.0f64];
+        let interp = interp_lin(0.25f64, &yvals);
+        assert_eq!(interp, 2.0f64);
+    }
+
+    #[test]
+    fn int_quintic() {
+        // y = x^5 - 2x^3 + x at x = -2, -1, 0, 1, 2, 3
+        let yvals = vec![-18.0f64, 0.0f64, 0.0f64, 0.0f64, 18.0f64, 192.0f64];
+        let interp = interp_quintic(0.3f64, &yvals);
+        assert!((interp - 0.24843f64).abs() < 1.0e-12);
+    }
+
+    #[test]
+    fn int_quintic_32() {
+        let yvals = vec![-18.0f32, 0.0f32, 0.0f32, 0.0f32, 18.0f32, 192.0f32];
+        let interp = interp_quintic(0.3f32, &yvals);
+        assert!((interp - 0.24843f32).abs() < 1.0e-5);
+    }
+
+    #[test]
+    fn int_hermite() {
+        // y = x^2 - x + 1 at x = -1, 0, 1, 2
+        let yvals = vec![3.0f64, 1.0f64, 1.0f64, 3.0f64];
+        let interp = interp_hermite(0.25f64, &yvals);
+        assert!((interp - 0.8125f64).abs() < 1.0e-12);
+    }
+
+    #[test]
+    fn int_hermite_32() {
+        let yvals = vec![3.0f32, 1.0f32, 1.0f32, 3.0f32];
+        let interp = interp_hermite(0.25f32, &yvals);
+        assert!((interp - 0.8125f32).abs() < 1.0e-5);
+    }
 }
@@ -10,12 +10,43 @@
 //! The resampling is based on band-limited interpolation using sinc
 //! interpolation filters. The sinc interpolation upsamples by an adjustable factor,
 //! and then the new sample points are calculated by interpolating between these points.
-//! The resampling ratio can be updated at any time.
+//! The resampling ratio can be updated at any time, within a range that is given
+//! when creating the resampler.
+//! The change can either take effect at once, or be ramped smoothly over the next chunk.
+//!
+//! The sinc resamplers can also be created in rational mode with `new_rational`,
+//! for synchronous resampling between two integer sample rates.
+//! The time of each new sample is then stepped with exact integer arithmetic,
+//! so that the output is bit-reproducible over arbitrarily long streams.
+//!
+//! ## Fast resampling
+//! For signals that don't need a high quality anti-aliasing filter, like voice prompts
+//! or control signals, `FastFixedIn` and `FastFixedOut` skip the sinc interpolation entirely.
+//! They interpolate directly between the input samples, using any of the `InterpolationType`s,
+//! and implement the same `Resampler` trait as the other resamplers.
 //!
 //! ## Synchronous resampling
 //! Synchronous resampling is implemented via FFT. The data is FFT:ed, the spectrum modified,
 //! and then inverse FFT:ed to get the resampled data.
 //! This type of resampler is considerably faster but doesn't support changing the resampling ratio.
+//! The cutoff, window and length of its antialiasing filter can be chosen with
+//! `FftFilterParameters`, given to `new_with_parameters`.
+//! The filter length does not depend on the chunk size, so small chunks for low latency
+//! can still use a long filter with a steep transition band.
+//! A long filter is split into partitions as long as the FFT input,
+//! which are applied to the spectra of the latest chunks.
+//! This is cheap when the sample rates are related by small integers, like 48000 and 96000 Hz.
+//! For rate pairs like 44100 and 48000 Hz, each FFT instead spans both the new input frames
+//! and the filter, when that costs less cpu time.
+//!
+//! The FFT sizes must be multiples of `fs_in / gcd(fs_in, fs_out)` input frames,
+//! which gets very large for rate pairs like 44100 to 44101 Hz, where it is 44100 frames.
+//! For such rates, `FftFixedIn` and `FftFixedOut` have a two-stage mode, created with `new_two_stage`.
+//! The FFTs then take chunks of any size and upsample them by two,
+//! and a short polyphase filter resamples the result to the output rate.
+//! For 44100 to 44101 Hz with chunks of 1024 frames and two sub chunks, the FFTs take 512 frames,
+//! and `FftFixedIn` returns 1024 or 1025 frames per chunk.
+//! `FftFixedInOut` has no two-stage mode, since it needs both chunk sizes to be fixed.
 //!
 //! ## Documentation
 //!
@@ -47,70 +78,132 @@
 //! let waves_out = resampler.process(&waves_in).unwrap();
 //! ```
 //!
+//! ## Choosing parameters
+//! The interpolation parameters can be derived from a quality target, either one of the
+//! presets `Fast`, `Balanced` and `Best`, or a stop band attenuation and passband edge,
+//! using `InterpolationParameters::from_quality`.
+//!
+//! ## Sharing filters
+//! Each sinc resampler calculates its own table of sinc filters, which can be large.
+//! When running many resamplers with the same parameters, calculate the table once
+//! as a `SincTable`, and share it with `SincFixedIn::new_with_table` or `SincFixedOut::new_with_table`.
+//! `SincTable::cached` returns a table from a process-wide cache, and only calculates
+//! a new one when there is none with the same parameters.
+//! The synchronous resamplers can share their antialiasing filter in the same way.
+//! Get it as an `FftFilter` from one resampler with `filter()`, and give it to the others
+//! with `new_with_filter`.
+//!
+//! ## SIMD
+//! Sinc filters are applied with SIMD instructions.
+//! On x86_64, the fastest of the SSE, AVX and AVX2+FMA kernels that the cpu supports
+//! is selected at runtime. Other platforms use portable code that the compiler can vectorize.
+//! The kernels give slightly different rounding. For bit-identical output on all machines,
+//! select `DotProductKernel::Portable` with `set_dot_product_kernel`.
+//!
+//! ## Parallel processing
+//! With the optional `rayon` feature, the sinc and FFT resamplers process the channels
+//! in parallel on the rayon thread pool. This helps with many channels, such as 32 or more.
+//! The FFT resamplers create one set of FFT buffers per thread.
+//! The fast resamplers do too little work per channel to gain from this, and always use a single thread.
+//! Note that rayon may allocate memory when queueing the work,
+//! so the feature is not suitable for a thread with hard real-time requirements.
+//!
+//! ## Real-time use
+//! The `process` method allocates new vectors for the output on every call.
+//! In a real-time context, use `process_into_buffer` instead.
+//! This writes the output to buffers provided by the caller,
+//! and returns the number of frames that were written.
+//! The output buffers must be long enough to hold `output_frames_max()` frames.
+//! None of the resamplers allocate any memory during processing after they have been created,
+//! unless the `rayon` feature is enabled.
+//!
+//! ## Interleaved data
+//! The resamplers work on data where each channel is stored separately.
+//! To process interleaved data, where the samples of all channels are stored frame by frame
+//! in a single slice, wrap the resampler in an `InterleavedResampler`.
+//! This makes one copy of each chunk in each direction, between the interleaved data
+//! and the separate channels that the resampler works on.
+//!
+//! ## Processing a complete stream
+//! The resamplers delay the signal slightly, and keep some frames in internal buffers.
+//! To resample a complete stream, so that the output starts at the first frame and has
+//! exactly as many frames as the input corresponds to:
+//! - Process the last frames, that are not enough for a full chunk, with `process_partial`.
+//!   `SincFixedIn` and `FftFixedIn` consume only the given frames,
+//!   while the other resamplers fill the missing frames with zeros.
+//! - Keep calling `process_partial` with `None` as input to flush out the remaining frames,
+//!   until the output has `output_delay()` + round(input length * ratio) frames.
+//! - Drop the first `output_delay()` frames, and truncate the output to the expected length.
+//!
+//! When the whole signal is available, for example when converting a file,
+//! `process_all` does all of this in a single call.
+//! The "fixedin64" example uses it through an `InterleavedResampler`.
+//!
+//! ## Clock drift compensation
+//! When bridging two devices with independent clocks, the ratio must follow the small difference
+//! between the clocks. The `DriftCompensator` wraps an asynchronous resampler, and steers the ratio
+//! from observations of the level of a buffer between the two devices, using a PI controller.
+//!
 //! ## Compatibility
 //!
 //! The `rubato` crate requires rustc version 1.40 or newer.
 
+mod drift;
+mod error;
+mod fast;
+mod interleaved;
 mod interpolation;
+mod polyphase;
+mod quality;
+mod simd;
 mod sinc;
+mod sinc_table;
 mod synchro;
 mod windows;
-pub use crate::synchro::{FftFixedIn, FftFixedInOut, FftFixedOut};
-pub use crate::windows::WindowFunction;
+pub use crate::drift::{DriftCompensationParameters, DriftCompensator};
+pub use crate::error::{ResampleError, ResampleResult};
+pub use crate::fast::{FastFixedIn, FastFixedOut};
+pub use crate::interleaved::InterleavedResampler;
+pub use crate::quality::QualityTarget;
+pub use crate::simd::DotProductKernel;
+pub use crate::sinc_table::SincTable;
+pub use crate::synchro::{FftFilter, FftFilterParameters, FftFixedIn, FftFixedInOut, FftFixedOut};
+pub use crate::windows::{calculate_cutoff, WindowFunction};
 
 use crate::interpolation::*;
-use crate::sinc::make_sincs;
+use crate::simd::DotProduct;
+use crate::sinc::{update_sincs, update_sincs_from_prototype};
+use crate::sinc_table::SincTableBank;
+use crate::windows::make_window;
+use num_integer as integer;
 use num_traits::Float;
-use std::error;
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
 use std::fmt;
+use std::sync::Arc;
 
 #[macro_use]
 extern crate log;
 
-type Res<T> = Result<T, Box<dyn error::Error>>;
-
-/// Custom error returned by resamplers
-#[derive(Debug)]
-pub struct ResamplerError {
-    desc: String,
-}
-
-impl fmt::Display for ResamplerError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.desc)
-    }
-}
-
-impl error::Error for ResamplerError {
-    fn description(&self) -> &str {
-        &self.desc
-    }
-}
-
-impl ResamplerError {
-    pub fn new(desc: &str) -> Self {
-        ResamplerError {
-            desc: desc.to_owned(),
-        }
-    }
-}
-
 /// A struct holding the parameters for interpolation.
 #[derive(Debug)]
 pub struct InterpolationParameters {
     /// Length of the windowed sinc interpolation filter.
     /// Higher values can allow a higher cut-off frequency leading to less high frequency roll-off
     /// at the expense of higher cpu usage. 256 is a good starting point.
-    /// The value will be rounded up to the nearest multiple of 8.
+    /// The value will be rounded up to the nearest multiple of 8, and must be larger than zero.
     pub sinc_len: usize,
     /// Relative cutoff frequency of the sinc interpolation filter
     /// (relative to the lowest one of fs_in/2 or fs_out/2). Start at 0.95, and increase if needed.
+    /// A suitable value for a given `sinc_len` and `window` can be found with `calculate_cutoff`.
+    /// Must be larger than zero and at most 1.0.
     pub f_cutoff: f32,
     /// The number of intermediate points to use for interpolation.
     /// Higher values use more memory for storing the sinc filters.
     /// Only the points actually needed are calculated dusing processing
     /// so a larger number does not directly lead to higher cpu usage.
     /// But keeping it down helps in keeping the sincs in the cpu cache. Start at 128.
+    /// Must be larger than zero.
     pub oversampling_factor: usize,
     /// Interpolation type, see `InterpolationType`
     pub interpolation: InterpolationType,
@@ -118,6 +211,37 @@ pub struct InterpolationParameters {
     pub window: WindowFunction,
 }
 
+/// A custom filter for the sinc interpolation, that replaces the windowed sinc
+/// made from `f_cutoff` and `window`. See `SincFixedIn::new_with_custom_filter`
+/// and `SincFixedOut::new_with_custom_filter`.
+pub enum CustomFilter {
+    /// A custom window function, used instead of `window` to window the sinc.
+    /// The function is called with the point `x` and the total number of points `npoints`,
+    /// and should return the value of the window at that point, like the built-in windows.
+    /// The window is centered at `npoints / 2`.
+    /// The cutoff of the sinc is adjusted as usual when the resample ratio changes.
+    Window(Box<dyn Fn(usize, usize) -> f64 + Send + Sync>),
+    /// A fully user-supplied prototype filter, that is split into the intermediate filters.
+    /// It must have `sinc_len * oversampling_factor` points, with its center at the point
+    /// `sinc_len * oversampling_factor / 2`, where `sinc_len` has been rounded up to a multiple of 8.
+    /// The filter is normalized to unity gain, but otherwise used as it is.
+    /// It is never re-derived when the resample ratio changes, so it must be designed for
+    /// the lowest ratio that will be used. A prototype designed for the original ratio
+    /// lets through aliasing if the ratio is later lowered much below it.
+    Prototype(Vec<f64>),
+}
+
+impl fmt::Debug for CustomFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CustomFilter::Window(_) => write!(f, "Window(<function>)"),
+            CustomFilter::Prototype(prototype) => {
+                write!(f, "Prototype(<{} points>)", prototype.len())
+            }
+        }
+    }
+}
+
 /// Interpolation methods that can be selected. For asynchronous interpolation where the
 /// ratio between inut and output sample rates can be any number, it's not possible to
 /// pre-calculate all the needed interpolation filters.
@@ -127,14 +251,31 @@ pub struct InterpolationParameters {
 /// Then sinc filters are used to provide a fixed number of interpolated points between input samples,
 /// and then the new value is calculated by interpolation between those points.
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum InterpolationType {
+    /// For quintic interpolation, the six nearest intermediate points are calculated
+    /// using sinc interpolation.
+    /// Then a fifth degree Lagrange polynomial is fitted to these points,
+    /// and is then used to calculate the new sample value.
+    /// The computation time is about 1.5 times the one for cubic interpolation,
+    /// but the oversampling factor can be lowered further for the same quality,
+    /// which reduces the memory used by the sinc table.
+    Quintic,
     /// For cubic interpolation, the four nearest intermediate points are calculated
     /// using sinc interpolation.
     /// Then a cubic polynomial is fitted to these points, and is then used to calculate the new sample value.
     /// The computation time as about twice the one for linear interpolation,
     /// but it requires much fewer intermediate points for a good result.
     Cubic,
+    /// Hermite interpolation uses the same four points as cubic interpolation,
+    /// but fits a cubic Hermite spline (Catmull-Rom) where the slopes at the two middle points
+    /// are estimated from their neighbours.
+    /// The resulting curve has a continuous first derivative, without the kinks
+    /// that cubic interpolation has at the intermediate points.
+    /// The computation time is the same as for cubic interpolation,
+    /// but it is exact only for polynomials up to second degree,
+    /// and needs a larger oversampling factor to reach the same accuracy.
+    Hermite,
     /// With linear interpolation the new sample value is calculated by linear interpolation
     /// between the two nearest points.
     /// This requires two intermediate points to be calcuated using sinc interpolation,
@@ -149,24 +290,591 @@ pub enum InterpolationType {
     /// no unneccesary computations are performed and the result is the same as for synchronous resampling.
     /// This also works for other ratios that can be expressed by a fraction. For 44.1kHz -> 48 kHz,
     /// setting oversampling_factor to 160 gives the desired result (since 48kHz = 160/147 * 44.1kHz).
+    /// The time of the new samples is however accumulated with floating point steps,
+    /// and the phase may drift over very long streams.
+    /// Use `SincFixedIn::new_rational` or `SincFixedOut::new_rational` to step it exactly.
     Nearest,
 }
 
 /// A resampler that us used to resample a chunk of audio to a new sample rate.
 /// The rate can be adjusted as required.
-pub trait Resampler<T> {
-    /// Resample a chunk of audio. Input and output data is stored in a vector,
-    /// where each element contains a vector with all samples for a single channel.
-    fn process(&mut self, wave_in: &[Vec<T>]) -> Res<Vec<Vec<T>>>;
+pub trait Resampler<T: Float> {
+    /// Resample a chunk of audio. Input data is given as a slice,
+    /// where each element contains all samples for a single channel.
+    /// The channels can be stored in any type that implements `AsRef<[T]>`,
+    /// for example `Vec<T>`, `&[T]` or `[T; N]`.
+    /// The output is returned as a vector of vectors, one per channel.
+    ///
+    /// This allocates a new output buffer on every call. Use `process_into_buffer`
+    /// to write the result to a pre-allocated buffer instead.
+    fn process<V: AsRef<[T]>>(&mut self, wave_in: &[V]) -> ResampleResult<Vec<Vec<T>>> {
+        let frames = self.output_frames_max();
+        let mut wave_out = vec![vec![T::zero(); frames]; self.nbr_channels()];
+        let frames_out = self.process_into_buffer(wave_in, &mut wave_out, None)?;
+        for wav in wave_out.iter_mut() {
+            wav.truncate(frames_out);
+        }
+        Ok(wave_out)
+    }
+
+    /// Resample a chunk of audio and write the result to a pre-allocated output buffer.
+    /// Input and output data is stored in slices,
+    /// where each element contains all samples for a single channel.
+    /// The input channels can be of any type that implements `AsRef<[T]>`,
+    /// and the output channels of any type that implements `AsMut<[T]>`.
+    /// Each output channel must have a length of at least `output_frames_max()`,
+    /// and the number of frames that were written is returned.
+    /// The output channels are not resized, and no memory is allocated.
+    ///
+    /// The `active_channels_mask` is optional.
+    /// Any channel marked as inactive by a false value will be skipped during processing,
+    /// and the corresponding output is left unchanged.
+    /// If `None` is given, all channels are considered active.
+    fn process_into_buffer<Vin: AsRef<[T]>, Vout: AsMut<[T]>>(
+        &mut self,
+        wave_in: &[Vin],
+        wave_out: &mut [Vout],
+        active_channels_mask: Option<&[bool]>,
+    ) -> ResampleResult<usize>;
+
+    /// Resample a chunk of audio that may be shorter than a full chunk,
+    /// and write the result to a pre-allocated output buffer.
+    /// This works like `process_into_buffer`, but the input channels may contain fewer frames
+    /// than given by `nbr_frames_needed()`.
+    /// The resamplers with a fixed input size, `SincFixedIn` and `FftFixedIn`,
+    /// then consume only the given frames and return the output that corresponds to them.
+    /// The other resamplers fill the missing frames with zeros.
+    /// If `None` is given for the input, a full chunk of zeros is processed.
+    ///
+    /// This is meant for the end of a stream, to process the last frames
+    /// and to flush out the frames that remain inside the resampler.
+    fn process_partial_into_buffer<Vin: AsRef<[T]>, Vout: AsMut<[T]>>(
+        &mut self,
+        wave_in: Option<&[Vin]>,
+        wave_out: &mut [Vout],
+        active_channels_mask: Option<&[bool]>,
+    ) -> ResampleResult<usize>;
+
+    /// Resample a chunk of audio that may be shorter than a full chunk,
+    /// see `process_partial_into_buffer`.
+    /// The output is returned as a vector of vectors, one per channel.
+    fn process_partial<V: AsRef<[T]>>(
+        &mut self,
+        wave_in: Option<&[V]>,
+    ) -> ResampleResult<Vec<Vec<T>>> {
+        let frames = self.output_frames_max();
+        let mut wave_out = vec![vec![T::zero(); frames]; self.nbr_channels()];
+        let frames_out = self.process_partial_into_buffer(wave_in, &mut wave_out, None)?;
+        for wav in wave_out.iter_mut() {
+            wav.truncate(frames_out);
+        }
+        Ok(wave_out)
+    }
+
+    /// Resample a complete signal, and return the output as a vector of vectors, one per channel.
+    /// The input is processed chunk by chunk, the last frames with `process_partial`,
+    /// and the resampler is then flushed until the delayed output is complete.
+    /// The first `output_delay()` frames are dropped, and the output is truncated
+    /// to `round(input length * resample_ratio())` frames. It then starts at the first frame,
+    /// and has as many frames as the input corresponds to.
+    /// The resampler should be new or reset with `reset`.
+    ///
+    /// This is meant for signals that are available in full, for example when converting a file.
+    /// For a stream, do the same steps with `process_partial`, see the documentation of the crate.
+    fn process_all<V: AsRef<[T]>>(&mut self, wave_in: &[V]) -> ResampleResult<Vec<Vec<T>>> {
+        let nbr_channels = self.nbr_channels();
+        if wave_in.len() != nbr_channels {
+            return Err(ResampleError::WrongNumberOfInputChannels {
+                expected: nbr_channels,
+                actual: wave_in.len(),
+            });
+        }
+        let nbr_frames = wave_in.first().map_or(0, |wav| wav.as_ref().len());
+        for (chan, wav) in wave_in.iter().enumerate() {
+            if wav.as_ref().len() != nbr_frames {
+                return Err(ResampleError::WrongNumberOfInputFrames {
+                    channel: chan,
+                    expected: nbr_frames,
+                    actual: wav.as_ref().len(),
+                });
+            }
+        }
+        let expected_len = (nbr_frames as f64 * self.resample_ratio()).round() as usize;
+        let delay = self.output_delay().round().max(0.0) as usize;
+        let frames_max = self.output_frames_max();
+        let mut wave_out =
+            vec![Vec::with_capacity(delay + expected_len + frames_max); nbr_channels];
+        let mut chunk_out = vec![vec![T::zero(); frames_max]; nbr_channels];
+        let mut frames_written = 0;
+        let mut start = 0;
+        let mut flushing = false;
+        while frames_written < delay + expected_len {
+            let frames_needed = self.nbr_frames_needed();
+            let frames_out = if start + frames_needed <= nbr_frames {
+                let chunk: Vec<&[T]> = wave_in
+                    .iter()
+                    .map(|wav| &wav.as_ref()[start..start + frames_needed])
+                    .collect();
+                start += frames_needed;
+                self.process_into_buffer(&chunk, &mut chunk_out, None)?
+            } else if !flushing {
+                // process the last frames, that are not enough for a full chunk
+                flushing = true;
+                let chunk: Vec<&[T]> = wave_in.iter().map(|wav| &wav.as_ref()[start..]).collect();
+                self.process_partial_into_buffer(Some(&chunk), &mut chunk_out, None)?
+            } else {
+                // flush the resampler with silence
+                self.process_partial_into_buffer::<&[T], _>(None, &mut chunk_out, None)?
+            };
+            for (wav, chunk) in wave_out.iter_mut().zip(chunk_out.iter()) {
+                wav.extend_from_slice(&chunk[..frames_out]);
+            }
+            frames_written += frames_out;
+        }
+        for wav in wave_out.iter_mut() {
+            wav.drain(..delay);
+            wav.truncate(expected_len);
+        }
+        Ok(wave_out)
+    }
 
-    /// Update the resample ratio.
-    fn set_resample_ratio(&mut self, new_ratio: f64) -> Res<()>;
+    /// Update the resample ratio. The new ratio is used for the whole next chunk.
+    fn set_resample_ratio(&mut self, new_ratio: f64) -> ResampleResult<()>;
 
     /// Update the resample ratio relative to the original one.
-    fn set_resample_ratio_relative(&mut self, rel_ratio: f64) -> Res<()>;
+    fn set_resample_ratio_relative(&mut self, rel_ratio: f64) -> ResampleResult<()>;
+
+    /// Update the resample ratio, changing it gradually from the current value
+    /// to the new one during the next chunk. This avoids an abrupt step in the rate of the output,
+    /// for example when the ratio is adjusted continuously to follow a clock.
+    /// Resamplers that can't ramp the ratio change it at once, like `set_resample_ratio`.
+    fn set_resample_ratio_ramped(&mut self, new_ratio: f64) -> ResampleResult<()> {
+        self.set_resample_ratio(new_ratio)
+    }
+
+    /// Update the resample ratio relative to the original one,
+    /// with a ramp like `set_resample_ratio_ramped`.
+    fn set_resample_ratio_relative_ramped(&mut self, rel_ratio: f64) -> ResampleResult<()> {
+        self.set_resample_ratio_relative(rel_ratio)
+    }
 
     /// Query for the number of frames needed for the next call to "process".
     fn nbr_frames_needed(&self) -> usize;
+
+    /// Query for the maximum number of frames that a call to "process" can require.
+    /// This is the largest value that "nbr_frames_needed" can return.
+    fn input_frames_max(&self) -> usize;
+
+    /// Query for the maximum number of frames that a call to "process" can return.
+    /// This is the required length of the output buffers for "process_into_buffer".
+    fn output_frames_max(&self) -> usize;
+
+    /// Query for the number of channels this resampler was created for.
+    fn nbr_channels(&self) -> usize;
+
+    /// Get the current resample ratio, which is the ratio between the output
+    /// and input sample rates, `fs_out / fs_in`.
+    fn resample_ratio(&self) -> f64;
+
+    /// Get the delay of the resampler, in output frames.
+    /// An input frame at index `n` (counted from the first frame given to the resampler)
+    /// ends up at index `n * ratio + output_delay()` in the output.
+    /// The value may be fractional, and for the asynchronous resamplers
+    /// it depends on the current resample ratio.
+    fn output_delay(&self) -> f64;
+
+    /// Reset the resampler to the state it had when it was created.
+    /// This clears the buffered history of audio samples and restores the original resample ratio,
+    /// so that the resampler can be reused for a new, unrelated stream.
+    /// The precomputed filters are kept.
+    fn reset(&mut self);
+}
+
+/// Helper to check if a channel is marked as active in an optional mask.
+fn channel_is_active(active_channels_mask: Option<&[bool]>, chan: usize) -> bool {
+    active_channels_mask.map_or(true, |mask| mask[chan])
+}
+
+/// The relative margin used when choosing the cutoffs of the sinc tables
+/// that a resampler switches between when the resample ratio changes.
+const SINC_CUTOFF_MARGIN: f32 = 0.02;
+
+/// The range that the resample ratio of a sinc resampler can be changed within,
+/// when it is created with `new`. The ratio can then be changed by about 10% up or down.
+const DEFAULT_MAX_RESAMPLE_RATIO_RELATIVE: f64 = 1.1;
+
+/// The largest oversampling factor used for a synchronous ratio with `InterpolationType::Nearest`.
+const MAX_SYNC_OVERSAMPLING: usize = 1024;
+
+/// Helper to calculate the cutoff of the sinc filters for a given resample ratio.
+/// When downsampling, the cutoff must be lowered to avoid aliasing.
+fn calculate_sinc_cutoff(f_cutoff: f32, resample_ratio: f64) -> f32 {
+    if resample_ratio >= 1.0 {
+        f_cutoff
+    } else {
+        f_cutoff * resample_ratio as f32
+    }
+}
+
+/// Helper to round the sinc length up to the nearest multiple of 8.
+fn round_sinc_len(sinc_len: usize) -> usize {
+    8 * (((sinc_len as f32) / 8.0).ceil() as usize)
+}
+
+/// Helper to calculate a set of sincs in place, from either a built-in window function
+/// or a custom filter.
+fn generate_sincs<T: Float>(
+    sincs: &mut [Vec<T>],
+    f_cutoff: f32,
+    window: WindowFunction,
+    custom_filter: Option<&CustomFilter>,
+) {
+    let totpoints = sincs.len() * sincs[0].len();
+    match custom_filter {
+        None => update_sincs(sincs, f_cutoff, &make_window::<T>(totpoints, window)),
+        Some(CustomFilter::Window(func)) => {
+            let window: Vec<T> = (0..totpoints)
+                .map(|x| T::from(func(x, totpoints)).unwrap())
+                .collect();
+            update_sincs(sincs, f_cutoff, &window)
+        }
+        Some(CustomFilter::Prototype(prototype)) => update_sincs_from_prototype(sincs, prototype),
+    }
+}
+
+/// Helper to validate the resample ratio, chunk size and number of channels
+/// for creating an asynchronous resampler.
+fn validate_ratio_parameters(
+    resample_ratio: f64,
+    max_resample_ratio_relative: f64,
+    chunk_size: usize,
+    nbr_channels: usize,
+) -> ResampleResult<()> {
+    if !(resample_ratio.is_finite() && resample_ratio > 0.0) {
+        return Err(ResampleError::invalid_parameter(
+            "resample_ratio",
+            "must be a positive number",
+        ));
+    }
+    if !(max_resample_ratio_relative.is_finite() && max_resample_ratio_relative >= 1.0) {
+        return Err(ResampleError::invalid_parameter(
+            "max_resample_ratio_relative",
+            "must be a number larger than or equal to 1.0",
+        ));
+    }
+    if chunk_size == 0 {
+        return Err(ResampleError::invalid_parameter(
+            "chunk_size",
+            "must be larger than zero",
+        ));
+    }
+    if nbr_channels == 0 {
+        return Err(ResampleError::invalid_parameter(
+            "nbr_channels",
+            "must be larger than zero",
+        ));
+    }
+    Ok(())
+}
+
+/// Helper to validate the parameters for creating a sinc resampler.
+fn validate_sinc_parameters(
+    resample_ratio: f64,
+    max_resample_ratio_relative: f64,
+    parameters: &InterpolationParameters,
+    chunk_size: usize,
+    nbr_channels: usize,
+) -> ResampleResult<()> {
+    validate_ratio_parameters(
+        resample_ratio,
+        max_resample_ratio_relative,
+        chunk_size,
+        nbr_channels,
+    )?;
+    validate_interpolation_parameters(parameters)
+}
+
+/// Helper to validate the interpolation parameters used to calculate the sinc filters.
+fn validate_interpolation_parameters(parameters: &InterpolationParameters) -> ResampleResult<()> {
+    if parameters.sinc_len == 0 {
+        return Err(ResampleError::invalid_parameter(
+            "sinc_len",
+            "must be larger than zero",
+        ));
+    }
+    if !(parameters.f_cutoff > 0.0 && parameters.f_cutoff <= 1.0) {
+        return Err(ResampleError::invalid_parameter(
+            "f_cutoff",
+            "must be larger than zero and at most 1.0",
+        ));
+    }
+    if parameters.oversampling_factor == 0 {
+        return Err(ResampleError::invalid_parameter(
+            "oversampling_factor",
+            "must be larger than zero",
+        ));
+    }
+    validate_window(parameters.window)
+}
+
+/// Helper to check that a custom filter fits the interpolation parameters.
+fn validate_custom_filter(
+    parameters: &InterpolationParameters,
+    custom_filter: &CustomFilter,
+) -> ResampleResult<()> {
+    if let CustomFilter::Prototype(prototype) = custom_filter {
+        if prototype.len() != round_sinc_len(parameters.sinc_len) * parameters.oversampling_factor {
+            return Err(ResampleError::invalid_parameter(
+                "custom_filter",
+                "the prototype must have sinc_len * oversampling_factor points",
+            ));
+        }
+        if prototype.iter().sum::<f64>() == 0.0 {
+            return Err(ResampleError::invalid_parameter(
+                "custom_filter",
+                "the prototype must have a non-zero sum",
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Helper to check that the parameters of a window function are usable.
+fn validate_window(window: WindowFunction) -> ResampleResult<()> {
+    if let WindowFunction::Kaiser { beta } = window {
+        if !(beta.is_finite() && beta >= 0.0) {
+            return Err(ResampleError::invalid_parameter(
+                "window",
+                "the Kaiser beta must be a finite number, at least zero",
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Helper to validate the input and output buffers given to "process_into_buffer"
+/// and "process_partial_into_buffer".
+/// If `partial` is true, the input channels may be shorter than `frames_in`,
+/// and a missing input is allowed.
+/// Only the active channels are checked for length.
+fn validate_buffers<T, Vin: AsRef<[T]>, Vout: AsMut<[T]>>(
+    wave_in: Option<&[Vin]>,
+    wave_out: &mut [Vout],
+    active_channels_mask: Option<&[bool]>,
+    nbr_channels: usize,
+    frames_in: usize,
+    frames_out: usize,
+    partial: bool,
+) -> ResampleResult<()> {
+    if let Some(wave_in) = wave_in {
+        if wave_in.len() != nbr_channels {
+            return Err(ResampleError::WrongNumberOfInputChannels {
+                expected: nbr_channels,
+                actual: wave_in.len(),
+            });
+        }
+    }
+    if wave_out.len() != nbr_channels {
+        return Err(ResampleError::WrongNumberOfOutputChannels {
+            expected: nbr_channels,
+            actual: wave_out.len(),
+        });
+    }
+    if let Some(mask) = active_channels_mask {
+        if mask.len() != nbr_channels {
+            return Err(ResampleError::WrongNumberOfMaskChannels {
+                expected: nbr_channels,
+                actual: mask.len(),
+            });
+        }
+    }
+    for chan in 0..nbr_channels {
+        if !channel_is_active(active_channels_mask, chan) {
+            continue;
+        }
+        if let Some(wave_in) = wave_in {
+            let len_in = wave_in[chan].as_ref().len();
+            if len_in > frames_in || (!partial && len_in != frames_in) {
+                return Err(ResampleError::WrongNumberOfInputFrames {
+                    channel: chan,
+                    expected: frames_in,
+                    actual: len_in,
+                });
+            }
+        }
+        let len_out = wave_out[chan].as_mut().len();
+        if len_out < frames_out {
+            return Err(ResampleError::InsufficientOutputBufferSize {
+                channel: chan,
+                expected: frames_out,
+                actual: len_out,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Helper to get a channel from an optional input, where a missing input gives an empty channel.
+fn input_channel<T, Vin: AsRef<[T]>>(wave_in: Option<&[Vin]>, chan: usize) -> &[T] {
+    wave_in.map_or(&[], |wave_in| wave_in[chan].as_ref())
+}
+
+/// Helper to get the number of frames in an optional partial input,
+/// given by the longest of the active channels.
+/// A missing input means a full chunk of `chunk_size` frames.
+fn nbr_input_frames<T, Vin: AsRef<[T]>>(
+    wave_in: Option<&[Vin]>,
+    active_channels_mask: Option<&[bool]>,
+    chunk_size: usize,
+) -> usize {
+    match wave_in {
+        Some(wave_in) => wave_in
+            .iter()
+            .enumerate()
+            .filter(|(chan, _)| channel_is_active(active_channels_mask, *chan))
+            .map(|(_, wav)| wav.as_ref().len())
+            .max()
+            .unwrap_or(0),
+        None => chunk_size,
+    }
+}
+
+/// Helper to copy an input channel to the beginning of a buffer,
+/// and fill the rest of the buffer with zeros.
+fn copy_and_pad<T: Float>(wave_in: &[T], buffer: &mut [T]) {
+    let len = wave_in.len();
+    buffer[..len].copy_from_slice(wave_in);
+    buffer[len..].iter_mut().for_each(|val| *val = T::zero());
+}
+
+/// Get the number of sets of scratch buffers to use when processing `nbr_channels` channels.
+/// This is one, unless the `rayon` feature is enabled. Then it is one per thread
+/// of the rayon thread pool, but not more than the number of channels.
+fn nbr_scratch_buffers(nbr_channels: usize) -> usize {
+    #[cfg(feature = "rayon")]
+    {
+        rayon::current_num_threads().max(1).min(nbr_channels.max(1))
+    }
+    #[cfg(not(feature = "rayon"))]
+    {
+        let _ = nbr_channels;
+        1
+    }
+}
+
+/// Helper to call `f` for each channel, with the channel number and the state of that channel.
+/// With the `rayon` feature, the channels are processed in parallel on the rayon thread pool.
+fn for_each_channel<S, F>(channels: &mut [S], f: F)
+where
+    S: Send,
+    F: Fn(usize, &mut S) + Send + Sync,
+{
+    #[cfg(feature = "rayon")]
+    channels
+        .par_iter_mut()
+        .enumerate()
+        .for_each(|(chan, state)| f(chan, state));
+    #[cfg(not(feature = "rayon"))]
+    channels
+        .iter_mut()
+        .enumerate()
+        .for_each(|(chan, state)| f(chan, state));
+}
+
+/// Helper to call `f` for each channel like `for_each_channel`, also giving it a set of scratch buffers.
+/// The channels are split into as many groups as there are sets of scratch buffers.
+/// The groups are processed in parallel with the `rayon` feature,
+/// and the channels within a group one after the other, using the same scratch buffers.
+fn for_each_channel_with_scratch<S, C, F>(channels: &mut [S], scratch: &mut [C], f: F)
+where
+    S: Send,
+    C: Send,
+    F: Fn(usize, &mut S, &mut C) + Send + Sync,
+{
+    let group_size = ((channels.len() + scratch.len() - 1) / scratch.len()).max(1);
+    let process_group = |(group, (states, scratch)): (usize, (&mut [S], &mut C))| {
+        for (idx, state) in states.iter_mut().enumerate() {
+            f(group * group_size + idx, state, scratch);
+        }
+    };
+    #[cfg(feature = "rayon")]
+    channels
+        .par_chunks_mut(group_size)
+        .zip(scratch.par_iter_mut())
+        .enumerate()
+        .for_each(process_group);
+    #[cfg(not(feature = "rayon"))]
+    channels
+        .chunks_mut(group_size)
+        .zip(scratch.iter_mut())
+        .enumerate()
+        .for_each(process_group);
+}
+
+/// The time of the output frames of a sinc resampler in rational mode,
+/// counted in exact integer steps of `1 / factor` input frames.
+#[derive(Debug, Clone, Copy)]
+struct RationalPhase {
+    /// The number of steps between output frames, `fs_in / gcd(fs_in, fs_out)`.
+    step: isize,
+    /// The number of steps per input frame, `fs_out / gcd(fs_in, fs_out)`.
+    factor: isize,
+    /// The time of the last output frame, in steps relative to the start of the current chunk.
+    index: isize,
+}
+
+impl RationalPhase {
+    /// Create a new RationalPhase for resampling from `fs_in` to `fs_out`,
+    /// starting at the same time as the asynchronous resamplers.
+    fn new(fs_in: usize, fs_out: usize, sinc_len: usize) -> Self {
+        let gcd = integer::gcd(fs_in, fs_out);
+        let factor = (fs_out / gcd) as isize;
+        RationalPhase {
+            step: (fs_in / gcd) as isize,
+            factor,
+            index: -((sinc_len / 2) as isize) * factor,
+        }
+    }
+
+    /// Calculate the number of input frames needed by a SincFixedOut for the next chunk.
+    fn needed_input_size(&self, chunk_size: usize, sinc_len: usize) -> usize {
+        let end = self.index + chunk_size as isize * self.step;
+        let end_frames = (end + self.factor - 1).div_euclid(self.factor);
+        (end_frames + sinc_len as isize) as usize + 2
+    }
+}
+
+/// Helper to validate the sample rates for a sinc resampler in rational mode,
+/// and get the interpolation parameters with the oversampling factor needed for the ratio.
+fn rational_parameters(
+    fs_in: usize,
+    fs_out: usize,
+    parameters: InterpolationParameters,
+) -> ResampleResult<InterpolationParameters> {
+    if fs_in == 0 {
+        return Err(ResampleError::invalid_parameter(
+            "fs_in",
+            "must be larger than zero",
+        ));
+    }
+    if fs_out == 0 {
+        return Err(ResampleError::invalid_parameter(
+            "fs_out",
+            "must be larger than zero",
+        ));
+    }
+    let oversampling_factor = fs_out / integer::gcd(fs_in, fs_out);
+    if oversampling_factor > MAX_SYNC_OVERSAMPLING {
+        return Err(ResampleError::invalid_parameter(
+            "fs_out",
+            "must give a ratio with a denominator of at most 1024",
+        ));
+    }
+    Ok(InterpolationParameters {
+        oversampling_factor,
+        interpolation: InterpolationType::Nearest,
+        ..parameters
+    })
 }
 
 /// An asynchronous resampler that accepts a fixed number of audio frames for input
@@ -179,12 +887,19 @@ pub struct SincFixedIn<T> {
     chunk_size: usize,
     oversampling_factor: usize,
     last_index: f64,
+    current_buffer_fill: usize,
     resample_ratio: f64,
+    target_ratio: f64,
     resample_ratio_original: f64,
+    max_resample_ratio_relative: f64,
     sinc_len: usize,
-    sincs: Vec<Vec<T>>,
+    f_cutoff: f32,
+    sincs: SincTableBank<T>,
     buffer: Vec<Vec<T>>,
+    output_buffers: Vec<Vec<T>>,
     interpolation: InterpolationType,
+    rational: Option<RationalPhase>,
+    kernel: DotProductKernel,
 }
 
 /// An asynchronous resampler that return a fixed number of audio frames.
@@ -200,543 +915,2578 @@ pub struct SincFixedOut<T> {
     last_index: f64,
     current_buffer_fill: usize,
     resample_ratio: f64,
+    target_ratio: f64,
     resample_ratio_original: f64,
+    max_resample_ratio_relative: f64,
     sinc_len: usize,
-    sincs: Vec<Vec<T>>,
+    f_cutoff: f32,
+    sincs: SincTableBank<T>,
     buffer: Vec<Vec<T>>,
+    output_buffers: Vec<Vec<T>>,
     interpolation: InterpolationType,
+    rational: Option<RationalPhase>,
+    kernel: DotProductKernel,
 }
 
 macro_rules! impl_resampler {
     ($ft:ty, $rt:ty) => {
         impl $rt {
-            /// Calculate the scalar produt of an input wave and the selected sinc filter
-            fn get_sinc_interpolated(&self, wave: &[$ft], index: usize, subindex: usize) -> $ft {
-                let wave_cut = &wave[index..(index + self.sincs[subindex].len())];
-                wave_cut
-                    .chunks(8)
-                    .zip(self.sincs[subindex].chunks(8))
-                    .fold([0.0; 8], |acc, (x, y)| {
-                        [
-                            acc[0] + x[0] * y[0],
-                            acc[1] + x[1] * y[1],
-                            acc[2] + x[2] * y[2],
-                            acc[3] + x[3] * y[3],
-                            acc[4] + x[4] * y[4],
-                            acc[5] + x[5] * y[5],
-                            acc[6] + x[6] * y[6],
-                            acc[7] + x[7] * y[7],
-                        ]
+            /// Check that a new resample ratio is within the range given when creating the resampler.
+            fn check_ratio_bounds(&self, new_ratio: f64) -> ResampleResult<()> {
+                let min = self.resample_ratio_original / self.max_resample_ratio_relative;
+                let max = self.resample_ratio_original * self.max_resample_ratio_relative;
+                if new_ratio >= min && new_ratio <= max {
+                    Ok(())
+                } else {
+                    Err(ResampleError::RatioOutOfBounds {
+                        provided: new_ratio,
+                        min,
+                        max,
                     })
-                    .iter()
-                    .sum()
+                }
             }
 
-            /// Perform cubic polynomial interpolation to get value at x.
-            /// Input points are assumed to be at x = -1, 0, 1, 2
-            fn interp_cubic(&self, x: $ft, yvals: &[$ft]) -> $ft {
-                let a0 = yvals[1];
-                let a1 =
-                    -(1.0 / 3.0) * yvals[0] - 0.5 * yvals[1] + yvals[2] - (1.0 / 6.0) * yvals[3];
-                let a2 = 0.5 * (yvals[0] + yvals[2]) - yvals[1];
-                let a3 = 0.5 * (yvals[1] - yvals[2]) + (1.0 / 6.0) * (yvals[3] - yvals[0]);
-                a0 + a1 * x + a2 * x.powi(2) + a3 * x.powi(3)
+            /// Switch to the sinc table with a cutoff that suits the current resample ratio.
+            /// The cutoff is chosen a little lower than needed, see `SincTableBank`.
+            /// During a ramp, the cutoff is chosen for the lower of the two ratios.
+            fn update_sinc_cutoff(&mut self) {
+                let ratio = self.resample_ratio.min(self.target_ratio);
+                self.sincs
+                    .select(calculate_sinc_cutoff(self.f_cutoff, ratio));
             }
 
-            /// Linear interpolation between two points at x=0 and x=1
-            fn interp_lin(&self, x: $ft, yvals: &[$ft]) -> $ft {
-                (1.0 - x) * yvals[0] + x * yvals[1]
+            /// Set a new resample ratio, that is either used directly or ramped to during the next chunk.
+            fn update_resample_ratio(&mut self, new_ratio: f64, ramp: bool) -> ResampleResult<()> {
+                trace!("Change resample ratio to {}, ramp: {}", new_ratio, ramp);
+                if self.rational.is_some() {
+                    return Err(ResampleError::SyncNotAdjustable);
+                }
+                self.check_ratio_bounds(new_ratio)?;
+                self.target_ratio = new_ratio;
+                if !ramp {
+                    self.resample_ratio = new_ratio;
+                }
+                self.update_sinc_cutoff();
+                Ok(())
             }
-        }
-    };
-}
-impl_resampler!(f32, SincFixedIn<f32>);
-impl_resampler!(f64, SincFixedIn<f64>);
-impl_resampler!(f32, SincFixedOut<f32>);
-impl_resampler!(f64, SincFixedOut<f64>);
 
-impl<T: Float> SincFixedIn<T> {
-    /// Create a new SincFixedIn
-    ///
-    /// Parameters are:
-    /// - `resample_ratio`: Ratio between output and input sample rates.
-    /// - `parameters`: Parameters for interpolation, see `InterpolationParameters`
-    /// - `chunk_size`: size of input data in frames
-    /// - `nbr_channels`: number of channels in input/output
-    pub fn new(
-        resample_ratio: f64,
-        parameters: InterpolationParameters,
-        chunk_size: usize,
-        nbr_channels: usize,
-    ) -> Self {
-        debug!(
-            "Create new SincFixedIn, ratio: {}, chunk_size: {}, channels: {}, parameters: {:?}",
-            resample_ratio, chunk_size, nbr_channels, parameters
-        );
-        let sinc_cutoff = if resample_ratio >= 1.0 {
-            parameters.f_cutoff
-        } else {
-            parameters.f_cutoff * resample_ratio as f32
-        };
-        let sinc_len = 8 * (((parameters.sinc_len as f32) / 8.0).ceil() as usize);
-        debug!("sinc_len rounded up to {}", sinc_len);
-        let sincs = make_sincs(
-            sinc_len,
-            parameters.oversampling_factor,
-            sinc_cutoff,
-            parameters.window,
-        );
-        let buffer = vec![vec![T::zero(); chunk_size + 2 * sinc_len]; nbr_channels];
-        SincFixedIn {
-            nbr_channels,
-            chunk_size,
-            oversampling_factor: parameters.oversampling_factor,
-            last_index: -((sinc_len / 2) as f64),
-            resample_ratio,
-            resample_ratio_original: resample_ratio,
-            sinc_len,
-            sincs,
-            buffer,
-            interpolation: parameters.interpolation,
-        }
-    }
-}
+            /// Restore the sinc filters to the ones used when the resampler was created.
+            fn reset_sinc_cutoff(&mut self) {
+                self.sincs.reset();
+            }
 
-macro_rules! resampler_sincfixedin {
-    ($t:ty) => {
-        impl Resampler<$t> for SincFixedIn<$t> {
-            /// Resample a chunk of audio. The input length is fixed, and the output varies in length.
-            /// # Errors
-            ///
-            /// The function returns an error if the length of the input data is not equal
-            /// to the number of channels and chunk size defined when creating the instance.
-            fn process(&mut self, wave_in: &[Vec<$t>]) -> Res<Vec<Vec<$t>>> {
-                if wave_in.len() != self.nbr_channels {
-                    return Err(Box::new(ResamplerError::new(
-                        "Wrong number of channels in input",
-                    )));
-                }
-                if wave_in[0].len() != self.chunk_size {
-                    return Err(Box::new(ResamplerError::new(
-                        "Wrong number of frames in input",
-                    )));
-                }
-                let end_idx = self.chunk_size as isize - (self.sinc_len as isize + 1);
-                //update buffer with new data
-                for wav in self.buffer.iter_mut() {
-                    for idx in 0..(2 * self.sinc_len) {
-                        wav[idx] = wav[idx + self.chunk_size];
+            /// Calculate the scalar product of an input wave and the selected sinc filter,
+            /// using the selected kernel.
+            fn get_sinc_interpolated(&self, wave: &[$ft], index: usize, subindex: usize) -> $ft {
+                let sinc = &self.sincs.current().filters()[subindex];
+                let wave_cut = &wave[index..(index + sinc.len())];
+                <$ft>::dot_product(self.kernel, wave_cut, sinc)
+            }
+
+            /// Resample the active channels with `resample`, that is called with the buffer
+            /// of a channel and the output to write `nbr_frames` frames to.
+            /// With the `rayon` feature, the channels are processed in parallel.
+            fn resample_channels<Vout, F>(
+                &mut self,
+                wave_out: &mut [Vout],
+                active_channels_mask: Option<&[bool]>,
+                nbr_frames: usize,
+                resample: F,
+            ) where
+                Vout: AsMut<[$ft]>,
+                F: Fn(&Self, &[$ft], &mut [$ft]) + Send + Sync,
+            {
+                let mut output_buffers = std::mem::take(&mut self.output_buffers);
+                let this = &*self;
+                for_each_channel(&mut output_buffers, |chan, output| {
+                    if channel_is_active(active_channels_mask, chan) {
+                        resample(this, &this.buffer[chan], &mut output[..nbr_frames]);
                     }
-                }
-                for (chan, wav) in wave_in.iter().enumerate() {
-                    for (idx, sample) in wav.iter().enumerate() {
-                        self.buffer[chan][idx + 2 * self.sinc_len] = *sample;
+                });
+                for (chan, wav) in wave_out.iter_mut().enumerate() {
+                    if channel_is_active(active_channels_mask, chan) {
+                        wav.as_mut()[..nbr_frames]
+                            .copy_from_slice(&output_buffers[chan][..nbr_frames]);
                     }
                 }
+                self.output_buffers = output_buffers;
+            }
 
-                let mut idx = self.last_index;
-                let t_ratio = 1.0 / self.resample_ratio as f64;
-
-                let mut wave_out = vec![
-                    vec![
-                        0.0 as $t;
-                        (self.chunk_size as f64 * self.resample_ratio + 10.0)
-                            as usize
-                    ];
-                    self.nbr_channels
-                ];
-                let mut n = 0;
+            /// Resample a single channel from the buffer `buf` in rational mode,
+            /// writing one new sample to each element of `wave_out`.
+            /// The time is counted in steps of `1 / factor` input frames,
+            /// and the first new sample is calculated at time `idx + step`.
+            fn resample_channel_rational(
+                &self,
+                buf: &[$ft],
+                wave_out: &mut [$ft],
+                mut idx: isize,
+                step: isize,
+                factor: isize,
+            ) {
+                let offset = 2 * self.sinc_len as isize;
+                for sample in wave_out.iter_mut() {
+                    idx += step;
+                    *sample = self.get_sinc_interpolated(
+                        buf,
+                        (idx.div_euclid(factor) + offset) as usize,
+                        idx.rem_euclid(factor) as usize,
+                    );
+                }
+            }
 
+            /// Resample a single channel from the buffer `buf`,
+            /// writing one new sample to each element of `wave_out`.
+            /// The first new sample is calculated at time `idx + t_ratio`,
+            /// and the step between samples is increased by `t_ratio_increment` for each new sample.
+            fn resample_channel(
+                &self,
+                buf: &[$ft],
+                wave_out: &mut [$ft],
+                mut idx: f64,
+                mut t_ratio: f64,
+                t_ratio_increment: f64,
+            ) {
+                let offset = 2 * self.sinc_len as isize;
+                let factor = self.oversampling_factor as isize;
                 match self.interpolation {
+                    InterpolationType::Quintic => {
+                        let mut points = [0.0 as $ft; 6];
+                        let mut nearest = [(0isize, 0isize); 6];
+                        for sample in wave_out.iter_mut() {
+                            idx += t_ratio;
+                            t_ratio += t_ratio_increment;
+                            get_nearest_times_6(idx, factor, &mut nearest);
+                            let frac = idx * factor as f64 - (idx * factor as f64).floor();
+                            let frac_offset = frac as $ft;
+                            for (n, p) in nearest.iter().zip(points.iter_mut()) {
+                                *p = self.get_sinc_interpolated(
+                                    buf,
+                                    (n.0 + offset) as usize,
+                                    n.1 as usize,
+                                );
+                            }
+                            *sample = interp_quintic(frac_offset, &points);
+                        }
+                    }
                     InterpolationType::Cubic => {
-                        let mut points = vec![0.0 as $t; 4];
-                        let mut nearest = vec![(0isize, 0isize); 4];
-                        while idx < end_idx as f64 {
+                        let mut points = [0.0 as $ft; 4];
+                        let mut nearest = [(0isize, 0isize); 4];
+                        for sample in wave_out.iter_mut() {
                             idx += t_ratio;
-                            get_nearest_times_4(
-                                idx,
-                                self.oversampling_factor as isize,
-                                &mut nearest,
-                            );
-                            let frac = idx * self.oversampling_factor as f64
-                                - (idx * self.oversampling_factor as f64).floor();
-                            let frac_offset = frac as $t;
-                            for (chan, buf) in self.buffer.iter().enumerate() {
-                                for (n, p) in nearest.iter().zip(points.iter_mut()) {
-                                    *p = self.get_sinc_interpolated(
-                                        &buf,
-                                        (n.0 + 2 * self.sinc_len as isize) as usize,
-                                        n.1 as usize,
-                                    );
-                                }
-                                wave_out[chan][n] = self.interp_cubic(frac_offset, &points);
+                            t_ratio += t_ratio_increment;
+                            get_nearest_times_4(idx, factor, &mut nearest);
+                            let frac = idx * factor as f64 - (idx * factor as f64).floor();
+                            let frac_offset = frac as $ft;
+                            for (n, p) in nearest.iter().zip(points.iter_mut()) {
+                                *p = self.get_sinc_interpolated(
+                                    buf,
+                                    (n.0 + offset) as usize,
+                                    n.1 as usize,
+                                );
+                            }
+                            *sample = interp_cubic(frac_offset, &points);
+                        }
+                    }
+                    InterpolationType::Hermite => {
+                        let mut points = [0.0 as $ft; 4];
+                        let mut nearest = [(0isize, 0isize); 4];
+                        for sample in wave_out.iter_mut() {
+                            idx += t_ratio;
+                            t_ratio += t_ratio_increment;
+                            get_nearest_times_4(idx, factor, &mut nearest);
+                            let frac = idx * factor as f64 - (idx * factor as f64).floor();
+                            let frac_offset = frac as $ft;
+                            for (n, p) in nearest.iter().zip(points.iter_mut()) {
+                                *p = self.get_sinc_interpolated(
+                                    buf,
+                                    (n.0 + offset) as usize,
+                                    n.1 as usize,
+                                );
                             }
-                            n += 1;
+                            *sample = interp_hermite(frac_offset, &points);
                         }
                     }
                     InterpolationType::Linear => {
-                        let mut points = vec![0.0 as $t; 2];
-                        let mut nearest = vec![(0isize, 0isize); 2];
-                        while idx < end_idx as f64 {
+                        let mut points = [0.0 as $ft; 2];
+                        let mut nearest = [(0isize, 0isize); 2];
+                        for sample in wave_out.iter_mut() {
                             idx += t_ratio;
-                            get_nearest_times_2(
-                                idx,
-                                self.oversampling_factor as isize,
-                                &mut nearest,
-                            );
-                            let frac = idx * self.oversampling_factor as f64
-                                - (idx * self.oversampling_factor as f64).floor();
-                            let frac_offset = frac as $t;
-                            for (chan, buf) in self.buffer.iter().enumerate() {
-                                for (n, p) in nearest.iter().zip(points.iter_mut()) {
-                                    *p = self.get_sinc_interpolated(
-                                        &buf,
-                                        (n.0 + 2 * self.sinc_len as isize) as usize,
-                                        n.1 as usize,
-                                    );
-                                }
-                                wave_out[chan][n] = self.interp_lin(frac_offset, &points);
+                            t_ratio += t_ratio_increment;
+                            get_nearest_times_2(idx, factor, &mut nearest);
+                            let frac = idx * factor as f64 - (idx * factor as f64).floor();
+                            let frac_offset = frac as $ft;
+                            for (n, p) in nearest.iter().zip(points.iter_mut()) {
+                                *p = self.get_sinc_interpolated(
+                                    buf,
+                                    (n.0 + offset) as usize,
+                                    n.1 as usize,
+                                );
                             }
-                            n += 1;
+                            *sample = interp_lin(frac_offset, &points);
                         }
                     }
                     InterpolationType::Nearest => {
-                        let mut point;
                         let mut nearest;
-                        while idx < end_idx as f64 {
+                        for sample in wave_out.iter_mut() {
                             idx += t_ratio;
-                            nearest = get_nearest_time(idx, self.oversampling_factor as isize);
-                            for (chan, buf) in self.buffer.iter().enumerate() {
-                                point = self.get_sinc_interpolated(
-                                    &buf,
-                                    (nearest.0 + 2 * self.sinc_len as isize) as usize,
-                                    nearest.1 as usize,
-                                );
-                                wave_out[chan][n] = point;
-                            }
-                            n += 1;
+                            t_ratio += t_ratio_increment;
+                            nearest = get_nearest_time(idx, factor);
+                            *sample = self.get_sinc_interpolated(
+                                buf,
+                                (nearest.0 + offset) as usize,
+                                nearest.1 as usize,
+                            );
                         }
                     }
                 }
-
-                // store last index for next iteration
-                self.last_index = idx - self.chunk_size as f64;
-                for w in wave_out.iter_mut() {
-                    w.truncate(n);
-                }
-                trace!(
-                    "Resampling, {} frames in, {} frames out",
-                    wave_in[0].len(),
-                    wave_out[0].len()
-                );
-                Ok(wave_out)
             }
+        }
+    };
+}
+impl_resampler!(f32, SincFixedIn<f32>);
+impl_resampler!(f64, SincFixedIn<f64>);
+impl_resampler!(f32, SincFixedOut<f32>);
+impl_resampler!(f64, SincFixedOut<f64>);
 
-            /// Update the resample ratio. New value must be within +-10% of the original one
-            fn set_resample_ratio(&mut self, new_ratio: f64) -> Res<()> {
-                trace!("Change resample ratio to {}", new_ratio);
-                if (new_ratio / self.resample_ratio_original > 0.9)
-                    && (new_ratio / self.resample_ratio_original < 1.1)
-                {
-                    self.resample_ratio = new_ratio;
-                    Ok(())
-                } else {
-                    Err(Box::new(ResamplerError::new(
-                        "New resample ratio is too far off from original",
-                    )))
-                }
+impl<T: Float> SincFixedIn<T> {
+    /// Create a new SincFixedIn
+    ///
+    /// Parameters are:
+    /// - `resample_ratio`: Ratio between output and input sample rates.
+    /// - `parameters`: Parameters for interpolation, see `InterpolationParameters`
+    /// - `chunk_size`: size of input data in frames
+    /// - `nbr_channels`: number of channels in input/output
+    ///
+    /// The resample ratio can be changed by about 10% up or down from `resample_ratio`.
+    /// Use `new_with_ratio_range` for a larger range.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any of the parameters is invalid, see `try_new`.
+    pub fn new(
+        resample_ratio: f64,
+        parameters: InterpolationParameters,
+        chunk_size: usize,
+        nbr_channels: usize,
+    ) -> Self {
+        Self::try_new(resample_ratio, parameters, chunk_size, nbr_channels).unwrap()
+    }
+
+    /// Create a new SincFixedIn, checking that the parameters are valid.
+    /// Takes the same parameters as `new`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidParameter` error if the resample ratio is not a positive number,
+    /// if the chunk size or number of channels is zero,
+    /// or if the interpolation parameters are invalid, see `InterpolationParameters`.
+    pub fn try_new(
+        resample_ratio: f64,
+        parameters: InterpolationParameters,
+        chunk_size: usize,
+        nbr_channels: usize,
+    ) -> ResampleResult<Self> {
+        Self::try_new_with_ratio_range(
+            resample_ratio,
+            DEFAULT_MAX_RESAMPLE_RATIO_RELATIVE,
+            parameters,
+            chunk_size,
+            nbr_channels,
+        )
+    }
+
+    /// Create a new SincFixedIn, with a given range for changing the resample ratio.
+    /// The buffers are sized for the largest ratio in the range,
+    /// and the cutoff of the sinc filters is lowered when the ratio is reduced far enough
+    /// to need it.
+    /// The filters for all the cutoffs needed within the range are calculated up front,
+    /// with cutoffs about 4% apart. When downsampling, a wide range therefore needs
+    /// many tables of `sinc_len * oversampling_factor` coefficients,
+    /// 7 for the default range and 37 for a range of 2.0.
+    ///
+    /// Parameters are:
+    /// - `resample_ratio`: Ratio between output and input sample rates.
+    /// - `max_resample_ratio_relative`: Maximum ratio that the resample ratio can be changed by,
+    ///   relative to `resample_ratio`. Must be at least 1.0.
+    ///   A value of 10.0 means that the ratio can be set anywhere from `resample_ratio / 10.0`
+    ///   to `resample_ratio * 10.0`.
+    /// - `parameters`: Parameters for interpolation, see `InterpolationParameters`
+    /// - `chunk_size`: size of input data in frames
+    /// - `nbr_channels`: number of channels in input/output
+    ///
+    /// # Panics
+    ///
+    /// Panics if any of the parameters is invalid, see `try_new_with_ratio_range`.
+    pub fn new_with_ratio_range(
+        resample_ratio: f64,
+        max_resample_ratio_relative: f64,
+        parameters: InterpolationParameters,
+        chunk_size: usize,
+        nbr_channels: usize,
+    ) -> Self {
+        Self::try_new_with_ratio_range(
+            resample_ratio,
+            max_resample_ratio_relative,
+            parameters,
+            chunk_size,
+            nbr_channels,
+        )
+        .unwrap()
+    }
+
+    /// Create a new SincFixedIn with a given range for the resample ratio,
+    /// checking that the parameters are valid.
+    /// Takes the same parameters as `new_with_ratio_range`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidParameter` error if the maximum relative resample ratio
+    /// is less than 1.0, or for the same reasons as `try_new`.
+    pub fn try_new_with_ratio_range(
+        resample_ratio: f64,
+        max_resample_ratio_relative: f64,
+        parameters: InterpolationParameters,
+        chunk_size: usize,
+        nbr_channels: usize,
+    ) -> ResampleResult<Self> {
+        validate_sinc_parameters(
+            resample_ratio,
+            max_resample_ratio_relative,
+            &parameters,
+            chunk_size,
+            nbr_channels,
+        )?;
+        let sinc_table = Arc::new(SincTable::try_new(resample_ratio, &parameters)?);
+        Self::try_new_with_table(
+            resample_ratio,
+            max_resample_ratio_relative,
+            parameters,
+            sinc_table,
+            chunk_size,
+            nbr_channels,
+        )
+    }
+
+    /// Create a new SincFixedIn that uses a custom filter instead of the built-in windowed sinc,
+    /// see `CustomFilter`. The other parameters are the same as for `new`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any of the parameters is invalid, see `try_new_with_custom_filter`.
+    pub fn new_with_custom_filter(
+        resample_ratio: f64,
+        parameters: InterpolationParameters,
+        custom_filter: CustomFilter,
+        chunk_size: usize,
+        nbr_channels: usize,
+    ) -> Self {
+        Self::try_new_with_custom_filter(
+            resample_ratio,
+            parameters,
+            custom_filter,
+            chunk_size,
+            nbr_channels,
+        )
+        .unwrap()
+    }
+
+    /// Create a new SincFixedIn with a custom filter, checking that the parameters are valid.
+    /// Takes the same parameters as `new_with_custom_filter`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidParameter` error if a prototype filter does not have
+    /// `sinc_len * oversampling_factor` points or has a zero sum, or for the same reasons as `try_new`.
+    pub fn try_new_with_custom_filter(
+        resample_ratio: f64,
+        parameters: InterpolationParameters,
+        custom_filter: CustomFilter,
+        chunk_size: usize,
+        nbr_channels: usize,
+    ) -> ResampleResult<Self> {
+        validate_sinc_parameters(
+            resample_ratio,
+            DEFAULT_MAX_RESAMPLE_RATIO_RELATIVE,
+            &parameters,
+            chunk_size,
+            nbr_channels,
+        )?;
+        let sinc_table = Arc::new(SincTable::try_new_with_custom_filter(
+            resample_ratio,
+            &parameters,
+            custom_filter,
+        )?);
+        Ok(Self::from_table(
+            resample_ratio,
+            DEFAULT_MAX_RESAMPLE_RATIO_RELATIVE,
+            parameters,
+            sinc_table,
+            chunk_size,
+            nbr_channels,
+        ))
+    }
+
+    /// Create a new SincFixedIn that uses a table of sinc filters that can be shared
+    /// with other resamplers, see `SincTable`.
+    /// The table must have been calculated for the same resample ratio and parameters.
+    /// If the table holds a custom filter, the resampler uses that filter.
+    /// The other parameters are the same as for `new_with_ratio_range`.
+    ///
+    /// The table is used for the original resample ratio. The filters for the other cutoffs
+    /// that may be needed within the ratio range are calculated when the resampler is created,
+    /// and are not shared.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any of the parameters is invalid, see `try_new_with_table`.
+    pub fn new_with_table(
+        resample_ratio: f64,
+        max_resample_ratio_relative: f64,
+        parameters: InterpolationParameters,
+        sinc_table: Arc<SincTable<T>>,
+        chunk_size: usize,
+        nbr_channels: usize,
+    ) -> Self {
+        Self::try_new_with_table(
+            resample_ratio,
+            max_resample_ratio_relative,
+            parameters,
+            sinc_table,
+            chunk_size,
+            nbr_channels,
+        )
+        .unwrap()
+    }
+
+    /// Create a new SincFixedIn with a shared table of sinc filters, checking that the parameters are valid.
+    /// Takes the same parameters as `new_with_table`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidParameter` error if the table was calculated for a different
+    /// resample ratio or different parameters, or for the same reasons as `try_new_with_ratio_range`.
+    pub fn try_new_with_table(
+        resample_ratio: f64,
+        max_resample_ratio_relative: f64,
+        parameters: InterpolationParameters,
+        sinc_table: Arc<SincTable<T>>,
+        chunk_size: usize,
+        nbr_channels: usize,
+    ) -> ResampleResult<Self> {
+        validate_sinc_parameters(
+            resample_ratio,
+            max_resample_ratio_relative,
+            &parameters,
+            chunk_size,
+            nbr_channels,
+        )?;
+        if !sinc_table.matches(resample_ratio, &parameters) {
+            return Err(ResampleError::invalid_parameter(
+                "sinc_table",
+                "must be calculated for the same resample ratio and parameters",
+            ));
+        }
+        Ok(Self::from_table(
+            resample_ratio,
+            max_resample_ratio_relative,
+            parameters,
+            sinc_table,
+            chunk_size,
+            nbr_channels,
+        ))
+    }
+
+    /// Create a new SincFixedIn from a table of sinc filters, with parameters that have already been validated.
+    fn from_table(
+        resample_ratio: f64,
+        max_resample_ratio_relative: f64,
+        parameters: InterpolationParameters,
+        sinc_table: Arc<SincTable<T>>,
+        chunk_size: usize,
+        nbr_channels: usize,
+    ) -> Self {
+        debug!(
+            "Create new SincFixedIn, ratio: {}, max relative ratio: {}, chunk_size: {}, channels: {}, parameters: {:?}",
+            resample_ratio, max_resample_ratio_relative, chunk_size, nbr_channels, parameters
+        );
+        let sinc_len = sinc_table.sinc_len();
+        let buffer = vec![vec![T::zero(); chunk_size + 2 * sinc_len]; nbr_channels];
+        let output_frames_max =
+            (chunk_size as f64 * resample_ratio * max_resample_ratio_relative) as usize + 10;
+        let output_buffers = vec![vec![T::zero(); output_frames_max]; nbr_channels];
+        SincFixedIn {
+            nbr_channels,
+            chunk_size,
+            oversampling_factor: parameters.oversampling_factor,
+            last_index: -((sinc_len / 2) as f64),
+            current_buffer_fill: chunk_size,
+            resample_ratio,
+            target_ratio: resample_ratio,
+            resample_ratio_original: resample_ratio,
+            max_resample_ratio_relative,
+            sinc_len,
+            f_cutoff: parameters.f_cutoff,
+            sincs: SincTableBank::new(
+                sinc_table,
+                parameters.f_cutoff,
+                resample_ratio,
+                max_resample_ratio_relative,
+            ),
+            buffer,
+            output_buffers,
+            interpolation: parameters.interpolation,
+            rational: None,
+            kernel: DotProductKernel::detect(),
+        }
+    }
+
+    /// Create a new SincFixedIn in rational mode, for synchronous resampling from `fs_in` to `fs_out`.
+    ///
+    /// The ratio `fs_out / fs_in` is reduced to a fraction, and the time of each new sample
+    /// is stepped with exact integer arithmetic. The phase never drifts,
+    /// and the output is bit-reproducible over arbitrarily long streams,
+    /// regardless of how the stream is divided into chunks.
+    /// The sinc table is calculated with an oversampling factor equal to the denominator
+    /// `fs_out / gcd(fs_in, fs_out)`, which must be at most 1024.
+    /// The `oversampling_factor` of `parameters` is ignored, and the `interpolation`
+    /// is always replaced by `InterpolationType::Nearest`, whatever type is given.
+    /// Note that the table holds `sinc_len * fs_out / gcd(fs_in, fs_out)` coefficients,
+    /// for example 256 * 160 for 44.1 to 48 kHz.
+    ///
+    /// The resample ratio can not be changed in rational mode,
+    /// and `set_resample_ratio` returns a `SyncNotAdjustable` error.
+    ///
+    /// Parameters are:
+    /// - `fs_in`: Input sample rate.
+    /// - `fs_out`: Output sample rate.
+    /// - `parameters`: Parameters for interpolation, see `InterpolationParameters`
+    /// - `chunk_size`: size of input data in frames
+    /// - `nbr_channels`: number of channels in input/output
+    ///
+    /// # Panics
+    ///
+    /// Panics if any of the parameters is invalid, see `try_new_rational`.
+    pub fn new_rational(
+        fs_in: usize,
+        fs_out: usize,
+        parameters: InterpolationParameters,
+        chunk_size: usize,
+        nbr_channels: usize,
+    ) -> Self {
+        Self::try_new_rational(fs_in, fs_out, parameters, chunk_size, nbr_channels).unwrap()
+    }
+
+    /// Create a new SincFixedIn in rational mode, checking that the parameters are valid.
+    /// Takes the same parameters as `new_rational`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidParameter` error if any of the sample rates is zero,
+    /// if the denominator `fs_out / gcd(fs_in, fs_out)` is larger than 1024,
+    /// or for the same reasons as `try_new`.
+    pub fn try_new_rational(
+        fs_in: usize,
+        fs_out: usize,
+        parameters: InterpolationParameters,
+        chunk_size: usize,
+        nbr_channels: usize,
+    ) -> ResampleResult<Self> {
+        let parameters = rational_parameters(fs_in, fs_out, parameters)?;
+        let mut resampler = Self::try_new_with_ratio_range(
+            fs_out as f64 / fs_in as f64,
+            1.0,
+            parameters,
+            chunk_size,
+            nbr_channels,
+        )?;
+        resampler.rational = Some(RationalPhase::new(fs_in, fs_out, resampler.sinc_len));
+        debug!("Using rational mode, {:?}", resampler.rational);
+        Ok(resampler)
+    }
+
+    /// Select the kernel used for the scalar products between the input and the sinc filters.
+    /// The fastest kernel supported by the cpu is selected when the resampler is created.
+    /// Choosing `DotProductKernel::Portable` gives the same output on all machines.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidParameter` error if the kernel is not supported by the cpu.
+    pub fn set_dot_product_kernel(&mut self, kernel: DotProductKernel) -> ResampleResult<()> {
+        if !kernel.is_available() {
+            return Err(ResampleError::invalid_parameter(
+                "kernel",
+                "is not supported by this cpu",
+            ));
+        }
+        self.kernel = kernel;
+        Ok(())
+    }
+}
+
+macro_rules! resampler_sincfixedin {
+    ($t:ty) => {
+        impl Resampler<$t> for SincFixedIn<$t> {
+            /// Resample a chunk of audio. The input length is fixed, and the output varies in length.
+            /// # Errors
+            ///
+            /// The function returns an error if the length of the input data is not equal
+            /// to the number of channels and chunk size defined when creating the instance,
+            /// or if the output buffers are shorter than given by "output_frames_max".
+            fn process_into_buffer<Vin: AsRef<[$t]>, Vout: AsMut<[$t]>>(
+                &mut self,
+                wave_in: &[Vin],
+                wave_out: &mut [Vout],
+                active_channels_mask: Option<&[bool]>,
+            ) -> ResampleResult<usize> {
+                validate_buffers(
+                    Some(wave_in),
+                    wave_out,
+                    active_channels_mask,
+                    self.nbr_channels,
+                    self.chunk_size,
+                    self.output_frames_max(),
+                    false,
+                )?;
+                Ok(self.resample_chunk(
+                    Some(wave_in),
+                    wave_out,
+                    active_channels_mask,
+                    self.chunk_size,
+                ))
+            }
+
+            /// Resample a chunk of audio that may be shorter than the chunk size.
+            /// Only the given frames are consumed, and the output contains
+            /// the frames that correspond to them. If the channels have different lengths,
+            /// the shorter ones are padded with zeros to the length of the longest.
+            /// If `None` is given, a full chunk of zeros is processed.
+            /// # Errors
+            ///
+            /// The function returns an error if the input data does not have the number of channels
+            /// defined when creating the instance, if it is longer than the chunk size,
+            /// or if the output buffers are shorter than given by "output_frames_max".
+            fn process_partial_into_buffer<Vin: AsRef<[$t]>, Vout: AsMut<[$t]>>(
+                &mut self,
+                wave_in: Option<&[Vin]>,
+                wave_out: &mut [Vout],
+                active_channels_mask: Option<&[bool]>,
+            ) -> ResampleResult<usize> {
+                validate_buffers(
+                    wave_in,
+                    wave_out,
+                    active_channels_mask,
+                    self.nbr_channels,
+                    self.chunk_size,
+                    self.output_frames_max(),
+                    true,
+                )?;
+                let frames_in = nbr_input_frames(wave_in, active_channels_mask, self.chunk_size);
+                Ok(self.resample_chunk(wave_in, wave_out, active_channels_mask, frames_in))
+            }
+
+            /// Update the resample ratio. The new value must be within the range set when creating
+            /// the resampler, about 10% up or down for `new`, see `new_with_ratio_range`.
+            /// If the ratio changes so much that the anti-aliasing cutoff needs to be changed,
+            /// the resampler switches to filters with another cutoff, that were calculated
+            /// when it was created. This is fast, and does not allocate memory.
+            /// # Errors
+            ///
+            /// Returns a `RatioOutOfBounds` error if the new ratio is outside this range.
+            fn set_resample_ratio(&mut self, new_ratio: f64) -> ResampleResult<()> {
+                self.update_resample_ratio(new_ratio, false)
             }
             /// Update the resample ratio relative to the original one
-            fn set_resample_ratio_relative(&mut self, rel_ratio: f64) -> Res<()> {
+            fn set_resample_ratio_relative(&mut self, rel_ratio: f64) -> ResampleResult<()> {
                 let new_ratio = self.resample_ratio_original * rel_ratio;
                 self.set_resample_ratio(new_ratio)
             }
 
+            /// Update the resample ratio, with the same limits as `set_resample_ratio`.
+            /// The ratio changes linearly over the output frames of the next chunk.
+            /// # Errors
+            ///
+            /// Returns a `RatioOutOfBounds` error if the new ratio is out of range.
+            fn set_resample_ratio_ramped(&mut self, new_ratio: f64) -> ResampleResult<()> {
+                self.update_resample_ratio(new_ratio, true)
+            }
+            /// Update the resample ratio relative to the original one, with a ramp
+            fn set_resample_ratio_relative_ramped(&mut self, rel_ratio: f64) -> ResampleResult<()> {
+                let new_ratio = self.resample_ratio_original * rel_ratio;
+                self.set_resample_ratio_ramped(new_ratio)
+            }
+
             /// Query for the number of frames needed for the next call to "process".
             /// Will always return the chunk_size defined when creating the instance.
             fn nbr_frames_needed(&self) -> usize {
                 self.chunk_size
             }
+
+            /// Query for the maximum number of frames that a call to "process" can require.
+            /// Will always return the chunk_size defined when creating the instance.
+            fn input_frames_max(&self) -> usize {
+                self.chunk_size
+            }
+
+            /// Query for the maximum number of frames that a call to "process" can return.
+            /// This takes into account that the resample ratio can be increased
+            /// up to the limit given by `max_resample_ratio_relative`.
+            fn output_frames_max(&self) -> usize {
+                (self.chunk_size as f64
+                    * self.resample_ratio_original
+                    * self.max_resample_ratio_relative) as usize
+                    + 10
+            }
+
+            /// Query for the number of channels this resampler was created for.
+            fn nbr_channels(&self) -> usize {
+                self.nbr_channels
+            }
+
+            /// Get the current resample ratio.
+            /// During a ramp, this is the ratio at the start of the next chunk.
+            fn resample_ratio(&self) -> f64 {
+                self.resample_ratio
+            }
+
+            /// Get the delay of the resampler, in output frames.
+            /// The initial `last_index` cancels the delay of the sinc filters,
+            /// and what remains is a small offset of one input frame minus one oversampling step.
+            /// The delay is less than one output frame, and may be negative when downsampling.
+            fn output_delay(&self) -> f64 {
+                self.resample_ratio * (1.0 - 1.0 / self.oversampling_factor as f64) - 1.0
+            }
+
+            /// Reset the resampler to the state it had when it was created.
+            fn reset(&mut self) {
+                for buf in self.buffer.iter_mut() {
+                    buf.iter_mut().for_each(|val| *val = 0.0);
+                }
+                self.last_index = -((self.sinc_len / 2) as f64);
+                self.current_buffer_fill = self.chunk_size;
+                self.resample_ratio = self.resample_ratio_original;
+                self.target_ratio = self.resample_ratio_original;
+                self.reset_sinc_cutoff();
+                if let Some(rational) = self.rational {
+                    self.rational = Some(RationalPhase {
+                        index: -((self.sinc_len / 2) as isize) * rational.factor,
+                        ..rational
+                    });
+                }
+            }
+        }
+
+        impl SincFixedIn<$t> {
+            /// Resample `frames_in` frames of audio from input buffers that have already been validated.
+            /// Input channels that are shorter than `frames_in` are padded with zeros.
+            fn resample_chunk<Vin: AsRef<[$t]>, Vout: AsMut<[$t]>>(
+                &mut self,
+                wave_in: Option<&[Vin]>,
+                wave_out: &mut [Vout],
+                active_channels_mask: Option<&[bool]>,
+                frames_in: usize,
+            ) -> usize {
+                let end_idx = frames_in as isize - (self.sinc_len as isize + 1);
+                //update buffer with new data
+                for chan in 0..self.nbr_channels {
+                    if channel_is_active(active_channels_mask, chan) {
+                        let buf = &mut self.buffer[chan];
+                        buf.copy_within(
+                            self.current_buffer_fill
+                                ..(self.current_buffer_fill + 2 * self.sinc_len),
+                            0,
+                        );
+                        copy_and_pad(
+                            input_channel(wave_in, chan),
+                            &mut buf[(2 * self.sinc_len)..(2 * self.sinc_len + frames_in)],
+                        );
+                    }
+                }
+                self.current_buffer_fill = frames_in;
+
+                if let Some(rational) = self.rational {
+                    return self.resample_chunk_rational(
+                        rational,
+                        wave_out,
+                        active_channels_mask,
+                        frames_in,
+                    );
+                }
+
+                // When ramping, the step is changed linearly so that it reaches
+                // the step of the target ratio after the expected number of output frames.
+                let t_ratio = 1.0 / self.resample_ratio as f64;
+                let t_ratio_end = 1.0 / self.target_ratio as f64;
+                let approximate_nbr_frames =
+                    (2.0 * frames_in as f64 / (t_ratio + t_ratio_end)).max(1.0);
+                let t_ratio_increment = (t_ratio_end - t_ratio) / approximate_nbr_frames;
+                let mut idx = self.last_index;
+                let mut t_ratio_current = t_ratio;
+                let mut nbr_frames = 0;
+                while idx < end_idx as f64 {
+                    idx += t_ratio_current;
+                    t_ratio_current += t_ratio_increment;
+                    nbr_frames += 1;
+                }
+
+                let last_index = self.last_index;
+                self.resample_channels(
+                    wave_out,
+                    active_channels_mask,
+                    nbr_frames,
+                    |this, buf, out| {
+                        this.resample_channel(buf, out, last_index, t_ratio, t_ratio_increment)
+                    },
+                );
+
+                // store last index for next iteration
+                self.last_index = idx - frames_in as f64;
+                self.resample_ratio = self.target_ratio;
+                trace!(
+                    "Resampling, {} frames in, {} frames out",
+                    frames_in,
+                    nbr_frames,
+                );
+                nbr_frames
+            }
+
+            /// Resample the frames in the buffer in rational mode, using exact integer time steps.
+            fn resample_chunk_rational<Vout: AsMut<[$t]>>(
+                &mut self,
+                rational: RationalPhase,
+                wave_out: &mut [Vout],
+                active_channels_mask: Option<&[bool]>,
+                frames_in: usize,
+            ) -> usize {
+                let end_idx = (frames_in as isize - (self.sinc_len as isize + 1)) * rational.factor;
+                let mut idx = rational.index;
+                let mut nbr_frames = 0;
+                while idx < end_idx {
+                    idx += rational.step;
+                    nbr_frames += 1;
+                }
+
+                self.resample_channels(
+                    wave_out,
+                    active_channels_mask,
+                    nbr_frames,
+                    |this, buf, out| {
+                        this.resample_channel_rational(
+                            buf,
+                            out,
+                            rational.index,
+                            rational.step,
+                            rational.factor,
+                        )
+                    },
+                );
+
+                // store last index for next iteration
+                let index = idx - frames_in as isize * rational.factor;
+                self.rational = Some(RationalPhase { index, ..rational });
+                self.last_index = index as f64 / rational.factor as f64;
+                trace!(
+                    "Resampling in rational mode, {} frames in, {} frames out",
+                    frames_in,
+                    nbr_frames,
+                );
+                nbr_frames
+            }
         }
     };
 }
 resampler_sincfixedin!(f32);
 resampler_sincfixedin!(f64);
 
-impl<T: Float> SincFixedOut<T> {
-    /// Create a new SincFixedOut
-    ///
-    /// Parameters are:
-    /// - `resample_ratio`: Ratio between output and input sample rates.
-    /// - `parameters`: Parameters for interpolation, see `InterpolationParameters`
-    /// - `chunk_size`: size of output data in frames
-    /// - `nbr_channels`: number of channels in input/output
-    pub fn new(
-        resample_ratio: f64,
-        parameters: InterpolationParameters,
-        chunk_size: usize,
-        nbr_channels: usize,
-    ) -> Self {
-        debug!(
-            "Create new SincFixedOut, ratio: {}, chunk_size: {}, channels: {}, parameters: {:?}",
-            resample_ratio, chunk_size, nbr_channels, parameters
+impl<T: Float> SincFixedOut<T> {
+    /// Create a new SincFixedOut
+    ///
+    /// Parameters are:
+    /// - `resample_ratio`: Ratio between output and input sample rates.
+    /// - `parameters`: Parameters for interpolation, see `InterpolationParameters`
+    /// - `chunk_size`: size of output data in frames
+    /// - `nbr_channels`: number of channels in input/output
+    ///
+    /// The resample ratio can be changed by about 10% up or down from `resample_ratio`.
+    /// Use `new_with_ratio_range` for a larger range.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any of the parameters is invalid, see `try_new`.
+    pub fn new(
+        resample_ratio: f64,
+        parameters: InterpolationParameters,
+        chunk_size: usize,
+        nbr_channels: usize,
+    ) -> Self {
+        Self::try_new(resample_ratio, parameters, chunk_size, nbr_channels).unwrap()
+    }
+
+    /// Create a new SincFixedOut, checking that the parameters are valid.
+    /// Takes the same parameters as `new`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidParameter` error if the resample ratio is not a positive number,
+    /// if the chunk size or number of channels is zero,
+    /// or if the interpolation parameters are invalid, see `InterpolationParameters`.
+    pub fn try_new(
+        resample_ratio: f64,
+        parameters: InterpolationParameters,
+        chunk_size: usize,
+        nbr_channels: usize,
+    ) -> ResampleResult<Self> {
+        Self::try_new_with_ratio_range(
+            resample_ratio,
+            DEFAULT_MAX_RESAMPLE_RATIO_RELATIVE,
+            parameters,
+            chunk_size,
+            nbr_channels,
+        )
+    }
+
+    /// Create a new SincFixedOut, with a given range for changing the resample ratio.
+    /// The buffers are sized for the largest ratio in the range,
+    /// and the cutoff of the sinc filters is lowered when the ratio is reduced far enough
+    /// to need it.
+    /// The filters for all the cutoffs needed within the range are calculated up front,
+    /// with cutoffs about 4% apart. When downsampling, a wide range therefore needs
+    /// many tables of `sinc_len * oversampling_factor` coefficients,
+    /// 7 for the default range and 37 for a range of 2.0.
+    ///
+    /// Parameters are:
+    /// - `resample_ratio`: Ratio between output and input sample rates.
+    /// - `max_resample_ratio_relative`: Maximum ratio that the resample ratio can be changed by,
+    ///   relative to `resample_ratio`. Must be at least 1.0.
+    ///   A value of 10.0 means that the ratio can be set anywhere from `resample_ratio / 10.0`
+    ///   to `resample_ratio * 10.0`.
+    /// - `parameters`: Parameters for interpolation, see `InterpolationParameters`
+    /// - `chunk_size`: size of output data in frames
+    /// - `nbr_channels`: number of channels in input/output
+    ///
+    /// # Panics
+    ///
+    /// Panics if any of the parameters is invalid, see `try_new_with_ratio_range`.
+    pub fn new_with_ratio_range(
+        resample_ratio: f64,
+        max_resample_ratio_relative: f64,
+        parameters: InterpolationParameters,
+        chunk_size: usize,
+        nbr_channels: usize,
+    ) -> Self {
+        Self::try_new_with_ratio_range(
+            resample_ratio,
+            max_resample_ratio_relative,
+            parameters,
+            chunk_size,
+            nbr_channels,
+        )
+        .unwrap()
+    }
+
+    /// Create a new SincFixedOut with a given range for the resample ratio,
+    /// checking that the parameters are valid.
+    /// Takes the same parameters as `new_with_ratio_range`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidParameter` error if the maximum relative resample ratio
+    /// is less than 1.0, or for the same reasons as `try_new`.
+    pub fn try_new_with_ratio_range(
+        resample_ratio: f64,
+        max_resample_ratio_relative: f64,
+        parameters: InterpolationParameters,
+        chunk_size: usize,
+        nbr_channels: usize,
+    ) -> ResampleResult<Self> {
+        validate_sinc_parameters(
+            resample_ratio,
+            max_resample_ratio_relative,
+            &parameters,
+            chunk_size,
+            nbr_channels,
+        )?;
+        let sinc_table = Arc::new(SincTable::try_new(resample_ratio, &parameters)?);
+        Self::try_new_with_table(
+            resample_ratio,
+            max_resample_ratio_relative,
+            parameters,
+            sinc_table,
+            chunk_size,
+            nbr_channels,
+        )
+    }
+
+    /// Create a new SincFixedOut that uses a custom filter instead of the built-in windowed sinc,
+    /// see `CustomFilter`. The other parameters are the same as for `new`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any of the parameters is invalid, see `try_new_with_custom_filter`.
+    pub fn new_with_custom_filter(
+        resample_ratio: f64,
+        parameters: InterpolationParameters,
+        custom_filter: CustomFilter,
+        chunk_size: usize,
+        nbr_channels: usize,
+    ) -> Self {
+        Self::try_new_with_custom_filter(
+            resample_ratio,
+            parameters,
+            custom_filter,
+            chunk_size,
+            nbr_channels,
+        )
+        .unwrap()
+    }
+
+    /// Create a new SincFixedOut with a custom filter, checking that the parameters are valid.
+    /// Takes the same parameters as `new_with_custom_filter`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidParameter` error if a prototype filter does not have
+    /// `sinc_len * oversampling_factor` points or has a zero sum, or for the same reasons as `try_new`.
+    pub fn try_new_with_custom_filter(
+        resample_ratio: f64,
+        parameters: InterpolationParameters,
+        custom_filter: CustomFilter,
+        chunk_size: usize,
+        nbr_channels: usize,
+    ) -> ResampleResult<Self> {
+        validate_sinc_parameters(
+            resample_ratio,
+            DEFAULT_MAX_RESAMPLE_RATIO_RELATIVE,
+            &parameters,
+            chunk_size,
+            nbr_channels,
+        )?;
+        let sinc_table = Arc::new(SincTable::try_new_with_custom_filter(
+            resample_ratio,
+            &parameters,
+            custom_filter,
+        )?);
+        Ok(Self::from_table(
+            resample_ratio,
+            DEFAULT_MAX_RESAMPLE_RATIO_RELATIVE,
+            parameters,
+            sinc_table,
+            chunk_size,
+            nbr_channels,
+        ))
+    }
+
+    /// Create a new SincFixedOut that uses a table of sinc filters that can be shared
+    /// with other resamplers, see `SincTable`.
+    /// The table must have been calculated for the same resample ratio and parameters.
+    /// If the table holds a custom filter, the resampler uses that filter.
+    /// The other parameters are the same as for `new_with_ratio_range`.
+    ///
+    /// The table is used for the original resample ratio. The filters for the other cutoffs
+    /// that may be needed within the ratio range are calculated when the resampler is created,
+    /// and are not shared.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any of the parameters is invalid, see `try_new_with_table`.
+    pub fn new_with_table(
+        resample_ratio: f64,
+        max_resample_ratio_relative: f64,
+        parameters: InterpolationParameters,
+        sinc_table: Arc<SincTable<T>>,
+        chunk_size: usize,
+        nbr_channels: usize,
+    ) -> Self {
+        Self::try_new_with_table(
+            resample_ratio,
+            max_resample_ratio_relative,
+            parameters,
+            sinc_table,
+            chunk_size,
+            nbr_channels,
+        )
+        .unwrap()
+    }
+
+    /// Create a new SincFixedOut with a shared table of sinc filters, checking that the parameters are valid.
+    /// Takes the same parameters as `new_with_table`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidParameter` error if the table was calculated for a different
+    /// resample ratio or different parameters, or for the same reasons as `try_new_with_ratio_range`.
+    pub fn try_new_with_table(
+        resample_ratio: f64,
+        max_resample_ratio_relative: f64,
+        parameters: InterpolationParameters,
+        sinc_table: Arc<SincTable<T>>,
+        chunk_size: usize,
+        nbr_channels: usize,
+    ) -> ResampleResult<Self> {
+        validate_sinc_parameters(
+            resample_ratio,
+            max_resample_ratio_relative,
+            &parameters,
+            chunk_size,
+            nbr_channels,
+        )?;
+        if !sinc_table.matches(resample_ratio, &parameters) {
+            return Err(ResampleError::invalid_parameter(
+                "sinc_table",
+                "must be calculated for the same resample ratio and parameters",
+            ));
+        }
+        Ok(Self::from_table(
+            resample_ratio,
+            max_resample_ratio_relative,
+            parameters,
+            sinc_table,
+            chunk_size,
+            nbr_channels,
+        ))
+    }
+
+    /// Create a new SincFixedOut from a table of sinc filters, with parameters that have already been validated.
+    fn from_table(
+        resample_ratio: f64,
+        max_resample_ratio_relative: f64,
+        parameters: InterpolationParameters,
+        sinc_table: Arc<SincTable<T>>,
+        chunk_size: usize,
+        nbr_channels: usize,
+    ) -> Self {
+        debug!(
+            "Create new SincFixedOut, ratio: {}, max relative ratio: {}, chunk_size: {}, channels: {}, parameters: {:?}",
+            resample_ratio, max_resample_ratio_relative, chunk_size, nbr_channels, parameters
+        );
+        let sinc_len = sinc_table.sinc_len();
+        let needed_input_size =
+            (chunk_size as f64 / resample_ratio).ceil() as usize + 2 + sinc_len / 2;
+        let input_frames_max = (chunk_size as f64 * max_resample_ratio_relative / resample_ratio)
+            .ceil() as usize
+            + sinc_len / 2
+            + 3;
+        let buffer = vec![vec![T::zero(); input_frames_max + 2 * sinc_len]; nbr_channels];
+        let output_buffers = vec![vec![T::zero(); chunk_size]; nbr_channels];
+        SincFixedOut {
+            nbr_channels,
+            chunk_size,
+            needed_input_size,
+            oversampling_factor: parameters.oversampling_factor,
+            last_index: -((sinc_len / 2) as f64),
+            current_buffer_fill: needed_input_size,
+            resample_ratio,
+            target_ratio: resample_ratio,
+            resample_ratio_original: resample_ratio,
+            max_resample_ratio_relative,
+            sinc_len,
+            f_cutoff: parameters.f_cutoff,
+            sincs: SincTableBank::new(
+                sinc_table,
+                parameters.f_cutoff,
+                resample_ratio,
+                max_resample_ratio_relative,
+            ),
+            buffer,
+            output_buffers,
+            interpolation: parameters.interpolation,
+            rational: None,
+            kernel: DotProductKernel::detect(),
+        }
+    }
+
+    /// Create a new SincFixedOut in rational mode, for synchronous resampling from `fs_in` to `fs_out`.
+    ///
+    /// The ratio `fs_out / fs_in` is reduced to a fraction, and the time of each new sample
+    /// is stepped with exact integer arithmetic. The phase never drifts,
+    /// and the output is bit-reproducible over arbitrarily long streams,
+    /// regardless of how the stream is divided into chunks.
+    /// The sinc table is calculated with an oversampling factor equal to the denominator
+    /// `fs_out / gcd(fs_in, fs_out)`, which must be at most 1024.
+    /// The `oversampling_factor` of `parameters` is ignored, and the `interpolation`
+    /// is always replaced by `InterpolationType::Nearest`, whatever type is given.
+    /// Note that the table holds `sinc_len * fs_out / gcd(fs_in, fs_out)` coefficients,
+    /// for example 256 * 160 for 44.1 to 48 kHz.
+    ///
+    /// The resample ratio can not be changed in rational mode,
+    /// and `set_resample_ratio` returns a `SyncNotAdjustable` error.
+    ///
+    /// Parameters are:
+    /// - `fs_in`: Input sample rate.
+    /// - `fs_out`: Output sample rate.
+    /// - `parameters`: Parameters for interpolation, see `InterpolationParameters`
+    /// - `chunk_size`: size of output data in frames
+    /// - `nbr_channels`: number of channels in input/output
+    ///
+    /// # Panics
+    ///
+    /// Panics if any of the parameters is invalid, see `try_new_rational`.
+    pub fn new_rational(
+        fs_in: usize,
+        fs_out: usize,
+        parameters: InterpolationParameters,
+        chunk_size: usize,
+        nbr_channels: usize,
+    ) -> Self {
+        Self::try_new_rational(fs_in, fs_out, parameters, chunk_size, nbr_channels).unwrap()
+    }
+
+    /// Create a new SincFixedOut in rational mode, checking that the parameters are valid.
+    /// Takes the same parameters as `new_rational`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidParameter` error if any of the sample rates is zero,
+    /// if the denominator `fs_out / gcd(fs_in, fs_out)` is larger than 1024,
+    /// or for the same reasons as `try_new`.
+    pub fn try_new_rational(
+        fs_in: usize,
+        fs_out: usize,
+        parameters: InterpolationParameters,
+        chunk_size: usize,
+        nbr_channels: usize,
+    ) -> ResampleResult<Self> {
+        let parameters = rational_parameters(fs_in, fs_out, parameters)?;
+        let mut resampler = Self::try_new_with_ratio_range(
+            fs_out as f64 / fs_in as f64,
+            1.0,
+            parameters,
+            chunk_size,
+            nbr_channels,
+        )?;
+        let rational = RationalPhase::new(fs_in, fs_out, resampler.sinc_len);
+        resampler.rational = Some(rational);
+        resampler.needed_input_size = rational.needed_input_size(chunk_size, resampler.sinc_len);
+        resampler.current_buffer_fill = resampler.needed_input_size;
+        debug!("Using rational mode, {:?}", resampler.rational);
+        Ok(resampler)
+    }
+
+    /// Select the kernel used for the scalar products between the input and the sinc filters.
+    /// The fastest kernel supported by the cpu is selected when the resampler is created.
+    /// Choosing `DotProductKernel::Portable` gives the same output on all machines.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidParameter` error if the kernel is not supported by the cpu.
+    pub fn set_dot_product_kernel(&mut self, kernel: DotProductKernel) -> ResampleResult<()> {
+        if !kernel.is_available() {
+            return Err(ResampleError::invalid_parameter(
+                "kernel",
+                "is not supported by this cpu",
+            ));
+        }
+        self.kernel = kernel;
+        Ok(())
+    }
+}
+
+macro_rules! resampler_sincfixedout {
+    ($t:ty) => {
+        impl Resampler<$t> for SincFixedOut<$t> {
+            /// Query for the number of frames needed for the next call to "process".
+            fn nbr_frames_needed(&self) -> usize {
+                self.needed_input_size
+            }
+
+            /// Query for the maximum number of frames that a call to "process" can require.
+            /// This takes into account that the resample ratio can be decreased
+            /// down to the limit given by `max_resample_ratio_relative`.
+            fn input_frames_max(&self) -> usize {
+                (self.chunk_size as f64 * self.max_resample_ratio_relative
+                    / self.resample_ratio_original)
+                    .ceil() as usize
+                    + self.sinc_len / 2
+                    + 3
+            }
+
+            /// Query for the maximum number of frames that a call to "process" can return.
+            /// Will always return the chunk_size defined when creating the instance.
+            fn output_frames_max(&self) -> usize {
+                self.chunk_size
+            }
+
+            /// Query for the number of channels this resampler was created for.
+            fn nbr_channels(&self) -> usize {
+                self.nbr_channels
+            }
+
+            /// Get the current resample ratio.
+            /// During a ramp, this is the ratio at the start of the next chunk.
+            fn resample_ratio(&self) -> f64 {
+                self.resample_ratio
+            }
+
+            /// Get the delay of the resampler, in output frames.
+            /// The initial `last_index` cancels the delay of the sinc filters,
+            /// and what remains is a small offset of one input frame minus one oversampling step.
+            /// The delay is less than one output frame, and may be negative when downsampling.
+            fn output_delay(&self) -> f64 {
+                self.resample_ratio * (1.0 - 1.0 / self.oversampling_factor as f64) - 1.0
+            }
+
+            /// Reset the resampler to the state it had when it was created.
+            fn reset(&mut self) {
+                for buf in self.buffer.iter_mut() {
+                    buf.iter_mut().for_each(|val| *val = 0.0);
+                }
+                self.last_index = -((self.sinc_len / 2) as f64);
+                self.resample_ratio = self.resample_ratio_original;
+                self.target_ratio = self.resample_ratio_original;
+                self.reset_sinc_cutoff();
+                self.needed_input_size = (self.chunk_size as f64 / self.resample_ratio).ceil()
+                    as usize
+                    + 2
+                    + self.sinc_len / 2;
+                if let Some(rational) = self.rational {
+                    self.rational = Some(RationalPhase {
+                        index: -((self.sinc_len / 2) as isize) * rational.factor,
+                        ..rational
+                    });
+                    self.needed_input_size = self.calculate_needed_input_size();
+                }
+                self.current_buffer_fill = self.needed_input_size;
+            }
+
+            /// Update the resample ratio. The new value must be within the range set when creating
+            /// the resampler, about 10% up or down for `new`, see `new_with_ratio_range`.
+            /// If the ratio changes so much that the anti-aliasing cutoff needs to be changed,
+            /// the resampler switches to filters with another cutoff, that were calculated
+            /// when it was created. This is fast, and does not allocate memory.
+            /// # Errors
+            ///
+            /// Returns a `RatioOutOfBounds` error if the new ratio is outside this range.
+            fn set_resample_ratio(&mut self, new_ratio: f64) -> ResampleResult<()> {
+                self.update_resample_ratio(new_ratio, false)?;
+                self.needed_input_size = self.calculate_needed_input_size();
+                Ok(())
+            }
+
+            /// Update the resample ratio relative to the original one
+            fn set_resample_ratio_relative(&mut self, rel_ratio: f64) -> ResampleResult<()> {
+                let new_ratio = self.resample_ratio_original * rel_ratio;
+                self.set_resample_ratio(new_ratio)
+            }
+
+            /// Update the resample ratio, with the same limits as `set_resample_ratio`.
+            /// The ratio changes linearly over the output frames of the next chunk,
+            /// and "nbr_frames_needed" returns the number of input frames needed for the ramped chunk.
+            /// # Errors
+            ///
+            /// Returns a `RatioOutOfBounds` error if the new ratio is out of range.
+            fn set_resample_ratio_ramped(&mut self, new_ratio: f64) -> ResampleResult<()> {
+                self.update_resample_ratio(new_ratio, true)?;
+                self.needed_input_size = self.calculate_needed_input_size();
+                Ok(())
+            }
+
+            /// Update the resample ratio relative to the original one, with a ramp
+            fn set_resample_ratio_relative_ramped(&mut self, rel_ratio: f64) -> ResampleResult<()> {
+                let new_ratio = self.resample_ratio_original * rel_ratio;
+                self.set_resample_ratio_ramped(new_ratio)
+            }
+
+            /// Resample a chunk of audio. The required input length is provided by
+            /// the "nbr_frames_needed" function, and the output length is fixed.
+            /// # Errors
+            ///
+            /// The function returns an error if the length of the input data is not
+            /// equal to the number of channels defined when creating the instance,
+            /// and the number of audio frames given by "nbr_frames_needed",
+            /// or if the output buffers are shorter than the chunk size.
+            fn process_into_buffer<Vin: AsRef<[$t]>, Vout: AsMut<[$t]>>(
+                &mut self,
+                wave_in: &[Vin],
+                wave_out: &mut [Vout],
+                active_channels_mask: Option<&[bool]>,
+            ) -> ResampleResult<usize> {
+                validate_buffers(
+                    Some(wave_in),
+                    wave_out,
+                    active_channels_mask,
+                    self.nbr_channels,
+                    self.needed_input_size,
+                    self.chunk_size,
+                    false,
+                )?;
+                self.resample_chunk(Some(wave_in), wave_out, active_channels_mask);
+                Ok(self.chunk_size)
+            }
+
+            /// Resample a chunk of audio that may be shorter than given by "nbr_frames_needed".
+            /// The input is padded with zeros to the needed length.
+            /// # Errors
+            ///
+            /// The function returns an error if the input data does not have the number of channels
+            /// defined when creating the instance, if it is longer than given by "nbr_frames_needed",
+            /// or if the output buffers are shorter than the chunk size.
+            fn process_partial_into_buffer<Vin: AsRef<[$t]>, Vout: AsMut<[$t]>>(
+                &mut self,
+                wave_in: Option<&[Vin]>,
+                wave_out: &mut [Vout],
+                active_channels_mask: Option<&[bool]>,
+            ) -> ResampleResult<usize> {
+                validate_buffers(
+                    wave_in,
+                    wave_out,
+                    active_channels_mask,
+                    self.nbr_channels,
+                    self.needed_input_size,
+                    self.chunk_size,
+                    true,
+                )?;
+                self.resample_chunk(wave_in, wave_out, active_channels_mask);
+                Ok(self.chunk_size)
+            }
+        }
+
+        impl SincFixedOut<$t> {
+            /// Get the step between output frames at the start of the next chunk,
+            /// and the increment of the step for each output frame.
+            /// When ramping, the step reaches the step of the target ratio at the end of the chunk.
+            fn ramp_steps(&self) -> (f64, f64) {
+                let t_ratio = 1.0 / self.resample_ratio as f64;
+                let t_ratio_end = 1.0 / self.target_ratio as f64;
+                (t_ratio, (t_ratio_end - t_ratio) / self.chunk_size as f64)
+            }
+
+            /// Calculate the number of input frames needed for the next chunk.
+            /// The output frames span `chunk_size` steps, plus the extra input frames
+            /// that are consumed when the step grows during a ramp.
+            fn calculate_needed_input_size(&self) -> usize {
+                if let Some(rational) = self.rational {
+                    return rational.needed_input_size(self.chunk_size, self.sinc_len);
+                }
+                let (_, t_ratio_increment) = self.ramp_steps();
+                let ramp_extra =
+                    t_ratio_increment * (self.chunk_size * (self.chunk_size - 1) / 2) as f64;
+                (self.last_index as f32
+                    + self.chunk_size as f32 / self.resample_ratio as f32
+                    + ramp_extra as f32
+                    + self.sinc_len as f32)
+                    .ceil() as usize
+                    + 2
+            }
+
+            /// Resample a chunk of audio from input buffers that have already been validated.
+            /// Input channels that are shorter than the needed length are padded with zeros.
+            fn resample_chunk<Vin: AsRef<[$t]>, Vout: AsMut<[$t]>>(
+                &mut self,
+                wave_in: Option<&[Vin]>,
+                wave_out: &mut [Vout],
+                active_channels_mask: Option<&[bool]>,
+            ) {
+                //update buffer with new data
+                for chan in 0..self.nbr_channels {
+                    if channel_is_active(active_channels_mask, chan) {
+                        let buf = &mut self.buffer[chan];
+                        buf.copy_within(
+                            self.current_buffer_fill..(self.current_buffer_fill + 2 * self.sinc_len),
+                            0,
+                        );
+                        copy_and_pad(
+                            input_channel(wave_in, chan),
+                            &mut buf[(2 * self.sinc_len)..(2 * self.sinc_len + self.needed_input_size)],
+                        );
+                    }
+                }
+                self.current_buffer_fill = self.needed_input_size;
+
+                if let Some(rational) = self.rational {
+                    let chunk_size = self.chunk_size;
+                    self.resample_channels(wave_out, active_channels_mask, chunk_size, |this, buf, out| {
+                        this.resample_channel_rational(buf, out, rational.index, rational.step, rational.factor)
+                    });
+                    let index = rational.index + self.chunk_size as isize * rational.step
+                        - self.current_buffer_fill as isize * rational.factor;
+                    self.rational = Some(RationalPhase { index, ..rational });
+                    self.last_index = index as f64 / rational.factor as f64;
+                    self.needed_input_size = self.calculate_needed_input_size();
+                    trace!(
+                        "Resampling in rational mode, {} frames in, {} frames out. Next needed length: {} frames",
+                        self.current_buffer_fill,
+                        self.chunk_size,
+                        self.needed_input_size,
+                    );
+                    return;
+                }
+
+                let (t_ratio, t_ratio_increment) = self.ramp_steps();
+                let mut idx = self.last_index;
+                let mut t_ratio_current = t_ratio;
+                for _ in 0..self.chunk_size {
+                    idx += t_ratio_current;
+                    t_ratio_current += t_ratio_increment;
+                }
+
+                let last_index = self.last_index;
+                let chunk_size = self.chunk_size;
+                self.resample_channels(wave_out, active_channels_mask, chunk_size, |this, buf, out| {
+                    this.resample_channel(buf, out, last_index, t_ratio, t_ratio_increment)
+                });
+
+                // store last index for next iteration
+                self.last_index = idx - self.current_buffer_fill as f64;
+                self.resample_ratio = self.target_ratio;
+                self.needed_input_size = self.calculate_needed_input_size();
+                trace!(
+                    "Resampling, {} frames in, {} frames out. Next needed length: {} frames, last index {}",
+                    self.current_buffer_fill,
+                    self.chunk_size,
+                    self.needed_input_size,
+                    self.last_index
+                );
+            }
+        }
+    }
+}
+resampler_sincfixedout!(f32);
+resampler_sincfixedout!(f64);
+
+#[cfg(test)]
+mod tests {
+    use crate::sinc::sinc;
+    use crate::windows::make_window;
+    use crate::CustomFilter;
+    use crate::DotProductKernel;
+    use crate::InterpolationParameters;
+    use crate::InterpolationType;
+    use crate::ResampleError;
+    use crate::Resampler;
+    use crate::WindowFunction;
+    use crate::{
+        FftFilterParameters, FftFixedIn, FftFixedInOut, FftFixedOut, InterleavedResampler,
+        SincFixedIn, SincFixedOut,
+    };
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::cell::Cell;
+
+    /// Allocator that counts the allocations made by each thread.
+    struct CountingAllocator;
+
+    thread_local! {
+        static ALLOCATIONS: Cell<usize> = Cell::new(0);
+    }
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout)
+        }
+    }
+
+    #[global_allocator]
+    static GLOBAL: CountingAllocator = CountingAllocator;
+
+    /// Run a closure and return the number of allocations it made.
+    fn count_allocations<F: FnMut()>(mut f: F) -> usize {
+        let before = ALLOCATIONS.with(|count| count.get());
+        f();
+        ALLOCATIONS.with(|count| count.get()) - before
+    }
+
+    /// Process a few chunks with "process_into_buffer" and return the number of allocations.
+    fn allocations_when_processing<R: Resampler<f64>>(resampler: &mut R) -> usize {
+        let nbr_channels = resampler.nbr_channels();
+        let mut waves_in: Vec<Vec<f64>> = (0..nbr_channels)
+            .map(|_| Vec::with_capacity(4096))
+            .collect();
+        let mut waves_out = vec![vec![0.0f64; resampler.output_frames_max()]; nbr_channels];
+        count_allocations(|| {
+            for _ in 0..10 {
+                let frames = resampler.nbr_frames_needed();
+                for wav in waves_in.iter_mut() {
+                    wav.resize(frames, 0.0);
+                }
+                resampler
+                    .process_into_buffer(&waves_in, &mut waves_out, None)
+                    .unwrap();
+            }
+        })
+    }
+
+    /// Process a few chunks of a test signal and return all the output frames.
+    fn process_test_signal<R: Resampler<f64>>(resampler: &mut R) -> Vec<Vec<f64>> {
+        let nbr_channels = resampler.nbr_channels();
+        let mut output = vec![Vec::new(); nbr_channels];
+        let mut frame = 0;
+        for _ in 0..5 {
+            let frames = resampler.nbr_frames_needed();
+            let waves_in: Vec<Vec<f64>> = (0..nbr_channels)
+                .map(|chan| {
+                    (frame..frame + frames)
+                        .map(|n| (0.01 * (chan + 1) as f64 * n as f64).sin())
+                        .collect()
+                })
+                .collect();
+            frame += frames;
+            let waves_out = resampler.process(&waves_in).unwrap();
+            for (out, wav) in output.iter_mut().zip(waves_out.iter()) {
+                out.extend_from_slice(wav);
+            }
+        }
+        output
+    }
+
+    /// Check that a resampler gives the same output after a reset as when it was new.
+    fn check_reset<R: Resampler<f64>>(resampler: &mut R) {
+        let first = process_test_signal(resampler);
+        let _ = resampler.set_resample_ratio_relative(1.05);
+        process_test_signal(resampler);
+        resampler.reset();
+        let second = process_test_signal(resampler);
+        assert_eq!(first, second);
+    }
+
+    /// Measure the delay in output frames of an impulse, given to the resampler after a few chunks.
+    /// The position of the impulse in the output is found as the centroid of the response.
+    fn measure_impulse_delay<R: Resampler<f64>>(resampler: &mut R, ratio: f64) -> f64 {
+        let impulse_frame = 3000;
+        let mut output = Vec::new();
+        let mut frame = 0;
+        for _ in 0..12 {
+            let frames = resampler.nbr_frames_needed();
+            let mut waves_in = vec![vec![0.0f64; frames]; resampler.nbr_channels()];
+            if (frame..frame + frames).contains(&impulse_frame) {
+                waves_in[0][impulse_frame - frame] = 1.0;
+            }
+            frame += frames;
+            let waves_out = resampler.process(&waves_in).unwrap();
+            output.extend_from_slice(&waves_out[0]);
+        }
+        let sum: f64 = output.iter().sum();
+        let centroid: f64 = output
+            .iter()
+            .enumerate()
+            .map(|(n, val)| n as f64 * val)
+            .sum::<f64>()
+            / sum;
+        centroid - impulse_frame as f64 * ratio
+    }
+
+    /// Resample a complete sine signal and compare the result with the expected sine.
+    fn check_complete_signal<R: Resampler<f64>>(resampler: &mut R, ratio: f64) {
+        assert!((resampler.resample_ratio() - ratio).abs() < 1.0e-12);
+        let signal: Vec<f64> = (0..10000).map(|n| (0.002 * n as f64).sin()).collect();
+        let output = resampler
+            .process_all(&vec![&signal; resampler.nbr_channels()])
+            .unwrap()
+            .remove(0);
+        assert_eq!(output.len(), (10000.0 * ratio).round() as usize);
+        for (n, val) in output.iter().enumerate().skip(100).take(output.len() - 200) {
+            let expected = (0.002 * n as f64 / ratio).sin();
+            assert!(
+                (val - expected).abs() < 0.01,
+                "frame {}: {} != {}",
+                n,
+                val,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn higher_order_interpolation() {
+        // the error of a resampled sine, compared to an ideal one
+        let rms_error = |interpolation| {
+            let params = InterpolationParameters {
+                sinc_len: 128,
+                f_cutoff: 0.95,
+                interpolation,
+                oversampling_factor: 8,
+                window: WindowFunction::BlackmanHarris2,
+            };
+            let ratio = 1.2345;
+            let freq = 0.2;
+            let mut resampler = SincFixedIn::<f64>::new(ratio, params, 1024, 1);
+            let delay = resampler.output_delay();
+            let mut output = Vec::new();
+            for chunk in 0..8 {
+                let wave: Vec<f64> = (chunk * 1024..(chunk + 1) * 1024)
+                    .map(|n| (2.0 * std::f64::consts::PI * freq * n as f64).sin())
+                    .collect();
+                output.extend(resampler.process(&[wave]).unwrap().remove(0));
+            }
+            let sum_squares: f64 = output
+                .iter()
+                .enumerate()
+                .skip(2048)
+                .map(|(n, val)| {
+                    let time = (n as f64 - delay) / ratio;
+                    (val - (2.0 * std::f64::consts::PI * freq * time).sin()).powi(2)
+                })
+                .sum();
+            (sum_squares / (output.len() - 2048) as f64).sqrt()
+        };
+        let cubic = rms_error(InterpolationType::Cubic);
+        let hermite = rms_error(InterpolationType::Hermite);
+        let quintic = rms_error(InterpolationType::Quintic);
+        // quintic interpolation is much more accurate for the same oversampling factor,
+        // while Hermite trades some accuracy for a continuous derivative
+        assert!(quintic < cubic / 10.0);
+        assert!(cubic < hermite);
+        assert!(hermite < 1.0e-4);
+    }
+
+    #[test]
+    fn make_resampler_fi() {
+        let params = InterpolationParameters {
+            sinc_len: 64,
+            f_cutoff: 0.95,
+            interpolation: InterpolationType::Cubic,
+            oversampling_factor: 16,
+            window: WindowFunction::BlackmanHarris2,
+        };
+        let mut resampler = SincFixedIn::<f64>::new(1.2, params, 1024, 2);
+        let waves = vec![vec![0.0f64; 1024]; 2];
+        let out = resampler.process(&waves).unwrap();
+        assert_eq!(out.len(), 2);
+        assert!(out[0].len() > 1150 && out[0].len() < 1250);
+    }
+
+    #[test]
+    fn make_resampler_fi_32() {
+        let params = InterpolationParameters {
+            sinc_len: 64,
+            f_cutoff: 0.95,
+            interpolation: InterpolationType::Cubic,
+            oversampling_factor: 16,
+            window: WindowFunction::BlackmanHarris2,
+        };
+        let mut resampler = SincFixedIn::<f32>::new(1.2, params, 1024, 2);
+        let waves = vec![vec![0.0f32; 1024]; 2];
+        let out = resampler.process(&waves).unwrap();
+        assert_eq!(out.len(), 2);
+        assert!(out[0].len() > 1150 && out[0].len() < 1250);
+    }
+
+    #[test]
+    fn make_resampler_fo() {
+        let params = InterpolationParameters {
+            sinc_len: 64,
+            f_cutoff: 0.95,
+            interpolation: InterpolationType::Cubic,
+            oversampling_factor: 16,
+            window: WindowFunction::BlackmanHarris2,
+        };
+        let mut resampler = SincFixedOut::<f64>::new(1.2, params, 1024, 2);
+        let frames = resampler.nbr_frames_needed();
+        println!("{}", frames);
+        assert!(frames > 800 && frames < 900);
+        let waves = vec![vec![0.0f64; frames]; 2];
+        let out = resampler.process(&waves).unwrap();
+        assert_eq!(out.len(), 2);
+        assert_eq!(out[0].len(), 1024);
+    }
+
+    #[test]
+    fn make_resampler_fo_32() {
+        let params = InterpolationParameters {
+            sinc_len: 64,
+            f_cutoff: 0.95,
+            interpolation: InterpolationType::Cubic,
+            oversampling_factor: 16,
+            window: WindowFunction::BlackmanHarris2,
+        };
+        let mut resampler = SincFixedOut::<f32>::new(1.2, params, 1024, 2);
+        let frames = resampler.nbr_frames_needed();
+        println!("{}", frames);
+        assert!(frames > 800 && frames < 900);
+        let waves = vec![vec![0.0f32; frames]; 2];
+        let out = resampler.process(&waves).unwrap();
+        assert_eq!(out.len(), 2);
+        assert_eq!(out[0].len(), 1024);
+    }
+
+    #[test]
+    fn counting_allocator() {
+        let allocations = count_allocations(|| {
+            // a volatile read keeps the vector from being optimized away
+            let data: Vec<u8> = vec![0; 16];
+            assert_eq!(unsafe { std::ptr::read_volatile(data.as_ptr()) }, 0);
+            drop(data);
+        });
+        assert_eq!(allocations, 1);
+    }
+
+    #[test]
+    #[cfg_attr(feature = "rayon", ignore = "rayon may allocate when queueing jobs")]
+    fn no_allocation_sincfixedin() {
+        let params = InterpolationParameters {
+            sinc_len: 64,
+            f_cutoff: 0.95,
+            interpolation: InterpolationType::Cubic,
+            oversampling_factor: 16,
+            window: WindowFunction::BlackmanHarris2,
+        };
+        let mut resampler = SincFixedIn::<f64>::new(1.2, params, 1024, 2);
+        assert_eq!(allocations_when_processing(&mut resampler), 0);
+        resampler.set_resample_ratio_relative(1.05).unwrap();
+        assert_eq!(allocations_when_processing(&mut resampler), 0);
+    }
+
+    #[test]
+    #[cfg_attr(feature = "rayon", ignore = "rayon may allocate when queueing jobs")]
+    fn no_allocation_sincfixedout() {
+        let params = InterpolationParameters {
+            sinc_len: 64,
+            f_cutoff: 0.95,
+            interpolation: InterpolationType::Linear,
+            oversampling_factor: 16,
+            window: WindowFunction::BlackmanHarris2,
+        };
+        let mut resampler = SincFixedOut::<f64>::new(0.8, params, 1024, 2);
+        assert_eq!(allocations_when_processing(&mut resampler), 0);
+        // the new ratio needs a lower cutoff, which is selected without allocating
+        let allocations = count_allocations(|| {
+            resampler.set_resample_ratio_relative(0.95).unwrap();
+        });
+        assert_eq!(allocations, 0);
+        assert_eq!(allocations_when_processing(&mut resampler), 0);
+    }
+
+    #[test]
+    #[cfg_attr(feature = "rayon", ignore = "rayon may allocate when queueing jobs")]
+    fn no_allocation_fft() {
+        let mut resampler = FftFixedIn::<f64>::new(44100, 48000, 1024, 2, 2);
+        assert_eq!(allocations_when_processing(&mut resampler), 0);
+        let mut resampler = FftFixedOut::<f64>::new(44100, 48000, 1024, 2, 2);
+        assert_eq!(allocations_when_processing(&mut resampler), 0);
+        let mut resampler = FftFixedInOut::<f64>::new(44100, 48000, 1024, 2);
+        assert_eq!(allocations_when_processing(&mut resampler), 0);
+    }
+
+    #[test]
+    #[cfg_attr(feature = "rayon", ignore = "rayon may allocate when queueing jobs")]
+    fn no_allocation_interleaved() {
+        let mut resampler =
+            InterleavedResampler::new(FftFixedOut::<f64>::new(44100, 48000, 1024, 2, 2));
+        let mut wave_in = Vec::with_capacity(2 * 4096);
+        let mut wave_out = vec![0.0f64; 2 * resampler.output_frames_max()];
+        let allocations = count_allocations(|| {
+            for _ in 0..10 {
+                wave_in.resize(2 * resampler.nbr_frames_needed(), 0.0);
+                resampler
+                    .process_into_buffer(&wave_in, &mut wave_out)
+                    .unwrap();
+            }
+        });
+        assert_eq!(allocations, 0);
+    }
+
+    #[test]
+    fn reset_sinc() {
+        let params = InterpolationParameters {
+            sinc_len: 64,
+            f_cutoff: 0.95,
+            interpolation: InterpolationType::Cubic,
+            oversampling_factor: 16,
+            window: WindowFunction::BlackmanHarris2,
+        };
+        let mut resampler = SincFixedIn::<f64>::new(1.2, params, 1024, 2);
+        check_reset(&mut resampler);
+        let params = InterpolationParameters {
+            sinc_len: 64,
+            f_cutoff: 0.95,
+            interpolation: InterpolationType::Linear,
+            oversampling_factor: 16,
+            window: WindowFunction::BlackmanHarris2,
+        };
+        let mut resampler = SincFixedOut::<f64>::new(0.8, params, 1024, 2);
+        check_reset(&mut resampler);
+    }
+
+    #[test]
+    fn reset_fft() {
+        let mut resampler = FftFixedIn::<f64>::new(44100, 48000, 1000, 2, 2);
+        check_reset(&mut resampler);
+        let mut resampler = FftFixedOut::<f64>::new(44100, 48000, 1000, 2, 2);
+        check_reset(&mut resampler);
+        let mut resampler = FftFixedInOut::<f64>::new(44100, 48000, 1024, 2);
+        check_reset(&mut resampler);
+    }
+
+    #[test]
+    fn output_delay_sinc() {
+        for ratio in [1.2, 0.5, 2.0].iter().copied() {
+            let params = InterpolationParameters {
+                sinc_len: 64,
+                f_cutoff: 0.95,
+                interpolation: InterpolationType::Cubic,
+                oversampling_factor: 16,
+                window: WindowFunction::BlackmanHarris2,
+            };
+            let mut resampler = SincFixedIn::<f64>::new(ratio, params, 1024, 2);
+            let delay = resampler.output_delay();
+            assert!((measure_impulse_delay(&mut resampler, ratio) - delay).abs() < 0.01);
+            let params = InterpolationParameters {
+                sinc_len: 64,
+                f_cutoff: 0.95,
+                interpolation: InterpolationType::Linear,
+                oversampling_factor: 16,
+                window: WindowFunction::BlackmanHarris2,
+            };
+            let mut resampler = SincFixedOut::<f64>::new(ratio, params, 1024, 2);
+            let delay = resampler.output_delay();
+            assert!((measure_impulse_delay(&mut resampler, ratio) - delay).abs() < 0.01);
+        }
+    }
+
+    #[test]
+    fn output_delay_fft() {
+        for (fs_in, fs_out) in [(44100, 48000), (48000, 44100), (44100, 96000)]
+            .iter()
+            .copied()
+        {
+            let ratio = fs_out as f64 / fs_in as f64;
+            let mut resampler = FftFixedIn::<f64>::new(fs_in, fs_out, 1024, 2, 2);
+            let delay = resampler.output_delay();
+            assert!((measure_impulse_delay(&mut resampler, ratio) - delay).abs() < 0.01);
+            let mut resampler = FftFixedOut::<f64>::new(fs_in, fs_out, 1024, 2, 2);
+            let delay = resampler.output_delay();
+            assert!((measure_impulse_delay(&mut resampler, ratio) - delay).abs() < 0.01);
+            let mut resampler = FftFixedInOut::<f64>::new(fs_in, fs_out, 1024, 2);
+            let delay = resampler.output_delay();
+            assert!((measure_impulse_delay(&mut resampler, ratio) - delay).abs() < 0.01);
+            let params = FftFilterParameters {
+                filter_len: Some(101),
+                ..Default::default()
+            };
+            let mut resampler =
+                FftFixedIn::<f64>::new_with_parameters(fs_in, fs_out, 1024, 2, 2, params);
+            let delay = resampler.output_delay();
+            assert!((measure_impulse_delay(&mut resampler, ratio) - delay).abs() < 0.01);
+            let params = FftFilterParameters {
+                filter_len: Some(3001),
+                ..Default::default()
+            };
+            let mut resampler =
+                FftFixedOut::<f64>::new_with_parameters(fs_in, fs_out, 1024, 2, 2, params);
+            let delay = resampler.output_delay();
+            assert!((measure_impulse_delay(&mut resampler, ratio) - delay).abs() < 0.01);
+            let params = FftFilterParameters::default();
+            let mut resampler = FftFixedIn::<f64>::new_two_stage(fs_in, fs_out, 1024, 2, 2, params);
+            let delay = resampler.output_delay();
+            assert!((measure_impulse_delay(&mut resampler, ratio) - delay).abs() < 0.01);
+            let mut resampler =
+                FftFixedOut::<f64>::new_two_stage(fs_in, fs_out, 1024, 2, 2, params);
+            let delay = resampler.output_delay();
+            assert!((measure_impulse_delay(&mut resampler, ratio) - delay).abs() < 0.01);
+        }
+    }
+
+    #[test]
+    fn complete_signal_sinc() {
+        for ratio in [1.2, 0.5].iter().copied() {
+            let params = InterpolationParameters {
+                sinc_len: 64,
+                f_cutoff: 0.95,
+                interpolation: InterpolationType::Cubic,
+                oversampling_factor: 16,
+                window: WindowFunction::BlackmanHarris2,
+            };
+            let mut resampler = SincFixedIn::<f64>::new(ratio, params, 1024, 2);
+            check_complete_signal(&mut resampler, ratio);
+            let params = InterpolationParameters {
+                sinc_len: 64,
+                f_cutoff: 0.95,
+                interpolation: InterpolationType::Linear,
+                oversampling_factor: 16,
+                window: WindowFunction::BlackmanHarris2,
+            };
+            let mut resampler = SincFixedOut::<f64>::new(ratio, params, 1024, 2);
+            check_complete_signal(&mut resampler, ratio);
+        }
+    }
+
+    #[test]
+    fn complete_signal_fft() {
+        for (fs_in, fs_out) in [(44100, 48000), (48000, 44100), (44100, 96000)]
+            .iter()
+            .copied()
+        {
+            let ratio = fs_out as f64 / fs_in as f64;
+            let mut resampler = FftFixedIn::<f64>::new(fs_in, fs_out, 1024, 2, 2);
+            check_complete_signal(&mut resampler, ratio);
+            let mut resampler = FftFixedOut::<f64>::new(fs_in, fs_out, 1024, 2, 2);
+            check_complete_signal(&mut resampler, ratio);
+            let mut resampler = FftFixedInOut::<f64>::new(fs_in, fs_out, 1024, 2);
+            check_complete_signal(&mut resampler, ratio);
+        }
+    }
+
+    /// Resample a signal with "process_partial", using chunks of the given sizes.
+    fn resample_in_partial_chunks<R: Resampler<f64>>(
+        resampler: &mut R,
+        signal: &[f64],
+        chunk_sizes: &[usize],
+    ) -> Vec<f64> {
+        let mut output = Vec::new();
+        let mut remaining = signal;
+        for chunk_size in chunk_sizes.iter().cycle() {
+            if remaining.is_empty() {
+                break;
+            }
+            let (chunk, rest) = remaining.split_at((*chunk_size).min(remaining.len()));
+            let waves_out = resampler.process_partial(Some(&[chunk, chunk])).unwrap();
+            output.extend_from_slice(&waves_out[0]);
+            remaining = rest;
+        }
+        output
+    }
+
+    #[test]
+    fn partial_fixed_input() {
+        let signal: Vec<f64> = (0..10240).map(|n| (0.01 * n as f64).sin()).collect();
+        let make_params = || InterpolationParameters {
+            sinc_len: 64,
+            f_cutoff: 0.95,
+            interpolation: InterpolationType::Cubic,
+            oversampling_factor: 16,
+            window: WindowFunction::BlackmanHarris2,
+        };
+        let mut resampler = SincFixedIn::<f64>::new(1.2, make_params(), 1024, 2);
+        let full = resample_in_partial_chunks(&mut resampler, &signal, &[1024]);
+        let mut resampler = SincFixedIn::<f64>::new(1.2, make_params(), 1024, 2);
+        let partial = resample_in_partial_chunks(&mut resampler, &signal, &[700, 1024, 13, 300]);
+        // the output length may differ by one frame, depending on where the last chunk ends
+        assert!((full.len() as isize - partial.len() as isize).abs() <= 1);
+        for (a, b) in full.iter().zip(partial.iter()) {
+            assert!((a - b).abs() < 1.0e-9);
+        }
+
+        let mut resampler = FftFixedIn::<f64>::new(44100, 48000, 1024, 2, 2);
+        let full = resample_in_partial_chunks(&mut resampler, &signal, &[1024]);
+        let mut resampler = FftFixedIn::<f64>::new(44100, 48000, 1024, 2, 2);
+        let partial = resample_in_partial_chunks(&mut resampler, &signal, &[700, 1024, 13, 300]);
+        assert_eq!(full.len(), partial.len());
+        for (a, b) in full.iter().zip(partial.iter()) {
+            assert!((a - b).abs() < 1.0e-12);
+        }
+    }
+
+    /// Resample a sine with the given frequency, relative to the input sample rate,
+    /// and return the amplitude of the output, calculated from the RMS value after the first chunks.
+    fn resampled_sine_amplitude<R: Resampler<f64>>(resampler: &mut R, freq: f64) -> f64 {
+        let mut frame = 0;
+        let mut sum_squares = 0.0;
+        let mut nbr_values = 0;
+        for chunk in 0..10 {
+            let frames = resampler.nbr_frames_needed();
+            let wave: Vec<f64> = (frame..frame + frames)
+                .map(|n| (2.0 * std::f64::consts::PI * freq * n as f64).sin())
+                .collect();
+            frame += frames;
+            let waves_out = resampler.process(&[&wave, &wave]).unwrap();
+            if chunk > 2 {
+                sum_squares += waves_out[0].iter().map(|val| val * val).sum::<f64>();
+                nbr_values += waves_out[0].len();
+            }
+        }
+        (2.0 * sum_squares / nbr_values as f64).sqrt()
+    }
+
+    #[test]
+    fn wide_ratio_range() {
+        let make_params = || InterpolationParameters {
+            sinc_len: 128,
+            f_cutoff: 0.95,
+            interpolation: InterpolationType::Linear,
+            oversampling_factor: 256,
+            window: WindowFunction::BlackmanHarris2,
+        };
+        let mut resampler =
+            SincFixedIn::<f64>::new_with_ratio_range(1.0, 4.0, make_params(), 1024, 2);
+        assert!(resampler.set_resample_ratio(4.1).is_err());
+        assert!(resampler.set_resample_ratio(0.24).is_err());
+        resampler.set_resample_ratio(4.0).unwrap();
+        assert!((resampled_sine_amplitude(&mut resampler, 0.05) - 1.0).abs() < 0.01);
+        resampler.set_resample_ratio(0.25).unwrap();
+        assert!((resampled_sine_amplitude(&mut resampler, 0.05) - 1.0).abs() < 0.01);
+        // above the new Nyquist frequency, must not alias
+        assert!(resampled_sine_amplitude(&mut resampler, 0.3) < 0.001);
+
+        let mut resampler =
+            SincFixedOut::<f64>::new_with_ratio_range(1.0, 4.0, make_params(), 1024, 2);
+        resampler.set_resample_ratio(0.25).unwrap();
+        assert!(resampler.nbr_frames_needed() <= resampler.input_frames_max());
+        assert!((resampled_sine_amplitude(&mut resampler, 0.05) - 1.0).abs() < 0.01);
+        assert!(resampled_sine_amplitude(&mut resampler, 0.3) < 0.001);
+        resampler.set_resample_ratio(4.0).unwrap();
+        assert!((resampled_sine_amplitude(&mut resampler, 0.05) - 1.0).abs() < 0.01);
+    }
+
+    /// Get interpolation parameters with a short filter, for the tests of the custom filters.
+    fn test_params(
+        interpolation: InterpolationType,
+        window: WindowFunction,
+    ) -> InterpolationParameters {
+        InterpolationParameters {
+            sinc_len: 64,
+            f_cutoff: 0.95,
+            interpolation,
+            oversampling_factor: 16,
+            window,
+        }
+    }
+
+    #[test]
+    fn custom_window() {
+        let hann2 = make_window::<f64>(64 * 16, WindowFunction::Hann2);
+        let window = move |x: usize, _npoints| hann2[x];
+        let mut builtin = SincFixedOut::<f64>::new(
+            0.8,
+            test_params(InterpolationType::Cubic, WindowFunction::Hann2),
+            1024,
+            2,
+        );
+        let mut custom = SincFixedOut::<f64>::new_with_custom_filter(
+            0.8,
+            test_params(InterpolationType::Cubic, WindowFunction::Hann2),
+            CustomFilter::Window(Box::new(window)),
+            1024,
+            2,
+        );
+        assert_eq!(
+            builtin.sincs.current().filters(),
+            custom.sincs.current().filters()
+        );
+        assert_eq!(
+            process_test_signal(&mut builtin),
+            process_test_signal(&mut custom)
+        );
+        // the cutoff follows the ratio also with a custom window
+        builtin.set_resample_ratio(0.75).unwrap();
+        custom.set_resample_ratio(0.75).unwrap();
+        assert_eq!(
+            builtin.sincs.current().filters(),
+            custom.sincs.current().filters()
+        );
+    }
+
+    #[test]
+    fn custom_prototype() {
+        // a windowed sinc made in the same way as the built-in one
+        let npoints = 64 * 16;
+        let window = make_window::<f64>(npoints, WindowFunction::Blackman);
+        let prototype: Vec<f64> = (0..npoints)
+            .map(|x| window[x] * sinc((x as f64 - (npoints / 2) as f64) * 0.95f32 as f64 / 16.0))
+            .collect();
+        let builtin = SincFixedIn::<f64>::new(
+            1.2,
+            test_params(InterpolationType::Linear, WindowFunction::Blackman),
+            1024,
+            2,
         );
-        let sinc_cutoff = if resample_ratio >= 1.0 {
-            parameters.f_cutoff
-        } else {
-            parameters.f_cutoff * resample_ratio as f32
-        };
-        let sinc_len = 8 * (((parameters.sinc_len as f32) / 8.0).ceil() as usize);
-        debug!("sinc_len rounded up to {}", sinc_len);
-        let sincs = make_sincs(
-            sinc_len,
-            parameters.oversampling_factor,
-            sinc_cutoff,
-            parameters.window,
+        let mut custom = SincFixedIn::<f64>::new_with_custom_filter(
+            1.2,
+            test_params(InterpolationType::Linear, WindowFunction::Blackman),
+            CustomFilter::Prototype(prototype),
+            1024,
+            2,
         );
-        let needed_input_size =
-            (chunk_size as f64 / resample_ratio).ceil() as usize + 2 + sinc_len / 2;
-        let buffer = vec![vec![T::zero(); 3 * needed_input_size / 2 + 2 * sinc_len]; nbr_channels];
-        SincFixedOut {
-            nbr_channels,
-            chunk_size,
-            needed_input_size,
-            oversampling_factor: parameters.oversampling_factor,
-            last_index: -((sinc_len / 2) as f64),
-            current_buffer_fill: needed_input_size,
-            resample_ratio,
-            resample_ratio_original: resample_ratio,
-            sinc_len,
-            sincs,
-            buffer,
-            interpolation: parameters.interpolation,
+        for (sinc_builtin, sinc_custom) in builtin
+            .sincs
+            .current()
+            .filters()
+            .iter()
+            .zip(custom.sincs.current().filters().iter())
+        {
+            for (a, b) in sinc_builtin.iter().zip(sinc_custom.iter()) {
+                assert!((a - b).abs() < 1.0e-12);
+            }
         }
+        // the prototype is kept when the ratio changes
+        let sincs = custom.sincs.current().filters().to_vec();
+        custom.set_resample_ratio(1.1).unwrap();
+        process_test_signal(&mut custom);
+        assert_eq!(custom.sincs.current().filters(), &sincs[..]);
     }
-}
 
-macro_rules! resampler_sincfixedout {
-    ($t:ty) => {
-        impl Resampler<$t> for SincFixedOut<$t> {
-            /// Query for the number of frames needed for the next call to "process".
-            fn nbr_frames_needed(&self) -> usize {
-                self.needed_input_size
+    #[test]
+    fn invalid_prototype() {
+        let params = InterpolationParameters {
+            sinc_len: 60,
+            ..test_params(InterpolationType::Linear, WindowFunction::Blackman)
+        };
+        let prototype = CustomFilter::Prototype(vec![1.0; 60 * 16]);
+        assert_eq!(format!("{:?}", prototype), "Prototype(<960 points>)");
+        // the sinc length is rounded up to 64
+        match SincFixedIn::<f64>::try_new_with_custom_filter(1.2, params, prototype, 1024, 2) {
+            Err(ResampleError::InvalidParameter { name, .. }) => {
+                assert_eq!(name, "custom_filter")
             }
+            _ => panic!("expected an InvalidParameter error"),
+        }
+    }
 
-            /// Update the resample ratio. New value must be within +-10% of the original one
-            fn set_resample_ratio(&mut self, new_ratio: f64) -> Res<()> {
-                trace!("Change resample ratio to {}", new_ratio);
-                if (new_ratio / self.resample_ratio_original > 0.9)
-                    && (new_ratio / self.resample_ratio_original < 1.1)
-                {
-                    self.resample_ratio = new_ratio;
-                    self.needed_input_size = (self.last_index as f32
-                        + self.chunk_size as f32 / self.resample_ratio as f32
-                        + self.sinc_len as f32)
-                        .ceil() as usize
-                        + 2;
-                    Ok(())
-                } else {
-                    Err(Box::new(ResamplerError::new(
-                        "New resample ratio is too far off from original",
-                    )))
-                }
-            }
+    #[test]
+    fn kaiser_window() {
+        let make_params = |attenuation| InterpolationParameters {
+            sinc_len: 256,
+            f_cutoff: 0.9,
+            interpolation: InterpolationType::Linear,
+            oversampling_factor: 256,
+            window: WindowFunction::kaiser_from_attenuation(attenuation),
+        };
+        let mut low = SincFixedIn::<f64>::new(0.5, make_params(40.0), 1024, 2);
+        let mut high = SincFixedIn::<f64>::new(0.5, make_params(120.0), 1024, 2);
+        assert!((resampled_sine_amplitude(&mut high, 0.1) - 1.0).abs() < 0.01);
+        // a higher attenuation must give less aliasing above the new Nyquist frequency
+        let aliased_low = resampled_sine_amplitude(&mut low, 0.3);
+        let aliased_high = resampled_sine_amplitude(&mut high, 0.3);
+        assert!(aliased_low < 0.01);
+        assert!(aliased_high < 0.000001);
+        assert!(aliased_high < 0.01 * aliased_low);
+    }
 
-            /// Update the resample ratio relative to the original one
-            fn set_resample_ratio_relative(&mut self, rel_ratio: f64) -> Res<()> {
-                let new_ratio = self.resample_ratio_original * rel_ratio;
-                self.set_resample_ratio(new_ratio)
-            }
+    #[test]
+    fn ramp_ratio_sincfixedin() {
+        let params = InterpolationParameters {
+            sinc_len: 64,
+            f_cutoff: 0.95,
+            interpolation: InterpolationType::Linear,
+            oversampling_factor: 16,
+            window: WindowFunction::BlackmanHarris2,
+        };
+        let mut resampler = SincFixedIn::<f64>::new(1.0, params, 1024, 2);
+        let waves_in = vec![vec![0.0f64; 1024]; 2];
+        resampler.process(&waves_in).unwrap();
+        resampler.set_resample_ratio_ramped(1.05).unwrap();
+        // the ratio goes from 1.0 to 1.05 during the chunk,
+        // giving the output frames of the mean step of the ramp
+        let expected = 2.0 * 1024.0 / (1.0 + 1.0 / 1.05);
+        let frames = resampler.process(&waves_in).unwrap()[0].len();
+        assert!((frames as f64 - expected).abs() < 2.0);
+        // the following chunks use the new ratio
+        let frames = resampler.process(&waves_in).unwrap()[0].len();
+        assert!((frames as f64 - 1024.0 * 1.05).abs() < 2.0);
+    }
 
-            /// Resample a chunk of audio. The required input length is provided by
-            /// the "nbr_frames_needed" function, and the output length is fixed.
-            /// # Errors
-            ///
-            /// The function returns an error if the length of the input data is not
-            /// equal to the number of channels defined when creating the instance,
-            /// and the number of audio frames given by "nbr_frames_needed".
-            fn process(&mut self, wave_in: &[Vec<$t>]) -> Res<Vec<Vec<$t>>> {
-                //update buffer with new data
-                if wave_in.len() != self.nbr_channels {
-                    return Err(Box::new(ResamplerError::new(
-                        "Wrong number of channels in input",
-                    )));
-                }
-                if wave_in[0].len() != self.needed_input_size {
-                    return Err(Box::new(ResamplerError::new(
-                        "Wrong number of frames in input",
-                    )));
-                }
-                for wav in self.buffer.iter_mut() {
-                    for idx in 0..(2 * self.sinc_len) {
-                        wav[idx] = wav[idx + self.current_buffer_fill];
-                    }
-                }
-                self.current_buffer_fill = wave_in[0].len();
-                for (chan, wav) in wave_in.iter().enumerate() {
-                    for (idx, sample) in wav.iter().enumerate() {
-                        self.buffer[chan][idx + 2 * self.sinc_len] = *sample;
-                    }
-                }
+    #[test]
+    fn ramp_ratio_sincfixedout() {
+        let make_params = || InterpolationParameters {
+            sinc_len: 64,
+            f_cutoff: 0.95,
+            interpolation: InterpolationType::Linear,
+            oversampling_factor: 16,
+            window: WindowFunction::BlackmanHarris2,
+        };
+        let mut ramped = SincFixedOut::<f64>::new(1.0, make_params(), 1024, 2);
+        let mut stepped = SincFixedOut::<f64>::new(1.0, make_params(), 1024, 2);
+        let frames_before = ramped.nbr_frames_needed();
+        ramped.set_resample_ratio_ramped(1.05).unwrap();
+        stepped.set_resample_ratio(1.05).unwrap();
+        // the ramped chunk needs the input frames of the mean step of the ramp
+        let expected = (frames_before + stepped.nbr_frames_needed()) as f64 / 2.0;
+        assert!((ramped.nbr_frames_needed() as f64 - expected).abs() < 2.0);
+        for _ in 0..3 {
+            let waves_in = vec![vec![0.0f64; ramped.nbr_frames_needed()]; 2];
+            assert_eq!(ramped.process(&waves_in).unwrap()[0].len(), 1024);
+            let waves_in = vec![vec![0.0f64; stepped.nbr_frames_needed()]; 2];
+            stepped.process(&waves_in).unwrap();
+        }
+        // after the ramp, both resamplers consume input at the same rate
+        let diff = ramped.nbr_frames_needed() as isize - stepped.nbr_frames_needed() as isize;
+        assert!(diff.abs() <= 1);
+    }
 
-                let mut idx = self.last_index;
-                let t_ratio = 1.0 / self.resample_ratio as f64;
+    #[test]
+    fn ramp_same_ratio() {
+        let make_params = || InterpolationParameters {
+            sinc_len: 64,
+            f_cutoff: 0.95,
+            interpolation: InterpolationType::Cubic,
+            oversampling_factor: 16,
+            window: WindowFunction::BlackmanHarris2,
+        };
+        let mut ramped = SincFixedOut::<f64>::new(1.2, make_params(), 1024, 2);
+        let mut plain = SincFixedOut::<f64>::new(1.2, make_params(), 1024, 2);
+        ramped.set_resample_ratio_ramped(1.2).unwrap();
+        assert_eq!(
+            process_test_signal(&mut ramped),
+            process_test_signal(&mut plain)
+        );
+    }
 
-                let mut wave_out = vec![vec![0.0 as $t; self.chunk_size]; self.nbr_channels];
+    #[test]
+    fn rational_matches_nearest() {
+        let make_params = |oversampling_factor| InterpolationParameters {
+            sinc_len: 64,
+            f_cutoff: 0.95,
+            interpolation: InterpolationType::Nearest,
+            oversampling_factor,
+            window: WindowFunction::BlackmanHarris2,
+        };
+        let ratio = 48000.0 / 44100.0;
+        let mut rational = SincFixedIn::<f64>::new_rational(44100, 48000, make_params(1), 1024, 2);
+        let mut nearest = SincFixedIn::<f64>::new(ratio, make_params(160), 1024, 2);
+        assert_eq!(
+            process_test_signal(&mut rational),
+            process_test_signal(&mut nearest)
+        );
+        let mut rational = SincFixedOut::<f64>::new_rational(44100, 48000, make_params(1), 1024, 2);
+        let mut nearest = SincFixedOut::<f64>::new(ratio, make_params(160), 1024, 2);
+        assert_eq!(
+            process_test_signal(&mut rational),
+            process_test_signal(&mut nearest)
+        );
+    }
 
-                match self.interpolation {
-                    InterpolationType::Cubic => {
-                        let mut points = vec![0.0 as $t; 4];
-                        let mut nearest = vec![(0isize, 0isize); 4];
-                        for n in 0..self.chunk_size {
-                            idx += t_ratio;
-                            get_nearest_times_4(idx, self.oversampling_factor as isize, &mut nearest);
-                            let frac = idx * self.oversampling_factor as f64
-                                - (idx * self.oversampling_factor as f64).floor();
-                            let frac_offset = frac as $t;
-                            for (chan, buf) in self.buffer.iter().enumerate() {
-                                for (n, p) in nearest.iter().zip(points.iter_mut()) {
-                                    *p = self.get_sinc_interpolated(
-                                        &buf,
-                                        (n.0 + 2 * self.sinc_len as isize) as usize,
-                                        n.1 as usize,
-                                    );
-                                }
-                                wave_out[chan][n] = self.interp_cubic(frac_offset, &points);
-                            }
-                        }
-                    }
-                    InterpolationType::Linear => {
-                        let mut points = vec![0.0 as $t; 2];
-                        let mut nearest = vec![(0isize, 0isize); 2];
-                        for n in 0..self.chunk_size {
-                            idx += t_ratio;
-                            get_nearest_times_2(idx, self.oversampling_factor as isize, &mut nearest);
-                            let frac = idx * self.oversampling_factor as f64
-                                - (idx * self.oversampling_factor as f64).floor();
-                            let frac_offset = frac as $t;
-                            for (chan, buf) in self.buffer.iter().enumerate() {
-                                for (n, p) in nearest.iter().zip(points.iter_mut()) {
-                                    *p = self.get_sinc_interpolated(
-                                        &buf,
-                                        (n.0 + 2 * self.sinc_len as isize) as usize,
-                                        n.1 as usize,
-                                    );
-                                }
-                                wave_out[chan][n] = self.interp_lin(frac_offset, &points);
-                            }
-                        }
-                    }
-                    InterpolationType::Nearest => {
-                        let mut point;
-                        let mut nearest;
-                        for n in 0..self.chunk_size {
-                            idx += t_ratio;
-                            nearest = get_nearest_time(idx, self.oversampling_factor as isize);
-                            for (chan, buf) in self.buffer.iter().enumerate() {
-                                point = self.get_sinc_interpolated(
-                                    &buf,
-                                    (nearest.0 + 2 * self.sinc_len as isize) as usize,
-                                    nearest.1 as usize,
-                                );
-                                wave_out[chan][n] = point;
-                            }
-                        }
-                    }
-                }
+    #[test]
+    fn rational_exact_steps() {
+        let make_params = || InterpolationParameters {
+            sinc_len: 16,
+            f_cutoff: 0.9,
+            interpolation: InterpolationType::Cubic,
+            oversampling_factor: 16,
+            window: WindowFunction::BlackmanHarris2,
+        };
+        // 44.1 to 48 kHz is 147 to 160, every chunk of 147 frames gives exactly 160 frames
+        let mut resampler = SincFixedIn::<f32>::new_rational(44100, 48000, make_params(), 147, 1);
+        let waves_in = vec![vec![0.0f32; 147]; 1];
+        resampler.process(&waves_in).unwrap();
+        for _ in 0..10000 {
+            let waves_out = resampler.process(&waves_in).unwrap();
+            assert_eq!(waves_out[0].len(), 160);
+        }
+        let mut resampler = SincFixedOut::<f32>::new_rational(44100, 48000, make_params(), 160, 1);
+        let waves_in = vec![vec![0.0f32; resampler.nbr_frames_needed()]; 1];
+        resampler.process(&waves_in).unwrap();
+        for _ in 0..10000 {
+            assert_eq!(resampler.nbr_frames_needed(), 147);
+            let waves_in = vec![vec![0.0f32; 147]; 1];
+            resampler.process(&waves_in).unwrap();
+        }
+    }
 
-                // store last index for next iteration
-                self.last_index = idx - self.current_buffer_fill as f64;
-                self.needed_input_size = (self.last_index as f32
-                    + self.chunk_size as f32 / self.resample_ratio as f32
-                    + self.sinc_len as f32)
-                    .ceil() as usize
-                    + 2;
-                trace!(
-                    "Resampling, {} frames in, {} frames out. Next needed length: {} frames, last index {}",
-                    wave_in[0].len(),
-                    wave_out[0].len(),
-                    self.needed_input_size,
-                    self.last_index
-                );
-                Ok(wave_out)
+    #[test]
+    fn rational_chunk_invariance() {
+        let make_params = || InterpolationParameters {
+            sinc_len: 64,
+            f_cutoff: 0.95,
+            interpolation: InterpolationType::Nearest,
+            oversampling_factor: 1,
+            window: WindowFunction::BlackmanHarris2,
+        };
+        let wave: Vec<f64> = (0..20000).map(|n| (0.01 * n as f64).sin()).collect();
+        let mut outputs = Vec::new();
+        for chunk_size in [1000, 441, 37].iter() {
+            let mut resampler =
+                SincFixedIn::<f64>::new_rational(48000, 44100, make_params(), *chunk_size, 1);
+            let mut output = Vec::new();
+            for chunk in wave.chunks_exact(*chunk_size) {
+                output.extend(resampler.process(&[chunk]).unwrap().remove(0));
             }
+            output.truncate(15000);
+            outputs.push(output);
         }
+        assert_eq!(outputs[0], outputs[1]);
+        assert_eq!(outputs[0], outputs[2]);
     }
-}
-resampler_sincfixedout!(f32);
-resampler_sincfixedout!(f64);
-
-#[cfg(test)]
-mod tests {
-    use crate::InterpolationParameters;
-    use crate::InterpolationType;
-    use crate::Resampler;
-    use crate::WindowFunction;
-    use crate::{SincFixedIn, SincFixedOut};
 
     #[test]
-    fn int_cubic() {
+    fn rational_not_adjustable() {
+        let params = InterpolationParameters {
+            sinc_len: 64,
+            f_cutoff: 0.95,
+            interpolation: InterpolationType::Nearest,
+            oversampling_factor: 1,
+            window: WindowFunction::BlackmanHarris2,
+        };
+        let mut resampler = SincFixedOut::<f64>::new_rational(44100, 48000, params, 1024, 2);
+        assert_eq!(
+            resampler.set_resample_ratio(1.1),
+            Err(ResampleError::SyncNotAdjustable)
+        );
+        check_reset(&mut resampler);
         let params = InterpolationParameters {
             sinc_len: 64,
             f_cutoff: 0.95,
+            interpolation: InterpolationType::Nearest,
+            oversampling_factor: 1,
+            window: WindowFunction::BlackmanHarris2,
+        };
+        match SincFixedIn::<f64>::try_new_rational(0, 48000, params, 1024, 2) {
+            Err(ResampleError::InvalidParameter { name, .. }) => assert_eq!(name, "fs_in"),
+            _ => panic!("expected an InvalidParameter error"),
+        }
+    }
+
+    #[test]
+    fn rational_oversampling_too_large() {
+        let make_params = || InterpolationParameters {
+            sinc_len: 256,
+            f_cutoff: 0.95,
             interpolation: InterpolationType::Cubic,
-            oversampling_factor: 16,
+            oversampling_factor: 128,
             window: WindowFunction::BlackmanHarris2,
         };
-        let resampler = SincFixedIn::<f64>::new(1.2, params, 1024, 2);
-        let yvals = vec![0.0f64, 2.0f64, 4.0f64, 6.0f64];
-        let interp = resampler.interp_cubic(0.5f64, &yvals);
-        assert_eq!(interp, 3.0f64);
+        // 44100 to 44101 would need 44101 filters
+        match SincFixedOut::<f64>::try_new_rational(44100, 44101, make_params(), 1024, 2) {
+            Err(ResampleError::InvalidParameter { name, .. }) => assert_eq!(name, "fs_out"),
+            _ => panic!("expected an InvalidParameter error"),
+        }
+        // 1024 filters are allowed
+        let resampler = SincFixedIn::<f64>::try_new_rational(1000, 1024, make_params(), 1024, 2);
+        assert!(resampler.is_ok());
+    }
+
+    /// Process a few chunks with a multi-channel resampler, and with one single-channel resampler
+    /// per channel, and check that each channel of the first gives the same output.
+    fn check_channels_independent<R: Resampler<f64>, F: Fn(usize) -> R>(make_resampler: F) {
+        let nbr_channels = 7;
+        let mut resampler = make_resampler(nbr_channels);
+        let mut single: Vec<R> = (0..nbr_channels).map(|_| make_resampler(1)).collect();
+        for chunk in 0..3 {
+            let frames = resampler.nbr_frames_needed();
+            let waves: Vec<Vec<f64>> = (0..nbr_channels)
+                .map(|chan| {
+                    (0..frames)
+                        .map(|n| (0.01 * (chan + 1) as f64 * (n + chunk * frames) as f64).sin())
+                        .collect()
+                })
+                .collect();
+            let mask: Vec<bool> = (0..nbr_channels).map(|chan| chan != 3).collect();
+            let mut waves_out = vec![vec![0.0; resampler.output_frames_max()]; nbr_channels];
+            let frames_out = resampler
+                .process_into_buffer(&waves, &mut waves_out, Some(&mask))
+                .unwrap();
+            for (chan, wav) in waves.iter().enumerate().filter(|(chan, _)| mask[*chan]) {
+                let expected = single[chan].process(&[wav]).unwrap();
+                assert_eq!(&waves_out[chan][..frames_out], &expected[0][..]);
+            }
+            assert!(waves_out[3].iter().all(|val| *val == 0.0));
+        }
     }
 
     #[test]
-    fn int_lin_32() {
-        let params = InterpolationParameters {
+    fn channels_independent() {
+        let make_params = || InterpolationParameters {
             sinc_len: 64,
             f_cutoff: 0.95,
             interpolation: InterpolationType::Cubic,
             oversampling_factor: 16,
             window: WindowFunction::BlackmanHarris2,
         };
-        let resampler = SincFixedIn::<f32>::new(1.2, params, 1024, 2);
-        let yvals = vec![1.0f32, 5.0f32];
-        let interp = resampler.interp_lin(0.25f32, &yvals);
-        assert_eq!(interp, 2.0f32);
+        check_channels_independent(|channels| {
+            SincFixedIn::<f64>::new(1.2, make_params(), 1024, channels)
+        });
+        check_channels_independent(|channels| {
+            SincFixedOut::<f64>::new(0.8, make_params(), 1024, channels)
+        });
+        check_channels_independent(|channels| {
+            SincFixedIn::<f64>::new_rational(44100, 48000, make_params(), 1024, channels)
+        });
+        check_channels_independent(|channels| {
+            FftFixedIn::<f64>::new(44100, 48000, 1024, 2, channels)
+        });
+        check_channels_independent(|channels| {
+            FftFixedOut::<f64>::new(44100, 48000, 1024, 2, channels)
+        });
+        check_channels_independent(|channels| {
+            FftFixedInOut::<f64>::new(44100, 48000, 1024, channels)
+        });
     }
 
     #[test]
-    fn int_cubic_32() {
-        let params = InterpolationParameters {
+    fn dot_product_kernels() {
+        let make_params = || InterpolationParameters {
             sinc_len: 64,
             f_cutoff: 0.95,
             interpolation: InterpolationType::Cubic,
             oversampling_factor: 16,
             window: WindowFunction::BlackmanHarris2,
         };
-        let resampler = SincFixedIn::<f32>::new(1.2, params, 1024, 2);
-        let yvals = vec![0.0f32, 2.0f32, 4.0f32, 6.0f32];
-        let interp = resampler.interp_cubic(0.5f32, &yvals);
-        assert_eq!(interp, 3.0f32);
+        let kernels = [
+            DotProductKernel::Sse,
+            DotProductKernel::Avx,
+            DotProductKernel::AvxFma,
+        ];
+        let mut portable = SincFixedIn::<f64>::new(1.2, make_params(), 1024, 2);
+        portable
+            .set_dot_product_kernel(DotProductKernel::Portable)
+            .unwrap();
+        let expected = process_test_signal(&mut portable);
+        for kernel in kernels.iter() {
+            let mut resampler = SincFixedIn::<f64>::new(1.2, make_params(), 1024, 2);
+            match resampler.set_dot_product_kernel(*kernel) {
+                Ok(()) => {
+                    let output = process_test_signal(&mut resampler);
+                    for (out, exp) in output.iter().flatten().zip(expected.iter().flatten()) {
+                        assert!((out - exp).abs() < 1.0e-13, "{:?}", kernel);
+                    }
+                }
+                Err(ResampleError::InvalidParameter { name, .. }) => {
+                    assert!(!kernel.is_available());
+                    assert_eq!(name, "kernel");
+                }
+                Err(_) => panic!("expected an InvalidParameter error"),
+            }
+        }
+        let mut portable = SincFixedOut::<f32>::new(0.8, make_params(), 1024, 1);
+        portable
+            .set_dot_product_kernel(DotProductKernel::Portable)
+            .unwrap();
+        let waves_in = vec![(0..portable.nbr_frames_needed())
+            .map(|n| (0.01 * n as f32).sin())
+            .collect::<Vec<f32>>()];
+        let expected = portable.process(&waves_in).unwrap();
+        for kernel in kernels.iter().filter(|kernel| kernel.is_available()) {
+            let mut resampler = SincFixedOut::<f32>::new(0.8, make_params(), 1024, 1);
+            resampler.set_dot_product_kernel(*kernel).unwrap();
+            let output = resampler.process(&waves_in).unwrap();
+            for (out, exp) in output[0].iter().zip(expected[0].iter()) {
+                assert!((out - exp).abs() < 1.0e-5, "{:?}", kernel);
+            }
+        }
     }
 
     #[test]
-    fn int_lin() {
+    fn invalid_max_ratio() {
         let params = InterpolationParameters {
             sinc_len: 64,
             f_cutoff: 0.95,
-            interpolation: InterpolationType::Cubic,
+            interpolation: InterpolationType::Linear,
             oversampling_factor: 16,
             window: WindowFunction::BlackmanHarris2,
         };
-        let resampler = SincFixedIn::<f64>::new(1.2, params, 1024, 2);
-        let yvals = vec![1.0f64, 5.0f64];
-        let interp = resampler.interp_lin(0.25f64, &yvals);
-        assert_eq!(interp, 2.0f64);
+        match SincFixedIn::<f64>::try_new_with_ratio_range(1.2, 0.9, params, 1024, 2) {
+            Err(ResampleError::InvalidParameter { name, .. }) => {
+                assert_eq!(name, "max_resample_ratio_relative")
+            }
+            _ => panic!("expected an InvalidParameter error"),
+        }
     }
 
     #[test]
-    fn make_resampler_fi() {
+    fn partial_input_errors() {
+        let mut resampler = FftFixedIn::<f64>::new(44100, 48000, 1024, 2, 2);
+        let waves_in = vec![vec![0.0f64; 1025]; 2];
+        assert_eq!(
+            resampler.process_partial(Some(&waves_in)),
+            Err(ResampleError::WrongNumberOfInputFrames {
+                channel: 0,
+                expected: 1024,
+                actual: 1025
+            })
+        );
+        let waves_in = vec![vec![0.0f64; 1000]; 1];
+        assert_eq!(
+            resampler.process_partial(Some(&waves_in)),
+            Err(ResampleError::WrongNumberOfInputChannels {
+                expected: 2,
+                actual: 1
+            })
+        );
+    }
+
+    #[test]
+    fn process_into_buffer_inactive_channel() {
         let params = InterpolationParameters {
             sinc_len: 64,
             f_cutoff: 0.95,
@@ -745,14 +3495,33 @@ mod tests {
             window: WindowFunction::BlackmanHarris2,
         };
         let mut resampler = SincFixedIn::<f64>::new(1.2, params, 1024, 2);
-        let waves = vec![vec![0.0f64; 1024]; 2];
-        let out = resampler.process(&waves).unwrap();
-        assert_eq!(out.len(), 2);
-        assert!(out[0].len() > 1150 && out[0].len() < 1250);
+        let waves_in = vec![vec![1.0f64; 1024], Vec::new()];
+        let mut waves_out = vec![vec![5.0f64; resampler.output_frames_max()]; 2];
+        let frames = resampler
+            .process_into_buffer(&waves_in, &mut waves_out, Some(&[true, false]))
+            .unwrap();
+        assert!(frames > 1150 && frames < 1250);
+        assert!(waves_out[0][..frames].iter().all(|v| *v < 2.0));
+        assert!(waves_out[1].iter().all(|v| *v == 5.0));
     }
 
     #[test]
-    fn make_resampler_fi_32() {
+    fn process_into_buffer_too_short() {
+        let mut resampler = FftFixedIn::<f64>::new(44100, 48000, 1024, 2, 2);
+        let waves_in = vec![vec![0.0f64; 1024]; 2];
+        let mut waves_out = vec![vec![0.0f64; resampler.output_frames_max() - 1]; 2];
+        assert_eq!(
+            resampler.process_into_buffer(&waves_in, &mut waves_out, None),
+            Err(ResampleError::InsufficientOutputBufferSize {
+                channel: 0,
+                expected: 1280,
+                actual: 1279
+            })
+        );
+    }
+
+    #[test]
+    fn wrong_input_errors() {
         let params = InterpolationParameters {
             sinc_len: 64,
             f_cutoff: 0.95,
@@ -760,15 +3529,36 @@ mod tests {
             oversampling_factor: 16,
             window: WindowFunction::BlackmanHarris2,
         };
-        let mut resampler = SincFixedIn::<f32>::new(1.2, params, 1024, 2);
-        let waves = vec![vec![0.0f32; 1024]; 2];
-        let out = resampler.process(&waves).unwrap();
-        assert_eq!(out.len(), 2);
-        assert!(out[0].len() > 1150 && out[0].len() < 1250);
+        let mut resampler = SincFixedIn::<f64>::new(1.2, params, 1024, 2);
+        let waves = vec![vec![0.0f64; 1024]; 3];
+        assert_eq!(
+            resampler.process(&waves),
+            Err(ResampleError::WrongNumberOfInputChannels {
+                expected: 2,
+                actual: 3
+            })
+        );
+        let waves = vec![vec![0.0f64; 1024], vec![0.0f64; 1000]];
+        assert_eq!(
+            resampler.process(&waves),
+            Err(ResampleError::WrongNumberOfInputFrames {
+                channel: 1,
+                expected: 1024,
+                actual: 1000
+            })
+        );
+        match resampler.set_resample_ratio(2.0) {
+            Err(ResampleError::RatioOutOfBounds { provided, min, max }) => {
+                assert_eq!(provided, 2.0);
+                assert!((min - 1.2 / 1.1).abs() < 1.0e-9);
+                assert!((max - 1.32).abs() < 1.0e-9);
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
     }
 
     #[test]
-    fn make_resampler_fo() {
+    fn process_borrowed_slices() {
         let params = InterpolationParameters {
             sinc_len: 64,
             f_cutoff: 0.95,
@@ -776,32 +3566,88 @@ mod tests {
             oversampling_factor: 16,
             window: WindowFunction::BlackmanHarris2,
         };
-        let mut resampler = SincFixedOut::<f64>::new(1.2, params, 1024, 2);
-        let frames = resampler.nbr_frames_needed();
-        println!("{}", frames);
-        assert!(frames > 800 && frames < 900);
-        let waves = vec![vec![0.0f64; frames]; 2];
-        let out = resampler.process(&waves).unwrap();
+        let mut resampler = SincFixedIn::<f32>::new(1.2, params, 1024, 2);
+        // the channels are slices of larger buffers
+        let arena_in = vec![0.0f32; 4096];
+        let mut arena_out = vec![0.0f32; 4096];
+        let (left_in, right_in) = arena_in[..2048].split_at(1024);
+        let waves_in: [&[f32]; 2] = [left_in, right_in];
+        let (left_out, right_out) = arena_out.split_at_mut(2048);
+        let mut waves_out: [&mut [f32]; 2] = [left_out, right_out];
+        let frames = resampler
+            .process_into_buffer(&waves_in, &mut waves_out, None)
+            .unwrap();
+        assert!(frames > 1150 && frames < 1250);
+    }
+
+    #[test]
+    fn process_arrays() {
+        let mut resampler = FftFixedInOut::<f64>::new(44100, 48000, 147, 2);
+        assert_eq!(resampler.nbr_frames_needed(), 147);
+        let waves_in = [[0.0f64; 147]; 2];
+        let out = resampler.process(&waves_in).unwrap();
         assert_eq!(out.len(), 2);
-        assert_eq!(out[0].len(), 1024);
+        assert_eq!(out[0].len(), 160);
+        let mut waves_out = [[0.0f64; 160]; 2];
+        let frames = resampler
+            .process_into_buffer(&waves_in, &mut waves_out, None)
+            .unwrap();
+        assert_eq!(frames, 160);
     }
 
     #[test]
-    fn make_resampler_fo_32() {
+    fn invalid_kaiser_beta() {
+        for beta in [-1.0, std::f64::NAN, std::f64::INFINITY].iter().copied() {
+            let params = InterpolationParameters {
+                sinc_len: 64,
+                f_cutoff: 0.95,
+                interpolation: InterpolationType::Linear,
+                oversampling_factor: 16,
+                window: WindowFunction::Kaiser { beta },
+            };
+            match SincFixedIn::<f64>::try_new(1.2, params, 1024, 2) {
+                Err(ResampleError::InvalidParameter { name, .. }) => assert_eq!(name, "window"),
+                _ => panic!("expected an InvalidParameter error"),
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_parameters() {
+        let params = InterpolationParameters {
+            sinc_len: 64,
+            f_cutoff: 1.5,
+            interpolation: InterpolationType::Linear,
+            oversampling_factor: 16,
+            window: WindowFunction::BlackmanHarris2,
+        };
+        match SincFixedIn::<f64>::try_new(1.2, params, 1024, 2) {
+            Err(ResampleError::InvalidParameter { name, .. }) => assert_eq!(name, "f_cutoff"),
+            _ => panic!("expected an InvalidParameter error"),
+        }
         let params = InterpolationParameters {
             sinc_len: 64,
             f_cutoff: 0.95,
-            interpolation: InterpolationType::Cubic,
+            interpolation: InterpolationType::Linear,
+            oversampling_factor: 0,
+            window: WindowFunction::BlackmanHarris2,
+        };
+        match SincFixedOut::<f64>::try_new(1.2, params, 1024, 2) {
+            Err(ResampleError::InvalidParameter { name, .. }) => {
+                assert_eq!(name, "oversampling_factor")
+            }
+            _ => panic!("expected an InvalidParameter error"),
+        }
+        let params = InterpolationParameters {
+            sinc_len: 64,
+            f_cutoff: 0.95,
+            interpolation: InterpolationType::Linear,
             oversampling_factor: 16,
             window: WindowFunction::BlackmanHarris2,
         };
-        let mut resampler = SincFixedOut::<f32>::new(1.2, params, 1024, 2);
-        let frames = resampler.nbr_frames_needed();
-        println!("{}", frames);
-        assert!(frames > 800 && frames < 900);
-        let waves = vec![vec![0.0f32; frames]; 2];
-        let out = resampler.process(&waves).unwrap();
-        assert_eq!(out.len(), 2);
-        assert_eq!(out[0].len(), 1024);
+        match SincFixedIn::<f32>::try_new(1.2, params, 1024, 0) {
+            Err(ResampleError::InvalidParameter { name, .. }) => assert_eq!(name, "nbr_channels"),
+            _ => panic!("expected an InvalidParameter error"),
+        }
     }
 }
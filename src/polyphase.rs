@@ -0,0 +1,222 @@
+use crate::interpolation::interp_cubic;
+use crate::simd::{DotProduct, DotProductKernel};
+use crate::sinc::make_sincs;
+use crate::windows::WindowFunction;
+use num_integer as integer;
+use num_traits::Float;
+
+/// Length of the sinc filters of the polyphase stage.
+/// The signal has been oversampled by two in the FFT stage,
+/// so the filters only need to remove images from half the intermediate Nyquist frequency
+/// and up, which leaves a wide transition band.
+const SINC_LEN: usize = 48;
+
+/// Number of sinc filters in the table, the values between them are found by cubic interpolation.
+const OVERSAMPLING_FACTOR: usize = 256;
+
+/// The second stage of a synchronous resampler in two-stage mode.
+///
+/// It resamples the output of the FFT stage, which has twice the input sample rate,
+/// to the output sample rate. The time of each output frame is counted in exact integer steps
+/// of `1 / factor` intermediate frames, so the phase never drifts, even for rate pairs
+/// like 88200 to 44101 Hz that have a very large denominator.
+/// The filter coefficients for each time are interpolated from a table of sincs,
+/// which keeps the table small regardless of the rates.
+///
+/// The buffers of the channels start with `history_len` frames of zeros,
+/// followed by the intermediate frames that have not yet been consumed.
+/// The stage holds the shared time and fill level, while the channels own their buffers.
+pub(crate) struct PolyphaseStage<T> {
+    sincs: Vec<Vec<T>>,
+    /// The number of steps between output frames, `fs_mid / gcd(fs_mid, fs_out)`.
+    step: u64,
+    /// The number of steps per intermediate frame, `fs_out / gcd(fs_mid, fs_out)`.
+    factor: u64,
+    /// The time of the next output frame, in steps relative to the start of the buffers.
+    time: u64,
+    /// The number of valid frames in the buffers.
+    buffered: usize,
+    kernel: DotProductKernel,
+}
+
+impl<T: Float + DotProduct> PolyphaseStage<T> {
+    /// Create a new stage for resampling from the intermediate rate `fs_mid` to `fs_out`.
+    pub(crate) fn new(fs_mid: usize, fs_out: usize) -> Self {
+        let gcd = integer::gcd(fs_mid, fs_out);
+        let factor = (fs_out / gcd) as u64;
+        let sincs = make_sincs(
+            SINC_LEN,
+            OVERSAMPLING_FACTOR,
+            1.0,
+            WindowFunction::BlackmanHarris2,
+        );
+        debug!(
+            "Create new PolyphaseStage, fs_mid: {}, fs_out: {}, sinc_len: {}, oversampling_factor: {}",
+            fs_mid, fs_out, SINC_LEN, OVERSAMPLING_FACTOR
+        );
+        PolyphaseStage {
+            sincs,
+            step: (fs_mid / gcd) as u64,
+            factor,
+            time: Self::history_len() as u64 * factor,
+            buffered: Self::history_len(),
+            kernel: DotProductKernel::detect(),
+        }
+    }
+
+    /// The number of frames of zeros at the start of the buffers after creating or resetting.
+    /// The first output frame is calculated at the first intermediate frame,
+    /// so the stage does not add any delay.
+    fn history_len() -> usize {
+        SINC_LEN / 2 + 2
+    }
+
+    /// The largest number of frames that can remain in the buffers between chunks.
+    fn retained_max() -> usize {
+        SINC_LEN + 4
+    }
+
+    /// Get the length the channel buffers need, for receiving up to `new_frames` frames per chunk.
+    pub(crate) fn buffer_len(new_frames: usize) -> usize {
+        Self::retained_max() + new_frames
+    }
+
+    /// Get the number of frames that are in the buffers.
+    pub(crate) fn buffered(&self) -> usize {
+        self.buffered
+    }
+
+    /// Get the number of output frames that can be calculated
+    /// when the buffers hold `available` frames.
+    pub(crate) fn nbr_output_frames(&self, available: usize) -> usize {
+        // the frame at time t needs the frames up to floor(t) + SINC_LEN / 2 + 1
+        let end = (available.saturating_sub(SINC_LEN / 2 + 1)) as u64 * self.factor;
+        if end <= self.time {
+            return 0;
+        }
+        ((end - self.time + self.step - 1) / self.step) as usize
+    }
+
+    /// Get the largest number of output frames that a chunk can give,
+    /// when up to `new_frames` intermediate frames are added per chunk.
+    pub(crate) fn output_frames_max(&self, new_frames: usize) -> usize {
+        ((Self::buffer_len(new_frames) as u64 * self.factor + self.step - 1) / self.step) as usize
+            + 1
+    }
+
+    /// Get the number of frames the buffers must hold to calculate `nbr_frames` output frames.
+    pub(crate) fn frames_needed(&self, nbr_frames: usize) -> usize {
+        if nbr_frames == 0 {
+            return 0;
+        }
+        let last = self.time + (nbr_frames as u64 - 1) * self.step;
+        (last / self.factor) as usize + SINC_LEN / 2 + 2
+    }
+
+    /// Get the largest number of frames that must be added to the buffers
+    /// for calculating `nbr_frames` output frames.
+    pub(crate) fn new_frames_max(&self, nbr_frames: usize) -> usize {
+        ((nbr_frames as u64 * self.step + self.factor - 1) / self.factor) as usize + SINC_LEN + 4
+    }
+
+    /// Get the number of frames to remove from the start of the buffers,
+    /// after calculating `nbr_frames` output frames from `available` frames.
+    pub(crate) fn frames_consumed(&self, nbr_frames: usize, available: usize) -> usize {
+        let next = (self.time + nbr_frames as u64 * self.step) / self.factor;
+        (next.saturating_sub(SINC_LEN as u64 / 2 + 1) as usize).min(available)
+    }
+
+    /// Calculate output frames from the buffer of a channel, one for each element of `wave_out`.
+    /// The buffer must hold the frames given by `frames_needed`.
+    pub(crate) fn resample(&self, buffer: &[T], wave_out: &mut [T]) {
+        let oversampling = OVERSAMPLING_FACTOR as u64;
+        // the sincs are centered at SINC_LEN / 2, and ordered by increasing time
+        let offset = (SINC_LEN as u64 / 2 - 1) * oversampling + 1;
+        let mut points = [T::zero(); 4];
+        let mut time = self.time;
+        for sample in wave_out.iter_mut() {
+            let scaled = time * oversampling;
+            let position = scaled / self.factor - offset;
+            let frac = T::from((scaled % self.factor) as f64 / self.factor as f64).unwrap();
+            for (n, point) in points.iter_mut().enumerate() {
+                let pos = (position + n as u64 - 1) as usize;
+                let index = pos / OVERSAMPLING_FACTOR;
+                let sinc = &self.sincs[pos % OVERSAMPLING_FACTOR];
+                *point = T::dot_product(self.kernel, &buffer[index..index + SINC_LEN], sinc);
+            }
+            *sample = interp_cubic(frac, &points);
+            time += self.step;
+        }
+    }
+
+    /// Step the time past `nbr_frames` output frames,
+    /// after the channels have removed `consumed` frames from `available` frames in their buffers.
+    pub(crate) fn advance(&mut self, nbr_frames: usize, consumed: usize, available: usize) {
+        self.time += nbr_frames as u64 * self.step;
+        self.time -= consumed as u64 * self.factor;
+        self.buffered = available - consumed;
+    }
+
+    /// Get the time of an intermediate frame in output frames.
+    pub(crate) fn output_frames(&self, intermediate_frames: f64) -> f64 {
+        intermediate_frames * self.factor as f64 / self.step as f64
+    }
+
+    /// Return to the state after creating the stage. The channels must clear their buffers.
+    pub(crate) fn reset(&mut self) {
+        self.time = Self::history_len() as u64 * self.factor;
+        self.buffered = Self::history_len();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::polyphase::PolyphaseStage;
+
+    /// Resample a sine from 96000 to the given rate in one go,
+    /// and return the largest error compared to the exact values.
+    fn max_error(fs_out: usize) -> f64 {
+        let freq = 22000.0;
+        let mut stage = PolyphaseStage::<f64>::new(96000, fs_out);
+        let nbr_frames = 3000;
+        let available = stage.frames_needed(nbr_frames);
+        let mut buffer = vec![0.0; PolyphaseStage::<f64>::buffer_len(available)];
+        for (n, val) in buffer[stage.buffered()..available].iter_mut().enumerate() {
+            *val = (2.0 * std::f64::consts::PI * freq * n as f64 / 96000.0).sin();
+        }
+        assert_eq!(stage.nbr_output_frames(available), nbr_frames);
+        let mut wave_out = vec![0.0; nbr_frames];
+        stage.resample(&buffer, &mut wave_out);
+        let consumed = stage.frames_consumed(nbr_frames, available);
+        stage.advance(nbr_frames, consumed, available);
+        assert!(stage.buffered() <= PolyphaseStage::<f64>::retained_max());
+        // skip the start, where the zeros before the sine are seen by the filter
+        wave_out
+            .iter()
+            .enumerate()
+            .skip(100)
+            .map(|(n, val)| {
+                let t = n as f64 / fs_out as f64;
+                (val - (2.0 * std::f64::consts::PI * freq * t).sin()).abs()
+            })
+            .fold(0.0, f64::max)
+    }
+
+    #[test]
+    fn polyphase_accuracy() {
+        for fs_out in [44101, 48000, 88201, 192000].iter() {
+            assert!(max_error(*fs_out) < 1.0e-7, "{}", fs_out);
+        }
+    }
+
+    #[test]
+    fn polyphase_frames_needed() {
+        let stage = PolyphaseStage::<f32>::new(88200, 44101);
+        for nbr_frames in [1, 10, 1000].iter() {
+            let needed = stage.frames_needed(*nbr_frames);
+            assert_eq!(stage.nbr_output_frames(needed), *nbr_frames);
+            assert_eq!(stage.nbr_output_frames(needed - 1), *nbr_frames - 1);
+            assert!(needed - stage.buffered() <= stage.new_frames_max(*nbr_frames));
+        }
+    }
+}
@@ -0,0 +1,201 @@
+use crate::windows::{calculate_cutoff, WindowFunction};
+use crate::{InterpolationParameters, InterpolationType, MAX_SYNC_OVERSAMPLING};
+use num_integer as integer;
+
+/// A quality target, used to derive the interpolation parameters with
+/// `InterpolationParameters::from_quality`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QualityTarget {
+    /// Fast, with a short filter and some roll-off below the Nyquist frequency.
+    Fast,
+    /// A balance between quality and speed.
+    Balanced,
+    /// Best quality, with a long filter and very little roll-off.
+    Best,
+    /// A filter designed for a given stop band attenuation in dB, and a passband edge
+    /// relative to the lowest one of the input and output Nyquist frequencies.
+    /// The passband edge is limited to the range 0.5 to 0.99, and the attenuation to at least 21 dB.
+    Custom {
+        attenuation: f64,
+        passband_edge: f32,
+    },
+}
+
+impl InterpolationParameters {
+    /// Derive interpolation parameters for resampling from `fs_in` to `fs_out`,
+    /// for the given quality target.
+    ///
+    /// If `adjustable` is false and the ratio between the sample rates can be expressed
+    /// as a fraction with a small enough denominator, `InterpolationType::Nearest` is used
+    /// with an oversampling factor that places the intermediate points exactly
+    /// where the new samples are. This gives the best quality at the lowest cost,
+    /// but only as long as the resample ratio is not changed.
+    /// When the ratio is to be adjusted after creating the resampler, set `adjustable` to true,
+    /// to get parameters that work for any ratio.
+    ///
+    /// The filter cutoff is calculated with `calculate_cutoff`.
+    /// For `QualityTarget::Custom`, a Kaiser window is used, and the sinc length
+    /// is chosen to reach the attenuation with a transition band between the passband edge
+    /// and the Nyquist frequency.
+    pub fn from_quality(
+        target: QualityTarget,
+        fs_in: usize,
+        fs_out: usize,
+        adjustable: bool,
+    ) -> Self {
+        let (sinc_len, window, interpolation, oversampling_factor) = match target {
+            QualityTarget::Fast => (64, WindowFunction::Hann2, InterpolationType::Linear, 1024),
+            QualityTarget::Balanced => (
+                128,
+                WindowFunction::Blackman2,
+                InterpolationType::Linear,
+                2048,
+            ),
+            QualityTarget::Best => (
+                256,
+                WindowFunction::BlackmanHarris2,
+                InterpolationType::Cubic,
+                256,
+            ),
+            QualityTarget::Custom {
+                attenuation,
+                passband_edge,
+            } => {
+                let attenuation = attenuation.max(21.0);
+                let passband_edge = passband_edge.max(0.5).min(0.99);
+                // Kaiser's formula for the length of the filter
+                let transition = (1.0 - passband_edge as f64) / 2.0;
+                let length = (attenuation - 7.95) / (14.36 * transition);
+                let sinc_len = 8 * (length / 8.0).ceil().max(1.0) as usize;
+                // the cubic interpolation error decreases by about 60 dB per decade of oversampling
+                let oversampling_factor = 10.0f64.powf(attenuation / 60.0).ceil() as usize;
+                (
+                    sinc_len,
+                    WindowFunction::kaiser_from_attenuation(attenuation),
+                    InterpolationType::Cubic,
+                    oversampling_factor.next_power_of_two(),
+                )
+            }
+        };
+        let f_cutoff = calculate_cutoff(sinc_len, window);
+        let sync_factor = fs_out / integer::gcd(fs_in, fs_out).max(1);
+        let (interpolation, oversampling_factor) =
+            if !adjustable && sync_factor > 0 && sync_factor <= MAX_SYNC_OVERSAMPLING {
+                (InterpolationType::Nearest, sync_factor)
+            } else {
+                (interpolation, oversampling_factor)
+            };
+        let parameters = InterpolationParameters {
+            sinc_len,
+            f_cutoff,
+            interpolation,
+            oversampling_factor,
+            window,
+        };
+        debug!(
+            "Parameters for quality target {:?}, from {} to {}: {:?}",
+            target, fs_in, fs_out, parameters
+        );
+        parameters
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        InterpolationParameters, InterpolationType, QualityTarget, Resampler, SincFixedIn,
+        WindowFunction,
+    };
+
+    /// Resample a sine with frequency `freq` in Hz from `fs_in` to `fs_out`, and return the level
+    /// in dB of the difference between the output and `expected_amplitude` times an ideal sine.
+    fn error_level(
+        parameters: InterpolationParameters,
+        fs_in: usize,
+        fs_out: usize,
+        freq: f64,
+        expected_amplitude: f64,
+    ) -> f64 {
+        let ratio = fs_out as f64 / fs_in as f64;
+        let freq = freq / fs_in as f64;
+        let mut resampler = SincFixedIn::<f64>::new(ratio, parameters, 1024, 1);
+        let delay = resampler.output_delay();
+        let mut output = Vec::new();
+        for chunk in 0..12 {
+            let wave: Vec<f64> = (chunk * 1024..(chunk + 1) * 1024)
+                .map(|n| (2.0 * std::f64::consts::PI * freq * n as f64).sin())
+                .collect();
+            output.extend(resampler.process(&[wave]).unwrap().remove(0));
+        }
+        let start = output.len() / 4;
+        let end = 3 * output.len() / 4;
+        let sum_squares: f64 = output[start..end]
+            .iter()
+            .enumerate()
+            .map(|(n, val)| {
+                let time = ((n + start) as f64 - delay) / ratio;
+                let expected = (2.0 * std::f64::consts::PI * freq * time).sin();
+                (val - expected_amplitude * expected).powi(2)
+            })
+            .sum();
+        let rms = (sum_squares / (end - start) as f64).sqrt();
+        20.0 * (rms * 2.0f64.sqrt()).log10()
+    }
+
+    #[test]
+    fn presets() {
+        let params = InterpolationParameters::from_quality(QualityTarget::Best, 44100, 48000, true);
+        assert_eq!(params.sinc_len, 256);
+        assert_eq!(params.oversampling_factor, 256);
+        assert!((params.f_cutoff - 0.9473).abs() < 0.0001);
+        match params.interpolation {
+            InterpolationType::Cubic => {}
+            _ => panic!("expected cubic interpolation"),
+        }
+        let params =
+            InterpolationParameters::from_quality(QualityTarget::Best, 44100, 48000, false);
+        assert_eq!(params.oversampling_factor, 160);
+        match params.interpolation {
+            InterpolationType::Nearest => {}
+            _ => panic!("expected nearest interpolation"),
+        }
+        // too large denominator for synchronous resampling
+        let params =
+            InterpolationParameters::from_quality(QualityTarget::Fast, 44100, 44101, false);
+        assert_eq!(params.window, WindowFunction::Hann2);
+        assert_eq!(params.oversampling_factor, 1024);
+    }
+
+    #[test]
+    fn preset_quality() {
+        let targets = [
+            (QualityTarget::Fast, 75.0),
+            (QualityTarget::Balanced, 115.0),
+            (QualityTarget::Best, 170.0),
+        ];
+        for (target, attenuation) in targets.iter() {
+            for adjustable in [false, true].iter() {
+                let params =
+                    || InterpolationParameters::from_quality(*target, 48000, 44100, *adjustable);
+                assert!(error_level(params(), 48000, 44100, 15000.0, 1.0) < -90.0);
+                assert!(error_level(params(), 48000, 44100, 23000.0, 0.0) < -attenuation);
+            }
+        }
+    }
+
+    #[test]
+    fn custom_quality() {
+        for attenuation in [60.0, 100.0].iter() {
+            let target = QualityTarget::Custom {
+                attenuation: *attenuation,
+                passband_edge: 0.9,
+            };
+            let params = || InterpolationParameters::from_quality(target, 48000, 44100, true);
+            // a tone at the passband edge, and one just above the new Nyquist frequency
+            let passband = error_level(params(), 48000, 44100, 0.9 * 22050.0, 1.0);
+            let stopband = error_level(params(), 48000, 44100, 22500.0, 0.0);
+            assert!(passband < -(attenuation - 6.0));
+            assert!(stopband < -(attenuation - 6.0));
+        }
+    }
+}
@@ -0,0 +1,284 @@
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+
+/// The implementations of the scalar product between the input samples and a sinc filter.
+/// The fastest one supported by the cpu is selected when a sinc resampler is created,
+/// and a different one can be chosen with `set_dot_product_kernel`.
+///
+/// The kernels add up the products in different orders, so their results differ
+/// by a few units of the last place.
+/// Choosing the same kernel on all machines gives bit-identical output.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DotProductKernel {
+    /// Portable code, that relies on the compiler to vectorize the calculation.
+    Portable,
+    /// Explicit SSE instructions, available on all x86_64 cpus.
+    Sse,
+    /// Explicit AVX instructions, on x86_64 cpus that support it.
+    Avx,
+    /// Explicit AVX2 and FMA instructions, using fused multiply-add,
+    /// on x86_64 cpus that support both.
+    AvxFma,
+}
+
+impl DotProductKernel {
+    /// Get the fastest kernel that is supported by the cpu.
+    pub fn detect() -> Self {
+        [
+            DotProductKernel::AvxFma,
+            DotProductKernel::Avx,
+            DotProductKernel::Sse,
+        ]
+        .iter()
+        .copied()
+        .find(|kernel| kernel.is_available())
+        .unwrap_or(DotProductKernel::Portable)
+    }
+
+    /// Check if the kernel is supported by the cpu.
+    pub fn is_available(self) -> bool {
+        match self {
+            DotProductKernel::Portable => true,
+            #[cfg(target_arch = "x86_64")]
+            DotProductKernel::Sse => is_x86_feature_detected!("sse2"),
+            #[cfg(target_arch = "x86_64")]
+            DotProductKernel::Avx => is_x86_feature_detected!("avx"),
+            #[cfg(target_arch = "x86_64")]
+            DotProductKernel::AvxFma => {
+                is_x86_feature_detected!("avx2") && is_x86_feature_detected!("fma")
+            }
+            #[cfg(not(target_arch = "x86_64"))]
+            _ => false,
+        }
+    }
+}
+
+/// Scalar product of two slices, using a given kernel.
+pub trait DotProduct: Sized {
+    /// Calculate the scalar product of `a` and `b`, that must have the same length,
+    /// which is a multiple of 8 like for all sinc filters.
+    /// The kernel must be available, see `DotProductKernel::is_available`.
+    fn dot_product(kernel: DotProductKernel, a: &[Self], b: &[Self]) -> Self;
+}
+
+macro_rules! impl_portable {
+    ($ft:ty, $name:ident) => {
+        /// Scalar product with eight separate sums, that the compiler can vectorize.
+        fn $name(a: &[$ft], b: &[$ft]) -> $ft {
+            a.chunks(8)
+                .zip(b.chunks(8))
+                .fold([0.0; 8], |acc, (x, y)| {
+                    [
+                        acc[0] + x[0] * y[0],
+                        acc[1] + x[1] * y[1],
+                        acc[2] + x[2] * y[2],
+                        acc[3] + x[3] * y[3],
+                        acc[4] + x[4] * y[4],
+                        acc[5] + x[5] * y[5],
+                        acc[6] + x[6] * y[6],
+                        acc[7] + x[7] * y[7],
+                    ]
+                })
+                .iter()
+                .sum()
+        }
+    };
+}
+impl_portable!(f32, dot_product_portable_f32);
+impl_portable!(f64, dot_product_portable_f64);
+
+/// Generate an x86_64 kernel, that processes `2 * $lanes` values per iteration
+/// with two accumulators, and any remaining values one by one.
+macro_rules! impl_x86_kernel {
+    ($name:ident, $ft:ty, $lanes:expr, $feature:expr, $vt:ty, $zero:ident, $load:ident, $store:ident, $add:ident, |$acc:ident, $x:ident, $y:ident| $madd:expr) => {
+        #[cfg(target_arch = "x86_64")]
+        #[target_feature(enable = $feature)]
+        unsafe fn $name(a: &[$ft], b: &[$ft]) -> $ft {
+            let len = a.len().min(b.len());
+            let chunks = len / (2 * $lanes);
+            let mut acc0: $vt = $zero();
+            let mut acc1: $vt = $zero();
+            for n in 0..chunks {
+                let idx = 2 * $lanes * n;
+                let (x0, y0) = ($load(a.as_ptr().add(idx)), $load(b.as_ptr().add(idx)));
+                let (x1, y1) = (
+                    $load(a.as_ptr().add(idx + $lanes)),
+                    $load(b.as_ptr().add(idx + $lanes)),
+                );
+                acc0 = {
+                    let ($acc, $x, $y) = (acc0, x0, y0);
+                    $madd
+                };
+                acc1 = {
+                    let ($acc, $x, $y) = (acc1, x1, y1);
+                    $madd
+                };
+            }
+            let mut lanes = [0.0 as $ft; $lanes];
+            $store(lanes.as_mut_ptr(), $add(acc0, acc1));
+            let mut sum: $ft = lanes.iter().sum();
+            for idx in (2 * $lanes * chunks)..len {
+                sum += a[idx] * b[idx];
+            }
+            sum
+        }
+    };
+}
+
+impl_x86_kernel!(
+    dot_product_sse_f32,
+    f32,
+    4,
+    "sse2",
+    __m128,
+    _mm_setzero_ps,
+    _mm_loadu_ps,
+    _mm_storeu_ps,
+    _mm_add_ps,
+    |acc, x, y| _mm_add_ps(acc, _mm_mul_ps(x, y))
+);
+impl_x86_kernel!(
+    dot_product_sse_f64,
+    f64,
+    2,
+    "sse2",
+    __m128d,
+    _mm_setzero_pd,
+    _mm_loadu_pd,
+    _mm_storeu_pd,
+    _mm_add_pd,
+    |acc, x, y| _mm_add_pd(acc, _mm_mul_pd(x, y))
+);
+impl_x86_kernel!(
+    dot_product_avx_f32,
+    f32,
+    8,
+    "avx",
+    __m256,
+    _mm256_setzero_ps,
+    _mm256_loadu_ps,
+    _mm256_storeu_ps,
+    _mm256_add_ps,
+    |acc, x, y| _mm256_add_ps(acc, _mm256_mul_ps(x, y))
+);
+impl_x86_kernel!(
+    dot_product_avx_f64,
+    f64,
+    4,
+    "avx",
+    __m256d,
+    _mm256_setzero_pd,
+    _mm256_loadu_pd,
+    _mm256_storeu_pd,
+    _mm256_add_pd,
+    |acc, x, y| _mm256_add_pd(acc, _mm256_mul_pd(x, y))
+);
+impl_x86_kernel!(
+    dot_product_fma_f32,
+    f32,
+    8,
+    "avx2,fma",
+    __m256,
+    _mm256_setzero_ps,
+    _mm256_loadu_ps,
+    _mm256_storeu_ps,
+    _mm256_add_ps,
+    |acc, x, y| _mm256_fmadd_ps(x, y, acc)
+);
+impl_x86_kernel!(
+    dot_product_fma_f64,
+    f64,
+    4,
+    "avx2,fma",
+    __m256d,
+    _mm256_setzero_pd,
+    _mm256_loadu_pd,
+    _mm256_storeu_pd,
+    _mm256_add_pd,
+    |acc, x, y| _mm256_fmadd_pd(x, y, acc)
+);
+
+macro_rules! impl_dot_product {
+    ($ft:ty, $portable:ident, $sse:ident, $avx:ident, $fma:ident) => {
+        impl DotProduct for $ft {
+            fn dot_product(kernel: DotProductKernel, a: &[$ft], b: &[$ft]) -> $ft {
+                debug_assert_eq!(a.len(), b.len());
+                match kernel {
+                    DotProductKernel::Portable => $portable(a, b),
+                    // Safety: the resamplers only use kernels that are available.
+                    #[cfg(target_arch = "x86_64")]
+                    DotProductKernel::Sse => unsafe { $sse(a, b) },
+                    #[cfg(target_arch = "x86_64")]
+                    DotProductKernel::Avx => unsafe { $avx(a, b) },
+                    #[cfg(target_arch = "x86_64")]
+                    DotProductKernel::AvxFma => unsafe { $fma(a, b) },
+                    #[cfg(not(target_arch = "x86_64"))]
+                    _ => $portable(a, b),
+                }
+            }
+        }
+    };
+}
+impl_dot_product!(
+    f32,
+    dot_product_portable_f32,
+    dot_product_sse_f32,
+    dot_product_avx_f32,
+    dot_product_fma_f32
+);
+impl_dot_product!(
+    f64,
+    dot_product_portable_f64,
+    dot_product_sse_f64,
+    dot_product_avx_f64,
+    dot_product_fma_f64
+);
+
+#[cfg(test)]
+mod tests {
+    use crate::simd::{DotProduct, DotProductKernel};
+
+    const KERNELS: [DotProductKernel; 4] = [
+        DotProductKernel::Portable,
+        DotProductKernel::Sse,
+        DotProductKernel::Avx,
+        DotProductKernel::AvxFma,
+    ];
+
+    #[test]
+    fn detect_kernel() {
+        let kernel = DotProductKernel::detect();
+        assert!(kernel.is_available());
+        assert!(DotProductKernel::Portable.is_available());
+    }
+
+    #[test]
+    fn kernels_f64() {
+        for len in [8, 16, 24, 40, 256].iter() {
+            let a: Vec<f64> = (0..*len).map(|n| (0.37 * n as f64).sin()).collect();
+            let b: Vec<f64> = (0..*len).map(|n| (0.11 * n as f64).cos()).collect();
+            let expected: f64 = a.iter().zip(b.iter()).map(|(x, y)| x * y).sum();
+            for kernel in KERNELS.iter().filter(|kernel| kernel.is_available()) {
+                let result = f64::dot_product(*kernel, &a, &b);
+                assert!((result - expected).abs() < 1.0e-13, "{:?}", kernel);
+            }
+        }
+    }
+
+    #[test]
+    fn kernels_f32() {
+        for len in [8, 16, 24, 40, 256].iter() {
+            let a: Vec<f32> = (0..*len).map(|n| (0.37 * n as f32).sin()).collect();
+            let b: Vec<f32> = (0..*len).map(|n| (0.11 * n as f32).cos()).collect();
+            let expected: f64 = a
+                .iter()
+                .zip(b.iter())
+                .map(|(x, y)| *x as f64 * *y as f64)
+                .sum();
+            for kernel in KERNELS.iter().filter(|kernel| kernel.is_available()) {
+                let result = f32::dot_product(*kernel, &a, &b);
+                assert!((result as f64 - expected).abs() < 1.0e-5, "{:?}", kernel);
+            }
+        }
+    }
+}
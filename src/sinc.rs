@@ -18,18 +18,45 @@ pub fn make_sincs<T: Float>(
     f_cutoff: f32,
     windowfunc: WindowFunction,
 ) -> Vec<Vec<T>> {
-    let totpoints = (npoints * factor) as isize;
-    let mut y = Vec::with_capacity(totpoints as usize);
-    let window = make_window::<T>(totpoints as usize, windowfunc);
-    let mut sum = T::zero();
-    for x in 0..totpoints {
-        let val = window[x as usize]
+    let window = make_window::<T>(npoints * factor, windowfunc);
+    let mut sincs = vec![vec![T::zero(); npoints]; factor];
+    update_sincs(&mut sincs, f_cutoff, &window);
+    sincs
+}
+
+/// Helper function. Recalculate a set of windowed sincs in place, using a new cutoff.
+/// The window must have as many points as the sincs have in total.
+pub fn update_sincs<T: Float>(sincs: &mut [Vec<T>], f_cutoff: f32, window: &[T]) {
+    let factor = sincs.len();
+    let totpoints = window.len() as isize;
+    fill_sincs(sincs, |x| {
+        window[x]
             * sinc(
-                T::from(x - totpoints / 2).unwrap() * T::from(f_cutoff).unwrap()
+                T::from(x as isize - totpoints / 2).unwrap() * T::from(f_cutoff).unwrap()
                     / T::from(factor).unwrap(),
-            );
+            )
+    });
+}
+
+/// Helper function. Split a prototype filter into a set of sincs in place.
+/// The prototype must have as many points as the sincs have in total.
+pub fn update_sincs_from_prototype<T: Float>(sincs: &mut [Vec<T>], prototype: &[f64]) {
+    fill_sincs(sincs, |x| T::from(prototype[x]).unwrap());
+}
+
+/// Helper function. Fill a set of sincs from a function giving the value at each point
+/// of the full filter, and normalize them to unity gain.
+fn fill_sincs<T: Float, F: Fn(usize) -> T>(sincs: &mut [Vec<T>], value: F) {
+    let factor = sincs.len();
+    let npoints = sincs[0].len();
+    let totpoints = npoints * factor;
+    let mut sum = T::zero();
+    for x in 0..totpoints {
+        let val = value(x);
         sum = sum + val;
-        y.push(val);
+        let p = x / factor;
+        let n = x % factor;
+        sincs[factor - n - 1][p] = val;
     }
     sum = sum / T::from(factor).unwrap();
     debug!(
@@ -38,13 +65,9 @@ pub fn make_sincs<T: Float>(
         factor,
         sum.to_f64()
     );
-    let mut sincs = vec![vec![T::zero(); npoints]; factor];
-    for p in 0..npoints {
-        for n in 0..factor {
-            sincs[factor - n - 1][p] = y[factor * p + n] / sum;
-        }
+    for sinc in sincs.iter_mut() {
+        sinc.iter_mut().for_each(|val| *val = *val / sum);
     }
-    sincs
 }
 
 #[cfg(test)]
@@ -59,4 +82,12 @@ mod tests {
         let sum: f64 = sincs.iter().map(|v| v.iter().sum::<f64>()).sum();
         assert!((sum - 8.0).abs() < 0.00001);
     }
+
+    #[test]
+    fn sincs_kaiser() {
+        let sincs = make_sincs::<f64>(32, 8, 0.9, WindowFunction::Kaiser { beta: 8.0 });
+        assert!((sincs[7][16] - 1.0).abs() < 0.2);
+        let sum: f64 = sincs.iter().map(|v| v.iter().sum::<f64>()).sum();
+        assert!((sum - 8.0).abs() < 0.00001);
+    }
 }
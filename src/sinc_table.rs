@@ -0,0 +1,495 @@
+use crate::windows::WindowFunction;
+use crate::{
+    calculate_sinc_cutoff, generate_sincs, round_sinc_len, validate_custom_filter,
+    validate_interpolation_parameters, CustomFilter, InterpolationParameters, ResampleError,
+    ResampleResult, SINC_CUTOFF_MARGIN,
+};
+use num_traits::Float;
+use std::any::{Any, TypeId};
+use std::fmt;
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, Ordering};
+use std::sync::{Arc, Mutex, Once, Weak};
+
+/// The parameters that determine the contents of a table of the built-in sinc filters.
+#[derive(Debug, Clone, PartialEq)]
+struct SincTableKey {
+    sinc_len: usize,
+    oversampling_factor: usize,
+    cutoff: f32,
+    window: WindowFunction,
+}
+
+impl SincTableKey {
+    fn new(resample_ratio: f64, parameters: &InterpolationParameters) -> Self {
+        SincTableKey {
+            sinc_len: round_sinc_len(parameters.sinc_len),
+            oversampling_factor: parameters.oversampling_factor,
+            cutoff: calculate_sinc_cutoff(parameters.f_cutoff, resample_ratio),
+            window: parameters.window,
+        }
+    }
+}
+
+/// Helper to validate the parameters for calculating a table.
+fn validate_table_parameters(
+    resample_ratio: f64,
+    parameters: &InterpolationParameters,
+) -> ResampleResult<()> {
+    if !(resample_ratio.is_finite() && resample_ratio > 0.0) {
+        return Err(ResampleError::invalid_parameter(
+            "resample_ratio",
+            "must be a positive number",
+        ));
+    }
+    validate_interpolation_parameters(parameters)
+}
+
+/// An entry in the process-wide cache. The tables are held by weak references,
+/// so that they are freed when no resampler uses them any more.
+type CacheEntry = (SincTableKey, TypeId, Weak<dyn Any + Send + Sync>);
+
+/// Get the process-wide cache, creating it on first use.
+fn sinc_table_cache() -> &'static Mutex<Vec<CacheEntry>> {
+    static INIT: Once = Once::new();
+    static CACHE: AtomicPtr<Mutex<Vec<CacheEntry>>> = AtomicPtr::new(ptr::null_mut());
+    INIT.call_once(|| {
+        let cache = Box::new(Mutex::new(Vec::new()));
+        CACHE.store(Box::into_raw(cache), Ordering::Release);
+    });
+    // Safety: the pointer was set from a leaked box by `call_once` above, and is never freed.
+    unsafe { &*CACHE.load(Ordering::Acquire) }
+}
+
+/// A table of precomputed sinc filters, that can be shared between resamplers.
+///
+/// Each sinc resampler normally calculates its own table, with `sinc_len * oversampling_factor`
+/// coefficients. When running many resamplers with the same parameters,
+/// the table can instead be calculated once, wrapped in an `Arc`,
+/// and given to each resampler with `SincFixedIn::new_with_table`
+/// or `SincFixedOut::new_with_table`.
+///
+/// The filters depend on the resample ratio only through the cutoff frequency,
+/// which is lowered when downsampling. A table made for upsampling can therefore be used
+/// for any upsampling ratio.
+///
+/// A table made with `new_with_custom_filter` holds the custom filter, see `CustomFilter`.
+/// The resamplers that are given the table then use that filter,
+/// also for the filters they calculate for other cutoffs.
+/// Such tables are never returned by `cached`.
+///
+/// Example, two resamplers sharing the same filters:
+/// ```
+/// use rubato::{InterpolationParameters, InterpolationType, SincFixedIn, SincTable, WindowFunction};
+/// use std::sync::Arc;
+/// let params = || InterpolationParameters {
+///     sinc_len: 256,
+///     f_cutoff: 0.95,
+///     interpolation: InterpolationType::Linear,
+///     oversampling_factor: 256,
+///     window: WindowFunction::BlackmanHarris2,
+/// };
+/// let ratio = 48000.0 / 44100.0;
+/// let table = Arc::new(SincTable::<f64>::new(ratio, &params()));
+/// let first = SincFixedIn::new_with_table(ratio, 1.1, params(), table.clone(), 1024, 2);
+/// let second = SincFixedIn::new_with_table(ratio, 1.1, params(), table, 1024, 2);
+/// ```
+#[derive(Clone)]
+pub struct SincTable<T> {
+    key: SincTableKey,
+    custom_filter: Option<Arc<CustomFilter>>,
+    filters: Vec<Vec<T>>,
+}
+
+impl<T> fmt::Debug for SincTable<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SincTable")
+            .field("sinc_len", &self.key.sinc_len)
+            .field("oversampling_factor", &self.key.oversampling_factor)
+            .field("cutoff", &self.key.cutoff)
+            .field("window", &self.key.window)
+            .field("custom_filter", &self.custom_filter)
+            .finish()
+    }
+}
+
+impl<T: Float> SincTable<T> {
+    /// Calculate a new table of sinc filters, for the given resample ratio and interpolation parameters.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any of the parameters is invalid, see `try_new`.
+    pub fn new(resample_ratio: f64, parameters: &InterpolationParameters) -> Self {
+        Self::try_new(resample_ratio, parameters).unwrap()
+    }
+
+    /// Calculate a new table of sinc filters, checking that the parameters are valid.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidParameter` error if the resample ratio is not a positive number,
+    /// or if the interpolation parameters are invalid, see `InterpolationParameters`.
+    pub fn try_new(
+        resample_ratio: f64,
+        parameters: &InterpolationParameters,
+    ) -> ResampleResult<Self> {
+        validate_table_parameters(resample_ratio, parameters)?;
+        let key = SincTableKey::new(resample_ratio, parameters);
+        Ok(Self::generate(key, None))
+    }
+
+    /// Calculate a new table of filters from a custom filter, see `CustomFilter`.
+    /// The custom filter replaces the windowed sinc made from `f_cutoff` and `window`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any of the parameters is invalid, see `try_new_with_custom_filter`.
+    pub fn new_with_custom_filter(
+        resample_ratio: f64,
+        parameters: &InterpolationParameters,
+        custom_filter: CustomFilter,
+    ) -> Self {
+        Self::try_new_with_custom_filter(resample_ratio, parameters, custom_filter).unwrap()
+    }
+
+    /// Calculate a new table of filters from a custom filter, checking that the parameters are valid.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidParameter` error if a prototype filter does not have
+    /// `sinc_len * oversampling_factor` points or has a zero sum, or for the same reasons as `try_new`.
+    pub fn try_new_with_custom_filter(
+        resample_ratio: f64,
+        parameters: &InterpolationParameters,
+        custom_filter: CustomFilter,
+    ) -> ResampleResult<Self> {
+        validate_table_parameters(resample_ratio, parameters)?;
+        validate_custom_filter(parameters, &custom_filter)?;
+        let key = SincTableKey::new(resample_ratio, parameters);
+        Ok(Self::generate(key, Some(Arc::new(custom_filter))))
+    }
+
+    /// Calculate the filters for a key that has already been validated.
+    fn generate(key: SincTableKey, custom_filter: Option<Arc<CustomFilter>>) -> Self {
+        debug!("Calculate new sinc table, {:?}", key);
+        let mut filters = vec![vec![T::zero(); key.sinc_len]; key.oversampling_factor];
+        generate_sincs(
+            &mut filters,
+            key.cutoff,
+            key.window,
+            custom_filter.as_deref(),
+        );
+        SincTable {
+            key,
+            custom_filter,
+            filters,
+        }
+    }
+
+    /// Get the length of the sinc filters, after rounding up to a multiple of 8.
+    pub fn sinc_len(&self) -> usize {
+        self.key.sinc_len
+    }
+
+    /// Get the number of intermediate points, which is the number of filters in the table.
+    pub fn oversampling_factor(&self) -> usize {
+        self.key.oversampling_factor
+    }
+
+    /// Get the cutoff frequency of the filters, relative to the input Nyquist frequency.
+    pub fn cutoff(&self) -> f32 {
+        self.key.cutoff
+    }
+
+    /// Check if the table was calculated for the given resample ratio and parameters.
+    /// A custom filter is held by the table itself, and is not part of the comparison.
+    pub(crate) fn matches(
+        &self,
+        resample_ratio: f64,
+        parameters: &InterpolationParameters,
+    ) -> bool {
+        self.key == SincTableKey::new(resample_ratio, parameters)
+    }
+
+    /// Get the filters, one for each intermediate point.
+    pub(crate) fn filters(&self) -> &[Vec<T>] {
+        &self.filters
+    }
+
+    /// Check if the filters are made from a custom prototype, that does not follow the cutoff.
+    pub(crate) fn has_prototype(&self) -> bool {
+        if let Some(CustomFilter::Prototype(_)) = self.custom_filter.as_deref() {
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Calculate a new table with the same parameters and custom filter, but a different cutoff.
+    pub(crate) fn with_cutoff(&self, cutoff: f32) -> Self {
+        let key = SincTableKey {
+            cutoff,
+            ..self.key.clone()
+        };
+        Self::generate(key, self.custom_filter.clone())
+    }
+}
+
+impl<T: Float + Send + Sync + 'static> SincTable<T> {
+    /// Get a table of sinc filters from a process-wide cache,
+    /// or calculate a new one and store it in the cache if there is none with the same parameters.
+    /// The cache only holds weak references, so a table is freed
+    /// when the last resampler using it is dropped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error for the same reasons as `try_new`.
+    pub fn cached(
+        resample_ratio: f64,
+        parameters: &InterpolationParameters,
+    ) -> ResampleResult<Arc<Self>> {
+        let key = SincTableKey::new(resample_ratio, parameters);
+        let type_id = TypeId::of::<T>();
+        let mut cache = sinc_table_cache()
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        cache.retain(|(_, _, table)| table.upgrade().is_some());
+        for (cached_key, cached_type, table) in cache.iter() {
+            if *cached_key == key && *cached_type == type_id {
+                if let Some(table) = table.upgrade() {
+                    if let Ok(table) = table.downcast::<Self>() {
+                        trace!("Using cached sinc table, {:?}", key);
+                        return Ok(table);
+                    }
+                }
+            }
+        }
+        let table = Arc::new(Self::try_new(resample_ratio, parameters)?);
+        let weak: Weak<dyn Any + Send + Sync> = Arc::downgrade(&table) as Weak<Self>;
+        cache.push((key, type_id, weak));
+        Ok(table)
+    }
+}
+
+/// The sinc tables used by a resampler, one for each cutoff that may be needed
+/// within the range of resample ratios given when creating the resampler.
+///
+/// All tables are calculated up front, so that changing the cutoff only means
+/// selecting another table, without calculating filters or allocating memory.
+/// The cutoffs are spaced by a factor `1 + 2 * SINC_CUTOFF_MARGIN`, starting from the cutoff
+/// of the original table, which may be shared with other resamplers.
+/// The other tables are private to the resampler.
+/// A table with a custom prototype does not follow the cutoff, and is used alone.
+pub(crate) struct SincTableBank<T> {
+    tables: Vec<Arc<SincTable<T>>>,
+    index: usize,
+    original_index: usize,
+}
+
+impl<T: Float> SincTableBank<T> {
+    /// Calculate the tables for the cutoffs needed when the resample ratio is changed
+    /// within `resample_ratio / max_resample_ratio_relative` to
+    /// `resample_ratio * max_resample_ratio_relative`.
+    /// The given table must have been calculated for `resample_ratio`.
+    pub(crate) fn new(
+        table: Arc<SincTable<T>>,
+        f_cutoff: f32,
+        resample_ratio: f64,
+        max_resample_ratio_relative: f64,
+    ) -> Self {
+        let original = table.cutoff();
+        let mut tables = vec![table];
+        if !tables[0].has_prototype() {
+            let step = 1.0 + 2.0 * SINC_CUTOFF_MARGIN;
+            let lowest =
+                calculate_sinc_cutoff(f_cutoff, resample_ratio / max_resample_ratio_relative);
+            let highest =
+                calculate_sinc_cutoff(f_cutoff, resample_ratio * max_resample_ratio_relative);
+            let mut lower = Vec::new();
+            let mut cutoff = original;
+            while cutoff > lowest {
+                cutoff /= step;
+                lower.push(Arc::new(tables[0].with_cutoff(cutoff)));
+            }
+            let mut cutoff = original;
+            while cutoff < highest {
+                cutoff = (cutoff * step).min(highest);
+                tables.push(Arc::new(tables[0].with_cutoff(cutoff)));
+            }
+            lower.reverse();
+            lower.append(&mut tables);
+            tables = lower;
+        }
+        let original_index = tables
+            .iter()
+            .position(|table| table.cutoff() == original)
+            .unwrap();
+        debug!(
+            "Calculated {} sinc tables, with cutoffs from {} to {}",
+            tables.len(),
+            tables[0].cutoff(),
+            tables[tables.len() - 1].cutoff()
+        );
+        SincTableBank {
+            tables,
+            index: original_index,
+            original_index,
+        }
+    }
+
+    /// Get the table that is currently selected.
+    pub(crate) fn current(&self) -> &SincTable<T> {
+        &self.tables[self.index]
+    }
+
+    /// Select the table with the highest cutoff that is not above `cutoff`,
+    /// or the one with the lowest cutoff if all are above.
+    pub(crate) fn select(&mut self, cutoff: f32) {
+        let index = self
+            .tables
+            .iter()
+            .rposition(|table| table.cutoff() <= cutoff)
+            .unwrap_or(0);
+        if index != self.index {
+            debug!(
+                "Change sinc cutoff from {} to {}",
+                self.tables[self.index].cutoff(),
+                self.tables[index].cutoff()
+            );
+            self.index = index;
+        }
+    }
+
+    /// Select the table that was used when the bank was created.
+    pub(crate) fn reset(&mut self) {
+        self.index = self.original_index;
+    }
+
+    /// Get the number of tables in the bank.
+    #[cfg(test)]
+    pub(crate) fn len(&self) -> usize {
+        self.tables.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::windows::make_window;
+    use crate::{
+        CustomFilter, InterpolationParameters, InterpolationType, ResampleError, Resampler,
+        SincFixedIn, SincFixedOut, SincTable, WindowFunction,
+    };
+    use std::sync::Arc;
+
+    fn make_params() -> InterpolationParameters {
+        InterpolationParameters {
+            sinc_len: 64,
+            f_cutoff: 0.95,
+            interpolation: InterpolationType::Cubic,
+            oversampling_factor: 16,
+            window: WindowFunction::BlackmanHarris2,
+        }
+    }
+
+    #[test]
+    fn shared_table() {
+        let table = Arc::new(SincTable::<f64>::new(1.2, &make_params()));
+        let mut shared =
+            SincFixedIn::new_with_table(1.2, 2.0, make_params(), table.clone(), 1024, 2);
+        let mut private =
+            SincFixedIn::<f64>::new_with_ratio_range(1.2, 2.0, make_params(), 1024, 2);
+        assert_eq!(Arc::strong_count(&table), 2);
+        let waves = vec![vec![0.5f64; 1024]; 2];
+        assert_eq!(
+            shared.process(&waves).unwrap(),
+            private.process(&waves).unwrap()
+        );
+        // a new cutoff selects another table, and leaves the shared table unchanged
+        shared.set_resample_ratio(0.8).unwrap();
+        assert!(shared.sincs.current().cutoff() <= 0.95 * 0.8);
+        assert_eq!(Arc::strong_count(&table), 2);
+        assert_eq!(table.cutoff(), 0.95);
+        // upsampling with any ratio uses the same filters
+        let mut fixed_out = SincFixedOut::new_with_table(2.0, 1.1, make_params(), table, 1024, 2);
+        fixed_out.set_resample_ratio_ramped(1.9).unwrap();
+    }
+
+    #[test]
+    fn cutoff_bank() {
+        let mut resampler = SincFixedOut::<f64>::new(0.5, make_params(), 1024, 2);
+        assert_eq!(resampler.sincs.len(), 7);
+        assert_eq!(resampler.sincs.current().cutoff(), 0.95 * 0.5);
+        for ratio in [0.455, 0.47, 0.5, 0.52, 0.55].iter().copied() {
+            resampler.set_resample_ratio(ratio).unwrap();
+            let wanted = 0.95 * ratio as f32;
+            let cutoff = resampler.sincs.current().cutoff();
+            assert!(cutoff <= wanted && cutoff > wanted / 1.05);
+        }
+        resampler.reset();
+        assert_eq!(resampler.sincs.current().cutoff(), 0.95 * 0.5);
+        // upsampling uses the same cutoff for the whole range
+        let resampler = SincFixedOut::<f64>::new(2.0, make_params(), 1024, 2);
+        assert_eq!(resampler.sincs.len(), 1);
+    }
+
+    #[test]
+    fn table_mismatch() {
+        let table = Arc::new(SincTable::<f64>::new(0.5, &make_params()));
+        match SincFixedIn::try_new_with_table(1.2, 1.1, make_params(), table, 1024, 2) {
+            Err(ResampleError::InvalidParameter { name, .. }) => assert_eq!(name, "sinc_table"),
+            _ => panic!("expected an InvalidParameter error"),
+        }
+    }
+
+    #[test]
+    fn shared_custom_table() {
+        let prototype =
+            || CustomFilter::Prototype(make_window::<f64>(64 * 16, WindowFunction::Hann));
+        let table = Arc::new(SincTable::<f64>::new_with_custom_filter(
+            1.2,
+            &make_params(),
+            prototype(),
+        ));
+        assert_eq!(
+            format!("{:?}", table),
+            "SincTable { sinc_len: 64, oversampling_factor: 16, cutoff: 0.95, \
+             window: BlackmanHarris2, custom_filter: Some(Prototype(<1024 points>)) }"
+        );
+        let mut shared =
+            SincFixedIn::new_with_table(1.2, 1.1, make_params(), table.clone(), 1024, 2);
+        let mut private =
+            SincFixedIn::<f64>::new_with_custom_filter(1.2, make_params(), prototype(), 1024, 2);
+        let waves = vec![vec![0.5f64; 1024]; 2];
+        assert_eq!(
+            shared.process(&waves).unwrap(),
+            private.process(&waves).unwrap()
+        );
+        // the prototype does not follow the cutoff, so the table stays shared
+        shared.set_resample_ratio(1.1).unwrap();
+        assert_eq!(Arc::strong_count(&table), 2);
+    }
+
+    #[test]
+    fn cached_table() {
+        let params = InterpolationParameters {
+            sinc_len: 24,
+            oversampling_factor: 12,
+            ..make_params()
+        };
+        let first = SincTable::<f32>::cached(1.5, &params).unwrap();
+        let second = SincTable::<f32>::cached(2.5, &params).unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+        // a different type or cutoff gives a different table
+        let other_type = SincTable::<f64>::cached(1.5, &params).unwrap();
+        assert_eq!(other_type.sinc_len(), 24);
+        let other_cutoff = SincTable::<f32>::cached(0.5, &params).unwrap();
+        assert!(!Arc::ptr_eq(&first, &other_cutoff));
+        // the cache does not keep tables alive
+        let cutoff = first.cutoff();
+        drop(first);
+        drop(second);
+        let third = SincTable::<f32>::cached(1.5, &params).unwrap();
+        assert_eq!(Arc::strong_count(&third), 1);
+        assert_eq!(third.cutoff(), cutoff);
+    }
+}
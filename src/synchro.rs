@@ -1,21 +1,153 @@
+use crate::polyphase::PolyphaseStage;
+use crate::simd::DotProduct;
 use crate::sinc::make_sincs;
 use crate::windows::WindowFunction;
 use num_complex::Complex;
 use num_integer as integer;
-use num_traits::Zero;
-use std::error;
+use num_traits::{Float, Zero};
 
-type Res<T> = Result<T, Box<dyn error::Error>>;
-
-use crate::Resampler;
-use crate::ResamplerError;
+use crate::{
+    channel_is_active, copy_and_pad, for_each_channel_with_scratch, input_channel,
+    nbr_input_frames, nbr_scratch_buffers, validate_buffers, validate_window, Resampler,
+};
+use crate::{ResampleError, ResampleResult};
 use realfft::{ComplexToReal, RealToComplex};
+use std::fmt;
+use std::sync::Arc;
+
+/// A struct holding the parameters for the antialiasing filter of the synchronous resamplers.
+///
+/// The default values give the filter used by `new`, a sinc as long as the FFT input
+/// windowed by `BlackmanHarris2`, with a cutoff that increases with the length.
+///
+/// Example, a synchronous resampler with a lower cutoff and a Kaiser window:
+/// ```
+/// use rubato::{FftFilterParameters, FftFixedIn, WindowFunction};
+/// let params = FftFilterParameters {
+///     f_cutoff: Some(0.9),
+///     window: WindowFunction::kaiser_from_attenuation(120.0),
+///     ..Default::default()
+/// };
+/// let resampler = FftFixedIn::<f64>::new_with_parameters(44100, 48000, 1024, 2, 2, params);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FftFilterParameters {
+    /// Length of the windowed sinc filter, in input frames.
+    /// Longer filters give a steeper transition between the pass band and the stop band,
+    /// and delay the signal more. Use `None` to make the filter as long as the FFT input,
+    /// which depends on the sample rates and the chunk size.
+    /// The length is independent of the chunk size. A filter longer than the FFT input
+    /// is either split into partitions of that length, or fitted in longer FFTs,
+    /// whichever is cheaper, without changing the chunk sizes. Must be larger than zero.
+    pub filter_len: Option<usize>,
+    /// Relative cutoff frequency of the filter (relative to the lowest one of fs_in/2 or fs_out/2).
+    /// A suitable value for a given `filter_len` and `window` can be found with `calculate_cutoff`.
+    /// Use `None` for a default cutoff, that approaches 1.0 for long filters.
+    /// Must be larger than zero and at most 1.0.
+    pub f_cutoff: Option<f32>,
+    /// Window function to use.
+    pub window: WindowFunction,
+}
 
-/// A helper for resampling a single chunk of data.
-struct FftResampler<T> {
+impl Default for FftFilterParameters {
+    fn default() -> Self {
+        FftFilterParameters {
+            filter_len: None,
+            f_cutoff: None,
+            window: WindowFunction::BlackmanHarris2,
+        }
+    }
+}
+
+/// The antialiasing filter of a synchronous resampler, that can be shared between resamplers.
+///
+/// The filter is stored as a spectrum, and its size depends on the FFT sizes,
+/// which are chosen from the sample rates and the chunk size.
+/// A filter that is longer than the FFT input is split into partitions of `fft_size_in` frames,
+/// and each FFT is multiplied with the spectra of all partitions.
+/// This costs one multiplication per partition for each frequency at the rate common
+/// to input and output, which is high for rate pairs like 44100 and 48000 Hz.
+/// When that is more expensive, the filter is instead fitted in a single FFT,
+/// that spans both the new input frames and the filter.
+/// When running many synchronous resamplers with the same parameters,
+/// the filter can be designed once. Get it from the first resampler with `filter`,
+/// and give it to the others with `new_with_filter`.
+/// Each resampler still creates its own FFT plans and work buffers,
+/// since these are modified during processing.
+///
+/// Example, two resamplers sharing the same filter:
+/// ```
+/// use rubato::FftFixedIn;
+/// let first = FftFixedIn::<f64>::new(44100, 48000, 1024, 2, 2);
+/// let second = FftFixedIn::<f64>::new_with_filter(44100, 48000, 1024, 2, 2, first.filter());
+/// ```
+pub struct FftFilter<T> {
     fft_size_in: usize,
     fft_size_out: usize,
+    /// Length of each FFT, that spans the new input frames and a partition of the filter.
+    transform_len_in: usize,
+    /// Length of each inverse FFT.
+    transform_len_out: usize,
+    filter_len: usize,
+    cutoff: f32,
+    nbr_partitions: usize,
+    /// The factor from the input rate to the rate common to input and output,
+    /// `fft_size_out / gcd(fft_size_in, fft_size_out)`.
+    upsampling: usize,
+    /// For a single partition, the spectrum of the filter at the input rate.
+    /// For several, the spectra of the partitions at the common rate, interleaved so that
+    /// the values of all partitions for one frequency are next to each other.
     filter_f: Vec<Complex<T>>,
+}
+
+impl<T> fmt::Debug for FftFilter<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FftFilter")
+            .field("fft_size_in", &self.fft_size_in)
+            .field("fft_size_out", &self.fft_size_out)
+            .field("filter_len", &self.filter_len)
+            .field("cutoff", &self.cutoff)
+            .field("nbr_partitions", &self.nbr_partitions)
+            .finish()
+    }
+}
+
+impl<T> FftFilter<T> {
+    /// Get the number of new input frames that are processed by each FFT.
+    pub fn fft_size_in(&self) -> usize {
+        self.fft_size_in
+    }
+
+    /// Get the number of output frames that are completed by each inverse FFT.
+    pub fn fft_size_out(&self) -> usize {
+        self.fft_size_out
+    }
+
+    /// Get the length of the windowed sinc filter, in input frames.
+    pub fn filter_len(&self) -> usize {
+        self.filter_len
+    }
+
+    /// Get the cutoff frequency of the filter, relative to the input Nyquist frequency.
+    pub fn cutoff(&self) -> f32 {
+        self.cutoff
+    }
+
+    /// Get the number of partitions of `fft_size_in` frames that the filter is split into.
+    /// This is one for a filter that fits in a single FFT.
+    pub fn nbr_partitions(&self) -> usize {
+        self.nbr_partitions
+    }
+
+    /// Get the length of the tail of each inverse FFT, that is added to the following ones.
+    fn overlap_len(&self) -> usize {
+        self.transform_len_out - self.fft_size_out
+    }
+}
+
+/// The FFT plans and work buffers used by an FftFilter when resampling a chunk.
+/// They are modified during processing, so each thread needs its own set.
+struct FftScratch<T> {
     fft: RealToComplex<T>,
     ifft: ComplexToReal<T>,
     input_buf: Vec<T>,
@@ -24,21 +156,119 @@ struct FftResampler<T> {
     output_buf: Vec<T>,
 }
 
+/// The state of the filter for a single channel, that is carried from one FFT to the next.
+struct FftHistory<T> {
+    /// The tail of the previous inverse FFT, that is added to the next one.
+    overlap: Vec<T>,
+    /// The spectra of the latest input blocks, one for each filter partition,
+    /// interleaved like the partitions. Only used by filters with several partitions.
+    spectra: Vec<Complex<T>>,
+    /// The position of the newest block in `spectra`.
+    newest: usize,
+}
+
+impl<T: Float> FftHistory<T> {
+    /// Create the state for a channel, filled with zeros.
+    fn new(filter: &FftFilter<T>) -> Self {
+        let spectra_len = if filter.nbr_partitions > 1 {
+            (filter.fft_size_in + 1) * filter.nbr_partitions
+        } else {
+            0
+        };
+        FftHistory {
+            overlap: vec![T::zero(); filter.overlap_len()],
+            spectra: vec![Complex::zero(); spectra_len],
+            newest: 0,
+        }
+    }
+
+    /// Fill the state with zeros.
+    fn reset(&mut self) {
+        self.overlap.iter_mut().for_each(|val| *val = T::zero());
+        self.spectra
+            .iter_mut()
+            .for_each(|val| *val = Complex::zero());
+        self.newest = 0;
+    }
+}
+
+/// The buffers of a single channel.
+struct FftChannel<T> {
+    /// The state of the filter.
+    history: FftHistory<T>,
+    /// Input frames waiting to be processed.
+    input: Vec<T>,
+    /// Output frames waiting to be returned.
+    output: Vec<T>,
+    /// Frames from the FFT stage waiting for the polyphase stage, only used in two-stage mode.
+    intermediate: Vec<T>,
+}
+
+impl<T: Float> FftChannel<T> {
+    /// Create the buffers for a channel, filled with zeros.
+    fn new(
+        filter: &FftFilter<T>,
+        input_len: usize,
+        output_len: usize,
+        intermediate_len: usize,
+    ) -> Self {
+        FftChannel {
+            history: FftHistory::new(filter),
+            input: vec![T::zero(); input_len],
+            output: vec![T::zero(); output_len],
+            intermediate: vec![T::zero(); intermediate_len],
+        }
+    }
+
+    /// Fill all the buffers with zeros.
+    fn reset(&mut self) {
+        self.history.reset();
+        for buf in [&mut self.input, &mut self.output, &mut self.intermediate].iter_mut() {
+            buf.iter_mut().for_each(|val| *val = T::zero());
+        }
+    }
+
+    /// Add `new_frames` frames from the start of the output buffer to the frames
+    /// waiting for the polyphase stage, and resample as many as the stage asks for.
+    /// The resampled frames replace the contents of the output buffer.
+    fn resample_polyphase(
+        &mut self,
+        stage: &PolyphaseStage<T>,
+        new_frames: usize,
+        nbr_frames: usize,
+        consumed: usize,
+    ) where
+        T: Float + DotProduct,
+    {
+        let available = stage.buffered() + new_frames;
+        self.intermediate[stage.buffered()..available].copy_from_slice(&self.output[..new_frames]);
+        stage.resample(
+            &self.intermediate[..available],
+            &mut self.output[..nbr_frames],
+        );
+        self.intermediate.copy_within(consumed..available, 0);
+    }
+}
+
 /// A synchronous resampler that needs a fixed number of audio frames for input
 /// and returns a variable number of frames.
 ///
 /// The resampling is done by FFT:ing the input data. The spectrum is then extended or
 /// truncated as well as multiplied with an antialiasing filter
 /// before it's inverse transformed to get the resampled waveforms.
+///
+/// In two-stage mode, see `new_two_stage`, the FFTs instead upsample by two,
+/// and a short polyphase filter resamples the result to the output rate.
 pub struct FftFixedIn<T> {
     nbr_channels: usize,
     chunk_size_in: usize,
     fft_size_in: usize,
     fft_size_out: usize,
-    overlaps: Vec<Vec<T>>,
-    input_buffers: Vec<Vec<T>>,
+    channels: Vec<FftChannel<T>>,
     saved_frames: usize,
-    resampler: FftResampler<T>,
+    filter: Arc<FftFilter<T>>,
+    scratch: Vec<FftScratch<T>>,
+    polyphase: Option<PolyphaseStage<T>>,
 }
 
 /// A synchronous resampler that needs a varying number of audio frames for input
@@ -47,16 +277,20 @@ pub struct FftFixedIn<T> {
 /// The resampling is done by FFT:ing the input data. The spectrum is then extended or
 /// truncated as well as multiplied with an antialiasing filter
 /// before it's inverse transformed to get the resampled waveforms.
+///
+/// In two-stage mode, see `new_two_stage`, the FFTs instead upsample by two,
+/// and a short polyphase filter resamples the result to the output rate.
 pub struct FftFixedOut<T> {
     nbr_channels: usize,
     chunk_size_out: usize,
     fft_size_in: usize,
     fft_size_out: usize,
-    overlaps: Vec<Vec<T>>,
-    output_buffers: Vec<Vec<T>>,
+    channels: Vec<FftChannel<T>>,
     saved_frames: usize,
     frames_needed: usize,
-    resampler: FftResampler<T>,
+    filter: Arc<FftFilter<T>>,
+    scratch: Vec<FftScratch<T>>,
+    polyphase: Option<PolyphaseStage<T>>,
 }
 
 /// A synchronous resampler that accepts a fixed number of audio frames for input
@@ -70,112 +304,415 @@ pub struct FftFixedInOut<T> {
     chunk_size_in: usize,
     chunk_size_out: usize,
     fft_size_in: usize,
-    overlaps: Vec<Vec<T>>,
-    resampler: FftResampler<T>,
+    channels: Vec<FftChannel<T>>,
+    filter: Arc<FftFilter<T>>,
+    scratch: Vec<FftScratch<T>>,
 }
 
 macro_rules! impl_resampler {
     ($ft:ty, $rt:ty) => {
         impl $rt {
-            //
-            pub fn new(fft_size_in: usize, fft_size_out: usize) -> Self {
+            /// Create a new FftFilter, with an antialiasing filter made from a sinc
+            /// windowed by the given window function.
+            /// The parameters must have been checked with `validate_filter_parameters`.
+            fn new(fft_size_in: usize, fft_size_out: usize, parameters: &FftFilterParameters) -> Self {
+                Self::new_for_rates(fft_size_in, fft_size_out, fft_size_in, fft_size_out, parameters)
+            }
+
+            /// Create a new FftFilter like `new`, with the cutoff placed for resampling from
+            /// `fs_in` to `fs_out`, which may differ from the ratio of the FFT sizes
+            /// when it is followed by a polyphase stage.
+            fn new_for_rates(
+                fft_size_in: usize,
+                fft_size_out: usize,
+                fs_in: usize,
+                fs_out: usize,
+                parameters: &FftFilterParameters,
+            ) -> Self {
+                let filter_len = parameters.filter_len.unwrap_or(fft_size_in);
+                let f_cutoff = parameters
+                    .f_cutoff
+                    .unwrap_or_else(|| 0.4f32.powf(16.0 / filter_len as f32));
                 // calculate antialiasing cutoff
-                let cutoff = if fft_size_in > fft_size_out {
-                    0.4f32.powf(16.0 / fft_size_in as f32) * fft_size_out as f32
-                        / fft_size_in as f32
+                let cutoff = if fs_in > fs_out {
+                    f_cutoff * fs_out as f32 / fs_in as f32
                 } else {
-                    0.4f32.powf(16.0 / fft_size_in as f32)
+                    f_cutoff
                 };
                 debug!(
-                    "Create new FftResampler, fft_size_in: {}, fft_size_out: {}, cutoff: {}",
-                    fft_size_in, fft_size_out, cutoff
+                    "Create new FftFilter, fft_size_in: {}, fft_size_out: {}, filter_len: {}, cutoff: {}, window: {:?}",
+                    fft_size_in, fft_size_out, filter_len, cutoff, parameters.window
                 );
-                let sinc =
-                    make_sincs::<$ft>(fft_size_in, 1, cutoff, WindowFunction::BlackmanHarris2);
-                let mut filter_t: Vec<$ft> = vec![0.0; 2 * fft_size_in];
-                let mut filter_f: Vec<Complex<$ft>> = vec![Complex::zero(); fft_size_in + 1];
-                for n in 0..fft_size_in {
-                    filter_t[n] = sinc[0][n] / (fft_size_in as $ft);
-                }
-
-                let input_f: Vec<Complex<$ft>> = vec![Complex::zero(); fft_size_in + 1];
-                let input_buf: Vec<$ft> = vec![0.0; 2 * fft_size_in];
-                let output_f: Vec<Complex<$ft>> = vec![Complex::zero(); fft_size_out + 1];
-                let output_buf: Vec<$ft> = vec![0.0; 2 * fft_size_out];
-                let mut fft = RealToComplex::<$ft>::new(2 * fft_size_in).unwrap();
-                let ifft = ComplexToReal::<$ft>::new(2 * fft_size_out).unwrap();
-                fft.process(&mut filter_t, &mut filter_f).unwrap();
+                // A filter longer than the FFT input is either split into partitions of `fft_size_in`
+                // frames, or fitted in longer FFTs. Those must fit the new frames and the filter,
+                // and have an even length. In both cases, each input frame affects the output
+                // of several FFTs, which are added together like for the shortest filters.
+                let nbr_partitions = (filter_len + fft_size_in - 1) / fft_size_in;
+                let upsampling = fft_size_out / integer::gcd(fft_size_in, fft_size_out);
+                let half_len_in =
+                    fft_size_in * ((filter_len + 3 * fft_size_in - 1) / (2 * fft_size_in));
+                let partitioned = nbr_partitions > 1
+                    && partitions_are_cheaper(
+                        fft_size_in,
+                        fft_size_out,
+                        nbr_partitions,
+                        upsampling,
+                        half_len_in,
+                    );
+                let sinc = make_sincs::<$ft>(filter_len, 1, cutoff, parameters.window);
+                let (nbr_partitions, half_len_in, filter_f) = if partitioned {
+                    let filter_f =
+                        Self::partition_spectra(&sinc[0], fft_size_in, nbr_partitions, upsampling);
+                    (nbr_partitions, fft_size_in, filter_f)
+                } else {
+                    let mut filter_t: Vec<$ft> = vec![0.0; 2 * half_len_in];
+                    let mut filter_f: Vec<Complex<$ft>> = vec![Complex::zero(); half_len_in + 1];
+                    for n in 0..filter_len {
+                        filter_t[n] = sinc[0][n] / (half_len_in as $ft);
+                    }
+                    let mut fft = RealToComplex::<$ft>::new(2 * half_len_in).unwrap();
+                    fft.process(&mut filter_t, &mut filter_f).unwrap();
+                    (1, half_len_in, filter_f)
+                };
+                debug!("FftFilter uses {} partitions, FFT length {}", nbr_partitions, 2 * half_len_in);
+                let half_len_out = half_len_in / fft_size_in * fft_size_out;
 
-                FftResampler {
+                FftFilter {
                     fft_size_in,
                     fft_size_out,
+                    transform_len_in: 2 * half_len_in,
+                    transform_len_out: 2 * half_len_out,
+                    filter_len,
+                    cutoff,
+                    nbr_partitions,
+                    upsampling,
                     filter_f,
-                    fft,
-                    ifft,
-                    input_buf,
-                    input_f,
-                    output_f,
-                    output_buf,
                 }
             }
 
-            /// Resample a small chunk
+            /// Split a filter into partitions of `fft_size_in` frames, and calculate their spectra
+            /// at the rate common to input and output, which is `upsampling` times the input rate.
+            /// The filter is first interpolated to the common rate by extending its spectrum with zeros.
+            fn partition_spectra(
+                filter: &[$ft],
+                fft_size_in: usize,
+                nbr_partitions: usize,
+                upsampling: usize,
+            ) -> Vec<Complex<$ft>> {
+                let period = 2 * nbr_partitions * fft_size_in;
+                let mut filter_t: Vec<$ft> = vec![0.0; period];
+                let mut filter_f: Vec<Complex<$ft>> = vec![Complex::zero(); period / 2 + 1];
+                filter_t[..filter.len()].copy_from_slice(filter);
+                let mut fft = RealToComplex::<$ft>::new(period).unwrap();
+                fft.process(&mut filter_t, &mut filter_f).unwrap();
+
+                let mut upsampled_f: Vec<Complex<$ft>> =
+                    vec![Complex::zero(); period * upsampling / 2 + 1];
+                upsampled_f[..period / 2].copy_from_slice(&filter_f[..period / 2]);
+                let mut upsampled: Vec<$ft> = vec![0.0; period * upsampling];
+                let mut ifft = ComplexToReal::<$ft>::new(period * upsampling).unwrap();
+                ifft.process(&upsampled_f, &mut upsampled).unwrap();
+
+                // Scale for the unnormalized transforms, and for a single partition at the input rate.
+                let partition_len = fft_size_in * upsampling;
+                let scale = (period * partition_len / 2) as $ft;
+                let mut partition_t: Vec<$ft> = vec![0.0; 2 * partition_len];
+                let mut partition_f: Vec<Complex<$ft>> = vec![Complex::zero(); partition_len + 1];
+                let mut spectra = vec![Complex::zero(); (partition_len + 1) * nbr_partitions];
+                let mut fft = RealToComplex::<$ft>::new(2 * partition_len).unwrap();
+                for (p, partition) in upsampled.chunks(partition_len).take(nbr_partitions).enumerate() {
+                    partition_t
+                        .iter_mut()
+                        .zip(partition.iter())
+                        .for_each(|(dest, val)| *dest = val / scale);
+                    partition_t[partition_len..].iter_mut().for_each(|val| *val = 0.0);
+                    fft.process(&mut partition_t, &mut partition_f).unwrap();
+                    for (q, val) in partition_f.iter().enumerate() {
+                        spectra[q * nbr_partitions + p] = *val;
+                    }
+                }
+                spectra
+            }
+
+            /// Create the FFT plans and work buffers needed for resampling a chunk.
+            fn make_scratch(&self) -> FftScratch<$ft> {
+                FftScratch {
+                    fft: RealToComplex::<$ft>::new(self.transform_len_in).unwrap(),
+                    ifft: ComplexToReal::<$ft>::new(self.transform_len_out).unwrap(),
+                    input_buf: vec![0.0; self.transform_len_in],
+                    input_f: vec![Complex::zero(); self.transform_len_in / 2 + 1],
+                    output_f: vec![Complex::zero(); self.transform_len_out / 2 + 1],
+                    output_buf: vec![0.0; self.transform_len_out],
+                }
+            }
+
+            /// Resample a small chunk.
+            /// The input may be shorter than the FFT size, and is then padded with zeros.
             fn resample_unit(
-                &mut self,
+                &self,
+                scratch: &mut FftScratch<$ft>,
                 wave_in: &[$ft],
                 wave_out: &mut [$ft],
-                overlap: &mut [$ft],
+                history: &mut FftHistory<$ft>,
             ) {
-                // Copy to input buffer and clear padding area
-                self.input_buf[0..self.fft_size_in].copy_from_slice(wave_in);
-                for item in self
-                    .input_buf
-                    .iter_mut()
-                    .skip(self.fft_size_in)
-                    .take(self.fft_size_in)
-                {
-                    *item = 0.0;
-                }
-                //for (n, item) in wave_in.iter().enumerate().take(self.fft_size_in) {
-                //    self.input_buf[n] = *item;
-                //    self.input_buf[n+self.fft_size_in] = 0.0;
-                //}
-
-                // FFT and store result in history, update index
-                self.fft
-                    .process(&mut self.input_buf, &mut self.input_f)
+                // Copy to input buffer and clear padding area.
+                // A short input is padded with zeros to the full size.
+                copy_and_pad(wave_in, &mut scratch.input_buf);
+
+                scratch.fft
+                    .process(&mut scratch.input_buf, &mut scratch.input_f)
                     .unwrap();
 
-                // multiply with filter FT
-                self.input_f
-                    .iter_mut()
-                    .take(self.fft_size_in + 1)
-                    .zip(self.filter_f.iter())
-                    .for_each(|(spec, filt)| *spec *= filt);
-                let new_len = if self.fft_size_in < self.fft_size_out {
-                    self.fft_size_in
-                } else {
-                    self.fft_size_out
-                };
+                if self.nbr_partitions == 1 {
+                    // multiply with filter FT
+                    scratch.input_f
+                        .iter_mut()
+                        .zip(self.filter_f.iter())
+                        .for_each(|(spec, filt)| *spec *= filt);
+                    let (half_len_in, half_len_out) =
+                        (self.transform_len_in / 2, self.transform_len_out / 2);
+                    let new_len = half_len_in.min(half_len_out);
 
-                // copy to modified spectrum
-                self.output_f[0..new_len].copy_from_slice(&self.input_f[0..new_len]);
-                self.output_f[self.fft_size_out] = self.input_f[self.fft_size_in];
+                    // copy to modified spectrum
+                    scratch.output_f[0..new_len].copy_from_slice(&scratch.input_f[0..new_len]);
+                    scratch.output_f[half_len_out] = scratch.input_f[half_len_in];
+                } else {
+                    self.multiply_partitions(&scratch.input_f, &mut scratch.output_f, history);
+                }
 
                 // IFFT result, store result and overlap
-                self.ifft
-                    .process(&self.output_f, &mut self.output_buf)
+                scratch.ifft
+                    .process(&scratch.output_f, &mut scratch.output_buf)
                     .unwrap();
+                let overlap = &mut history.overlap;
                 for (n, item) in wave_out.iter_mut().enumerate().take(self.fft_size_out) {
-                    *item = self.output_buf[n] + overlap[n];
+                    *item = scratch.output_buf[n] + overlap[n];
+                }
+                // shift out the completed frames, and add the tail of this chunk
+                overlap.copy_within(self.fft_size_out.., 0);
+                let overlap_len = overlap.len();
+                overlap[overlap_len - self.fft_size_out..]
+                    .iter_mut()
+                    .for_each(|val| *val = 0.0);
+                overlap
+                    .iter_mut()
+                    .zip(scratch.output_buf[self.fft_size_out..].iter())
+                    .for_each(|(val, new)| *val += new);
+            }
+
+            /// Store the spectrum of a new input block in the history, and multiply the latest
+            /// blocks with the filter partitions, the newest block with the first partition.
+            ///
+            /// The partitions are cut from the filter at frame boundaries and are not band limited,
+            /// so the spectrum can't simply be truncated or extended like for a single partition.
+            /// The products are instead evaluated at the common rate, where the spectrum
+            /// of the input repeats, and folded down to the output rate.
+            fn multiply_partitions(
+                &self,
+                input_f: &[Complex<$ft>],
+                output_f: &mut [Complex<$ft>],
+                history: &mut FftHistory<$ft>,
+            ) {
+                let nbr_partitions = self.nbr_partitions;
+                // the new block replaces the oldest one
+                let newest = (history.newest + 1) % nbr_partitions;
+                history.newest = newest;
+                for (s, val) in input_f.iter().enumerate() {
+                    history.spectra[s * nbr_partitions + newest] = *val;
+                }
+                let len_in = 2 * self.fft_size_in;
+                let len_out = 2 * self.fft_size_out;
+                let common_len = len_in * self.upsampling;
+                for (j, out) in output_f.iter_mut().enumerate() {
+                    *out = Complex::zero();
+                    for q in (j..common_len).step_by(len_out) {
+                        // the spectra of real signals are symmetric, only the lower halves are stored
+                        let (q, conj) = if q > common_len / 2 {
+                            (common_len - q, true)
+                        } else {
+                            (q, false)
+                        };
+                        let s = q % len_in;
+                        let (s, conj_in) = if s > self.fft_size_in {
+                            (len_in - s, true)
+                        } else {
+                            (s, false)
+                        };
+                        let blocks = &history.spectra[s * nbr_partitions..(s + 1) * nbr_partitions];
+                        let partitions = &self.filter_f[q * nbr_partitions..(q + 1) * nbr_partitions];
+                        let mut sum = Complex::zero();
+                        for (block, partition) in blocks[..=newest]
+                            .iter()
+                            .rev()
+                            .chain(blocks[newest + 1..].iter().rev())
+                            .zip(partitions.iter())
+                        {
+                            let block = if conj_in { block.conj() } else { *block };
+                            sum += block * partition;
+                        }
+                        *out += if conj { sum.conj() } else { sum };
+                    }
                 }
-                overlap.copy_from_slice(&self.output_buf[self.fft_size_out..]);
             }
         }
     };
 }
-impl_resampler!(f32, FftResampler<f32>);
-impl_resampler!(f64, FftResampler<f64>);
+impl_resampler!(f32, FftFilter<f32>);
+impl_resampler!(f64, FftFilter<f64>);
+
+/// Estimate if a filter of `nbr_partitions` partitions is cheaper to apply by splitting it,
+/// than by fitting it in FFTs of length `2 * half_len_in`.
+/// Each partition costs a complex multiplication for each frequency at the common rate,
+/// while an FFT of length `n` costs roughly `n * log2(n)` operations.
+fn partitions_are_cheaper(
+    fft_size_in: usize,
+    fft_size_out: usize,
+    nbr_partitions: usize,
+    upsampling: usize,
+    half_len_in: usize,
+) -> bool {
+    let fft_cost = |len: usize| 2.5 * len as f64 * (len as f64).log2();
+    let half_len_out = half_len_in / fft_size_in * fft_size_out;
+    let partitioned = 8.0 * (fft_size_in * upsampling * nbr_partitions) as f64
+        + fft_cost(2 * fft_size_in)
+        + fft_cost(2 * fft_size_out);
+    let single = 8.0 * half_len_in as f64 + fft_cost(2 * half_len_in) + fft_cost(2 * half_len_out);
+    partitioned < single
+}
+
+/// Helper to validate the parameters for creating a synchronous resampler.
+fn validate_fft_parameters(
+    fs_in: usize,
+    fs_out: usize,
+    chunk_size: usize,
+    sub_chunks: usize,
+    nbr_channels: usize,
+) -> ResampleResult<()> {
+    if fs_in == 0 {
+        return Err(ResampleError::invalid_parameter(
+            "fs_in",
+            "must be larger than zero",
+        ));
+    }
+    if fs_out == 0 {
+        return Err(ResampleError::invalid_parameter(
+            "fs_out",
+            "must be larger than zero",
+        ));
+    }
+    if chunk_size == 0 {
+        return Err(ResampleError::invalid_parameter(
+            "chunk_size",
+            "must be larger than zero",
+        ));
+    }
+    if sub_chunks == 0 || sub_chunks > chunk_size {
+        return Err(ResampleError::invalid_parameter(
+            "sub_chunks",
+            "must be larger than zero and at most equal to the chunk size",
+        ));
+    }
+    if nbr_channels == 0 {
+        return Err(ResampleError::invalid_parameter(
+            "nbr_channels",
+            "must be larger than zero",
+        ));
+    }
+    Ok(())
+}
+
+/// Calculate the FFT sizes for an FftFixedInOut,
+/// with the input size rounded up to a multiple of `fs_in / gcd(fs_in, fs_out)`.
+fn fixed_inout_fft_sizes(fs_in: usize, fs_out: usize, chunk_size_in: usize) -> (usize, usize) {
+    let gcd = integer::gcd(fs_in, fs_out);
+    let min_chunk_out = fs_out / gcd;
+    let fft_chunks = (chunk_size_in as f32 / min_chunk_out as f32).ceil() as usize;
+    (fft_chunks * fs_in / gcd, fft_chunks * fs_out / gcd)
+}
+
+/// Calculate the FFT sizes for an FftFixedOut, for the wanted size of each sub chunk.
+fn fixed_out_fft_sizes(
+    fs_in: usize,
+    fs_out: usize,
+    chunk_size_out: usize,
+    sub_chunks: usize,
+) -> (usize, usize) {
+    let gcd = integer::gcd(fs_in, fs_out);
+    let min_chunk_out = fs_out / gcd;
+    let wanted_subsize = chunk_size_out / sub_chunks;
+    let fft_chunks = (wanted_subsize as f32 / min_chunk_out as f32).ceil() as usize;
+    (fft_chunks * fs_in / gcd, fft_chunks * fs_out / gcd)
+}
+
+/// Calculate the FFT sizes for an FftFixedIn, for the wanted size of each sub chunk.
+fn fixed_in_fft_sizes(
+    fs_in: usize,
+    fs_out: usize,
+    chunk_size_in: usize,
+    sub_chunks: usize,
+) -> (usize, usize) {
+    let gcd = integer::gcd(fs_in, fs_out);
+    let min_chunk_in = fs_in / gcd;
+    let wanted_subsize = chunk_size_in / sub_chunks;
+    let fft_chunks = (wanted_subsize as f32 / min_chunk_in as f32).ceil() as usize;
+    (fft_chunks * fs_in / gcd, fft_chunks * fs_out / gcd)
+}
+
+/// Calculate the FFT sizes for the FFT stage of a resampler in two-stage mode,
+/// that upsamples `frames_in` input frames by two in each FFT.
+fn two_stage_fft_sizes(frames_in: usize) -> (usize, usize) {
+    let fft_size_in = frames_in.max(1);
+    (fft_size_in, 2 * fft_size_in)
+}
+
+/// Calculate the number of input frames a resampler in two-stage mode needs,
+/// for the FFT stage to give the polyphase stage enough frames for `nbr_frames` output frames.
+fn two_stage_frames_needed<T: Float + DotProduct>(
+    stage: &PolyphaseStage<T>,
+    nbr_frames: usize,
+    fft_size_in: usize,
+    fft_size_out: usize,
+) -> usize {
+    let missing = stage
+        .frames_needed(nbr_frames)
+        .saturating_sub(stage.buffered());
+    (missing + fft_size_out - 1) / fft_size_out * fft_size_in
+}
+
+/// Helper to validate the parameters for the antialiasing filter.
+fn validate_filter_parameters(parameters: &FftFilterParameters) -> ResampleResult<()> {
+    if let Some(f_cutoff) = parameters.f_cutoff {
+        if !(f_cutoff > 0.0 && f_cutoff <= 1.0) {
+            return Err(ResampleError::invalid_parameter(
+                "f_cutoff",
+                "must be larger than zero and at most 1.0",
+            ));
+        }
+    }
+    if parameters.filter_len == Some(0) {
+        return Err(ResampleError::invalid_parameter(
+            "filter_len",
+            "must be larger than zero",
+        ));
+    }
+    validate_window(parameters.window)?;
+    Ok(())
+}
+
+/// Helper to check that a shared filter was made for the FFT sizes that a resampler uses.
+fn validate_filter<T>(
+    filter: &FftFilter<T>,
+    fft_size_in: usize,
+    fft_size_out: usize,
+) -> ResampleResult<()> {
+    if filter.fft_size_in != fft_size_in || filter.fft_size_out != fft_size_out {
+        return Err(ResampleError::invalid_parameter(
+            "filter",
+            "must be made for the same FFT sizes",
+        ));
+    }
+    Ok(())
+}
 
 macro_rules! impl_fixedinout {
     ($ft:ty) => {
@@ -187,36 +724,139 @@ macro_rules! impl_fixedinout {
             /// - `fs_out`: Output sample rate.
             /// - `chunk_size_in`: desired length of input data in frames, actual value may be different.
             /// - `nbr_channels`: number of channels in input/output.
+            ///
+            /// # Panics
+            ///
+            /// Panics if any of the parameters is invalid, see `try_new`.
             pub fn new(
                 fs_in: usize,
                 fs_out: usize,
                 chunk_size_in: usize,
                 nbr_channels: usize,
             ) -> Self {
+                Self::try_new(fs_in, fs_out, chunk_size_in, nbr_channels).unwrap()
+            }
+
+            /// Create a new FftFixedInOut, checking that the parameters are valid.
+            /// Takes the same parameters as `new`.
+            ///
+            /// # Errors
+            ///
+            /// Returns an `InvalidParameter` error if any of the sample rates,
+            /// the chunk size or the number of channels is zero.
+            pub fn try_new(
+                fs_in: usize,
+                fs_out: usize,
+                chunk_size_in: usize,
+                nbr_channels: usize,
+            ) -> ResampleResult<Self> {
+                Self::try_new_with_parameters(
+                    fs_in,
+                    fs_out,
+                    chunk_size_in,
+                    nbr_channels,
+                    FftFilterParameters::default(),
+                )
+            }
+
+            /// Create a new FftFixedInOut, with an antialiasing filter made from the given parameters.
+            /// Takes the same parameters as `new`, and the filter parameters, see `FftFilterParameters`.
+            ///
+            /// # Panics
+            ///
+            /// Panics if any of the parameters is invalid, see `try_new_with_parameters`.
+            pub fn new_with_parameters(
+                fs_in: usize,
+                fs_out: usize,
+                chunk_size_in: usize,
+                nbr_channels: usize,
+                parameters: FftFilterParameters,
+            ) -> Self {
+                Self::try_new_with_parameters(fs_in, fs_out, chunk_size_in, nbr_channels, parameters)
+                    .unwrap()
+            }
+
+            /// Create a new FftFixedInOut with the given filter parameters, checking that the parameters are valid.
+            /// Takes the same parameters as `new_with_parameters`.
+            ///
+            /// # Errors
+            ///
+            /// Returns an `InvalidParameter` error if the filter parameters are invalid,
+            /// see `FftFilterParameters`, or for the same reasons as `try_new`.
+            pub fn try_new_with_parameters(
+                fs_in: usize,
+                fs_out: usize,
+                chunk_size_in: usize,
+                nbr_channels: usize,
+                parameters: FftFilterParameters,
+            ) -> ResampleResult<Self> {
+                validate_fft_parameters(fs_in, fs_out, chunk_size_in, 1, nbr_channels)?;
+                let (fft_size_in, fft_size_out) = fixed_inout_fft_sizes(fs_in, fs_out, chunk_size_in);
+                validate_filter_parameters(&parameters)?;
+                let filter = Arc::new(FftFilter::<$ft>::new(fft_size_in, fft_size_out, &parameters));
+                Self::try_new_with_filter(fs_in, fs_out, chunk_size_in, nbr_channels, filter)
+            }
+
+            /// Create a new FftFixedInOut, using a filter shared with other resamplers.
+            /// Takes the same parameters as `new`, and the filter from another resampler,
+            /// that must have been created for the same FFT sizes, see `FftFilter`.
+            ///
+            /// # Panics
+            ///
+            /// Panics if any of the parameters is invalid, see `try_new_with_filter`.
+            pub fn new_with_filter(
+                fs_in: usize,
+                fs_out: usize,
+                chunk_size_in: usize,
+                nbr_channels: usize,
+                filter: Arc<FftFilter<$ft>>,
+            ) -> Self {
+                Self::try_new_with_filter(fs_in, fs_out, chunk_size_in, nbr_channels, filter).unwrap()
+            }
+
+            /// Create a new FftFixedInOut using a shared filter, checking that the parameters are valid.
+            /// Takes the same parameters as `new_with_filter`.
+            ///
+            /// # Errors
+            ///
+            /// Returns an `InvalidParameter` error if the filter was made for different FFT sizes,
+            /// or for the same reasons as `try_new`.
+            pub fn try_new_with_filter(
+                fs_in: usize,
+                fs_out: usize,
+                chunk_size_in: usize,
+                nbr_channels: usize,
+                filter: Arc<FftFilter<$ft>>,
+            ) -> ResampleResult<Self> {
+                validate_fft_parameters(fs_in, fs_out, chunk_size_in, 1, nbr_channels)?;
+                let (fft_size_in, fft_size_out) = fixed_inout_fft_sizes(fs_in, fs_out, chunk_size_in);
+                validate_filter(&filter, fft_size_in, fft_size_out)?;
                 debug!(
                     "Create new FftFixedInOut, fs_in: {}, fs_out: {} chunk_size_in: {}, channels: {}",
                     fs_in, fs_out, chunk_size_in, nbr_channels
                 );
 
-                let gcd = integer::gcd(fs_in, fs_out);
-                let min_chunk_out = fs_out/gcd;
-                let wanted = chunk_size_in;
-                let fft_chunks = (wanted as f32 / min_chunk_out as f32).ceil() as usize;
-                let fft_size_out = fft_chunks * fs_out / gcd;
-                let fft_size_in = fft_chunks * fs_in / gcd;
-
-                let resampler = FftResampler::<$ft>::new(fft_size_in, fft_size_out);
+                let channels = (0..nbr_channels)
+                    .map(|_| FftChannel::new(&filter, fft_size_in, fft_size_out, 0))
+                    .collect();
+                let scratch = (0..nbr_scratch_buffers(nbr_channels))
+                    .map(|_| filter.make_scratch())
+                    .collect();
 
-                let overlaps: Vec<Vec<$ft>> = vec![vec![0.0; fft_size_out]; nbr_channels];
-
-                FftFixedInOut {
+                Ok(FftFixedInOut {
                     nbr_channels,
                     chunk_size_in: fft_size_in,
                     chunk_size_out: fft_size_out,
                     fft_size_in,
-                    overlaps,
-                    resampler,
-                }
+                    channels,
+                    filter,
+                    scratch,
+                })
+            }
+
+            /// Get the antialiasing filter, for sharing with other resamplers.
+            pub fn filter(&self) -> Arc<FftFilter<$ft>> {
+                self.filter.clone()
             }
         }
     }
@@ -233,50 +873,135 @@ macro_rules! resampler_FftFixedinout {
             }
 
             /// Update the resample ratio. This is not supported by this resampler and always returns an error.
-            fn set_resample_ratio(&mut self, _new_ratio: f64) -> Res<()> {
-                Err(Box::new(ResamplerError::new(
-                    "Not possible to adjust a synchronous resampler)",
-                )))
+            fn set_resample_ratio(&mut self, _new_ratio: f64) -> ResampleResult<()> {
+                Err(ResampleError::SyncNotAdjustable)
             }
 
             /// Update the resample ratio relative to the original one.
             /// This is not supported by this resampler and always returns an error.
-            fn set_resample_ratio_relative(&mut self, _rel_ratio: f64) -> Res<()> {
-                Err(Box::new(ResamplerError::new(
-                    "Not possible to adjust a synchronous resampler)",
-                )))
+            fn set_resample_ratio_relative(&mut self, _rel_ratio: f64) -> ResampleResult<()> {
+                Err(ResampleError::SyncNotAdjustable)
+            }
+
+            /// Query for the maximum number of frames that a call to "process" can require.
+            fn input_frames_max(&self) -> usize {
+                self.chunk_size_in
+            }
+
+            /// Query for the maximum number of frames that a call to "process" can return.
+            fn output_frames_max(&self) -> usize {
+                self.chunk_size_out
+            }
+
+            /// Query for the number of channels this resampler was created for.
+            fn nbr_channels(&self) -> usize {
+                self.nbr_channels
+            }
+
+            /// Get the resample ratio, `fs_out / fs_in`.
+            fn resample_ratio(&self) -> f64 {
+                self.chunk_size_out as f64 / self.chunk_size_in as f64
+            }
+
+            /// Get the delay of the resampler, in output frames.
+            /// The antialiasing filter delays the signal by half its length.
+            fn output_delay(&self) -> f64 {
+                (self.filter.filter_len / 2) as f64 * self.filter.fft_size_out as f64
+                    / self.fft_size_in as f64
+            }
+
+            /// Reset the resampler to the state it had when it was created.
+            fn reset(&mut self) {
+                self.channels.iter_mut().for_each(|channel| channel.reset());
             }
 
             /// Resample a chunk of audio. The input and output lengths are fixed.
             /// # Errors
             ///
             /// The function returns an error if the size of the input data is not equal
-            /// to the number of channels and input size defined when creating the instance.
-            fn process(&mut self, wave_in: &[Vec<$t>]) -> Res<Vec<Vec<$t>>> {
-                if wave_in.len() != self.nbr_channels {
-                    return Err(Box::new(ResamplerError::new(
-                        "Wrong number of channels in input",
-                    )));
-                }
-                if wave_in[0].len() != self.chunk_size_in {
-                    return Err(Box::new(ResamplerError::new(
-                        format!(
-                            "Wrong number of frames in input, expected {}, got {}",
-                            self.chunk_size_in,
-                            wave_in[0].len()
-                        )
-                        .as_str(),
-                    )));
+            /// to the number of channels and input size defined when creating the instance,
+            /// or if the output buffers are shorter than the output size.
+            fn process_into_buffer<Vin: AsRef<[$t]>, Vout: AsMut<[$t]>>(
+                &mut self,
+                wave_in: &[Vin],
+                wave_out: &mut [Vout],
+                active_channels_mask: Option<&[bool]>,
+            ) -> ResampleResult<usize> {
+                validate_buffers(
+                    Some(wave_in),
+                    wave_out,
+                    active_channels_mask,
+                    self.nbr_channels,
+                    self.chunk_size_in,
+                    self.chunk_size_out,
+                    false,
+                )?;
+                self.resample_chunk(Some(wave_in), wave_out, active_channels_mask);
+                Ok(self.chunk_size_out)
+            }
+
+            /// Resample a chunk of audio that may be shorter than the input size.
+            /// The input is padded with zeros to the input size.
+            /// # Errors
+            ///
+            /// The function returns an error if the input data does not have the number of channels
+            /// defined when creating the instance, if it is longer than the input size,
+            /// or if the output buffers are shorter than the output size.
+            fn process_partial_into_buffer<Vin: AsRef<[$t]>, Vout: AsMut<[$t]>>(
+                &mut self,
+                wave_in: Option<&[Vin]>,
+                wave_out: &mut [Vout],
+                active_channels_mask: Option<&[bool]>,
+            ) -> ResampleResult<usize> {
+                validate_buffers(
+                    wave_in,
+                    wave_out,
+                    active_channels_mask,
+                    self.nbr_channels,
+                    self.chunk_size_in,
+                    self.chunk_size_out,
+                    true,
+                )?;
+                self.resample_chunk(wave_in, wave_out, active_channels_mask);
+                Ok(self.chunk_size_out)
+            }
+        }
+
+        impl FftFixedInOut<$t> {
+            /// Resample a chunk of audio from input buffers that have already been validated.
+            /// With the `rayon` feature, the channels are processed in parallel.
+            fn resample_chunk<Vin: AsRef<[$t]>, Vout: AsMut<[$t]>>(
+                &mut self,
+                wave_in: Option<&[Vin]>,
+                wave_out: &mut [Vout],
+                active_channels_mask: Option<&[bool]>,
+            ) {
+                for (n, channel) in self.channels.iter_mut().enumerate() {
+                    if channel_is_active(active_channels_mask, n) {
+                        copy_and_pad(input_channel(wave_in, n), &mut channel.input);
+                    }
                 }
-                let mut wave_out = vec![vec![0.0 as $t; self.chunk_size_out]; self.nbr_channels];
-                for n in 0..self.nbr_channels {
-                    self.resampler.resample_unit(
-                        &wave_in[n],
-                        &mut wave_out[n],
-                        &mut self.overlaps[n],
-                    )
+                let filter = &*self.filter;
+                for_each_channel_with_scratch(
+                    &mut self.channels,
+                    &mut self.scratch,
+                    |n, channel, scratch| {
+                        if channel_is_active(active_channels_mask, n) {
+                            filter.resample_unit(
+                                scratch,
+                                &channel.input,
+                                &mut channel.output,
+                                &mut channel.history,
+                            );
+                        }
+                    },
+                );
+                for (n, wav) in wave_out.iter_mut().enumerate() {
+                    if channel_is_active(active_channels_mask, n) {
+                        wav.as_mut()[..self.chunk_size_out]
+                            .copy_from_slice(&self.channels[n].output);
+                    }
                 }
-                Ok(wave_out)
             }
         }
     };
@@ -295,6 +1020,10 @@ macro_rules! impl_fixedout {
             /// - `chunk_size_out`: length of output data in frames.
             /// - `sub_chunks`: desired number of subchunks for processing, actual number may be different.
             /// - `nbr_channels`: number of channels in input/output.
+            ///
+            /// # Panics
+            ///
+            /// Panics if any of the parameters is invalid, see `try_new`.
             pub fn new(
                 fs_in: usize,
                 fs_out: usize,
@@ -302,41 +1031,255 @@ macro_rules! impl_fixedout {
                 sub_chunks: usize,
                 nbr_channels: usize,
             ) -> Self {
+                Self::try_new(fs_in, fs_out, chunk_size_out, sub_chunks, nbr_channels).unwrap()
+            }
 
+            /// Create a new FftFixedOut, checking that the parameters are valid.
+            /// Takes the same parameters as `new`.
+            ///
+            /// # Errors
+            ///
+            /// Returns an `InvalidParameter` error if any of the sample rates,
+            /// the chunk size or the number of channels is zero,
+            /// or if the number of sub chunks is zero or larger than the chunk size.
+            pub fn try_new(
+                fs_in: usize,
+                fs_out: usize,
+                chunk_size_out: usize,
+                sub_chunks: usize,
+                nbr_channels: usize,
+            ) -> ResampleResult<Self> {
+                Self::try_new_with_parameters(
+                    fs_in,
+                    fs_out,
+                    chunk_size_out,
+                    sub_chunks,
+                    nbr_channels,
+                    FftFilterParameters::default(),
+                )
+            }
 
-                let gcd = integer::gcd(fs_in, fs_out);
-                let min_chunk_out = fs_out/gcd;
-                let wanted_subsize = chunk_size_out/sub_chunks;
-                let fft_chunks = (wanted_subsize as f32 / min_chunk_out as f32).ceil() as usize;
-                let fft_size_out = fft_chunks * fs_out / gcd;
-                let fft_size_in = fft_chunks * fs_in / gcd;
+            /// Create a new FftFixedOut, with an antialiasing filter made from the given parameters.
+            /// Takes the same parameters as `new`, and the filter parameters, see `FftFilterParameters`.
+            ///
+            /// # Panics
+            ///
+            /// Panics if any of the parameters is invalid, see `try_new_with_parameters`.
+            pub fn new_with_parameters(
+                fs_in: usize,
+                fs_out: usize,
+                chunk_size_out: usize,
+                sub_chunks: usize,
+                nbr_channels: usize,
+                parameters: FftFilterParameters,
+            ) -> Self {
+                Self::try_new_with_parameters(fs_in, fs_out, chunk_size_out, sub_chunks, nbr_channels, parameters)
+                    .unwrap()
+            }
 
-                let resampler = FftResampler::<$ft>::new(fft_size_in, fft_size_out);
+            /// Create a new FftFixedOut with the given filter parameters, checking that the parameters are valid.
+            /// Takes the same parameters as `new_with_parameters`.
+            ///
+            /// # Errors
+            ///
+            /// Returns an `InvalidParameter` error if the filter parameters are invalid,
+            /// see `FftFilterParameters`, or for the same reasons as `try_new`.
+            pub fn try_new_with_parameters(
+                fs_in: usize,
+                fs_out: usize,
+                chunk_size_out: usize,
+                sub_chunks: usize,
+                nbr_channels: usize,
+                parameters: FftFilterParameters,
+            ) -> ResampleResult<Self> {
+                validate_fft_parameters(fs_in, fs_out, chunk_size_out, sub_chunks, nbr_channels)?;
+                let (fft_size_in, fft_size_out) =
+                    fixed_out_fft_sizes(fs_in, fs_out, chunk_size_out, sub_chunks);
+                validate_filter_parameters(&parameters)?;
+                let filter = Arc::new(FftFilter::<$ft>::new(fft_size_in, fft_size_out, &parameters));
+                Self::try_new_with_filter(fs_in, fs_out, chunk_size_out, sub_chunks, nbr_channels, filter)
+            }
+
+            /// Create a new FftFixedOut, using a filter shared with other resamplers.
+            /// Takes the same parameters as `new`, and the filter from another resampler,
+            /// that must have been created for the same FFT sizes, see `FftFilter`.
+            ///
+            /// # Panics
+            ///
+            /// Panics if any of the parameters is invalid, see `try_new_with_filter`.
+            pub fn new_with_filter(
+                fs_in: usize,
+                fs_out: usize,
+                chunk_size_out: usize,
+                sub_chunks: usize,
+                nbr_channels: usize,
+                filter: Arc<FftFilter<$ft>>,
+            ) -> Self {
+                Self::try_new_with_filter(fs_in, fs_out, chunk_size_out, sub_chunks, nbr_channels, filter)
+                    .unwrap()
+            }
 
+            /// Create a new FftFixedOut using a shared filter, checking that the parameters are valid.
+            /// Takes the same parameters as `new_with_filter`.
+            ///
+            /// # Errors
+            ///
+            /// Returns an `InvalidParameter` error if the filter was made for different FFT sizes,
+            /// or for the same reasons as `try_new`.
+            pub fn try_new_with_filter(
+                fs_in: usize,
+                fs_out: usize,
+                chunk_size_out: usize,
+                sub_chunks: usize,
+                nbr_channels: usize,
+                filter: Arc<FftFilter<$ft>>,
+            ) -> ResampleResult<Self> {
+                validate_fft_parameters(fs_in, fs_out, chunk_size_out, sub_chunks, nbr_channels)?;
+                let (fft_size_in, fft_size_out) =
+                    fixed_out_fft_sizes(fs_in, fs_out, chunk_size_out, sub_chunks);
+                validate_filter(&filter, fft_size_in, fft_size_out)?;
                 debug!(
-                    "Create new FftFixedOut, fs_in: {}, fs_out: {} chunk_size_in: {}, channels: {}, fft_size_in: {}, fft_size_out: {}",
+                    "Create new FftFixedOut, fs_in: {}, fs_out: {} chunk_size_out: {}, channels: {}, fft_size_in: {}, fft_size_out: {}",
                     fs_in, fs_out, chunk_size_out, nbr_channels, fft_size_in, fft_size_out
                 );
 
-                let overlaps: Vec<Vec<$ft>> = vec![vec![0.0; fft_size_out]; nbr_channels];
-                let output_buffers: Vec<Vec<$ft>> = vec![vec![0.0; chunk_size_out+fft_size_out]; nbr_channels];
+                Ok(Self::from_parts(chunk_size_out, nbr_channels, fft_size_in, fft_size_out, filter, None))
+            }
+
+            /// Create a new FftFixedOut in two-stage mode, for rate pairs where the FFTs
+            /// of the normal mode would be very long.
+            ///
+            /// This works like the two-stage mode of `FftFixedIn`, see `FftFixedIn::new_two_stage`.
+            /// Each FFT takes about `chunk_size_out * fs_in / fs_out / sub_chunks` input frames,
+            /// and upsamples them by two before the polyphase filter resamples them to the output rate.
+            /// The number of frames needed for each chunk is a multiple of the FFT size,
+            /// that varies from chunk to chunk. For chunks of 1024 frames from 44100 to 44101 Hz
+            /// and two sub chunks, the FFT size is 512 and most chunks need 1024 frames,
+            /// while a few need 512 or 1536 frames.
+            ///
+            /// Parameters are:
+            /// - `fs_in`: Input sample rate.
+            /// - `fs_out`: Output sample rate.
+            /// - `chunk_size_out`: length of output data in frames.
+            /// - `sub_chunks`: desired number of subchunks for processing.
+            /// - `nbr_channels`: number of channels in input/output.
+            /// - `parameters`: parameters for the antialiasing filter of the FFT stage, see `FftFilterParameters`.
+            ///
+            /// # Panics
+            ///
+            /// Panics if any of the parameters is invalid, see `try_new_two_stage`.
+            pub fn new_two_stage(
+                fs_in: usize,
+                fs_out: usize,
+                chunk_size_out: usize,
+                sub_chunks: usize,
+                nbr_channels: usize,
+                parameters: FftFilterParameters,
+            ) -> Self {
+                Self::try_new_two_stage(fs_in, fs_out, chunk_size_out, sub_chunks, nbr_channels, parameters)
+                    .unwrap()
+            }
+
+            /// Create a new FftFixedOut in two-stage mode, checking that the parameters are valid.
+            /// Takes the same parameters as `new_two_stage`.
+            ///
+            /// # Errors
+            ///
+            /// Returns an `InvalidParameter` error for the same reasons as `try_new_with_parameters`.
+            pub fn try_new_two_stage(
+                fs_in: usize,
+                fs_out: usize,
+                chunk_size_out: usize,
+                sub_chunks: usize,
+                nbr_channels: usize,
+                parameters: FftFilterParameters,
+            ) -> ResampleResult<Self> {
+                validate_fft_parameters(fs_in, fs_out, chunk_size_out, sub_chunks, nbr_channels)?;
+                validate_filter_parameters(&parameters)?;
+                let frames_in = chunk_size_out as f64 * fs_in as f64 / fs_out as f64;
+                let (fft_size_in, fft_size_out) =
+                    two_stage_fft_sizes((frames_in / sub_chunks as f64).ceil() as usize);
+                debug!(
+                    "Create new FftFixedOut in two-stage mode, fs_in: {}, fs_out: {} chunk_size_out: {}, channels: {}, fft_size_in: {}",
+                    fs_in, fs_out, chunk_size_out, nbr_channels, fft_size_in
+                );
+                let filter = Arc::new(FftFilter::<$ft>::new_for_rates(
+                    fft_size_in,
+                    fft_size_out,
+                    fs_in,
+                    fs_out,
+                    &parameters,
+                ));
+                let polyphase = PolyphaseStage::new(2 * fs_in, fs_out);
+                Ok(Self::from_parts(
+                    chunk_size_out,
+                    nbr_channels,
+                    fft_size_in,
+                    fft_size_out,
+                    filter,
+                    Some(polyphase),
+                ))
+            }
+
+            /// Create the buffers and assemble a new FftFixedOut from validated parts.
+            fn from_parts(
+                chunk_size_out: usize,
+                nbr_channels: usize,
+                fft_size_in: usize,
+                fft_size_out: usize,
+                filter: Arc<FftFilter<$ft>>,
+                polyphase: Option<PolyphaseStage<$ft>>,
+            ) -> Self {
+                let (frames_needed, input_len, output_len, intermediate_len) = match &polyphase {
+                    Some(stage) => {
+                        let chunks_max =
+                            (stage.new_frames_max(chunk_size_out) + fft_size_out - 1) / fft_size_out;
+                        (
+                            two_stage_frames_needed(stage, chunk_size_out, fft_size_in, fft_size_out),
+                            chunks_max * fft_size_in,
+                            chunk_size_out.max(chunks_max * fft_size_out),
+                            // the frames left from the previous chunk may exceed what the stage
+                            // retains when reading whole FFT blocks, by at most one block
+                            PolyphaseStage::<$ft>::buffer_len((chunks_max + 1) * fft_size_out),
+                        )
+                    }
+                    None => {
+                        let frames_needed =
+                            (chunk_size_out + fft_size_out - 1) / fft_size_out * fft_size_in;
+                        (frames_needed, frames_needed, chunk_size_out + fft_size_out, 0)
+                    }
+                };
+
+                let channels = (0..nbr_channels)
+                    .map(|_| {
+                        FftChannel::new(&filter, input_len, output_len, intermediate_len)
+                    })
+                    .collect();
+                let scratch = (0..nbr_scratch_buffers(nbr_channels))
+                    .map(|_| filter.make_scratch())
+                    .collect();
 
                 let saved_frames = 0;
-                let chunks_needed = (chunk_size_out as f32 / fft_size_out as f32).ceil() as usize;
-                let frames_needed = chunks_needed*fft_size_in;
 
                 FftFixedOut {
                     nbr_channels,
                     chunk_size_out,
                     fft_size_in,
                     fft_size_out,
-                    overlaps,
-                    output_buffers,
+                    channels,
                     saved_frames,
                     frames_needed,
-                    resampler,
+                    filter,
+                    scratch,
+                    polyphase,
                 }
             }
+
+            /// Get the antialiasing filter, for sharing with other resamplers.
+            /// In two-stage mode, this is the filter of the FFT stage.
+            pub fn filter(&self) -> Arc<FftFilter<$ft>> {
+                self.filter.clone()
+            }
         }
     }
 }
@@ -352,18 +1295,72 @@ macro_rules! resampler_FftFixedout {
             }
 
             /// Update the resample ratio. This is not supported by this resampler and always returns an error.
-            fn set_resample_ratio(&mut self, _new_ratio: f64) -> Res<()> {
-                Err(Box::new(ResamplerError::new(
-                    "Not possible to adjust a synchronous resampler)",
-                )))
+            fn set_resample_ratio(&mut self, _new_ratio: f64) -> ResampleResult<()> {
+                Err(ResampleError::SyncNotAdjustable)
             }
 
             /// Update the resample ratio relative to the original one.
             /// This is not supported by this resampler and always returns an error.
-            fn set_resample_ratio_relative(&mut self, _rel_ratio: f64) -> Res<()> {
-                Err(Box::new(ResamplerError::new(
-                    "Not possible to adjust a synchronous resampler)",
-                )))
+            fn set_resample_ratio_relative(&mut self, _rel_ratio: f64) -> ResampleResult<()> {
+                Err(ResampleError::SyncNotAdjustable)
+            }
+
+            /// Query for the maximum number of frames that a call to "process" can require.
+            fn input_frames_max(&self) -> usize {
+                let frames_out_max = match &self.polyphase {
+                    Some(stage) => stage.new_frames_max(self.chunk_size_out),
+                    None => self.chunk_size_out,
+                };
+                (frames_out_max + self.fft_size_out - 1) / self.fft_size_out * self.fft_size_in
+            }
+
+            /// Query for the maximum number of frames that a call to "process" can return.
+            fn output_frames_max(&self) -> usize {
+                self.chunk_size_out
+            }
+
+            /// Query for the number of channels this resampler was created for.
+            fn nbr_channels(&self) -> usize {
+                self.nbr_channels
+            }
+
+            /// Get the resample ratio, `fs_out / fs_in`.
+            fn resample_ratio(&self) -> f64 {
+                let ratio = self.fft_size_out as f64 / self.fft_size_in as f64;
+                match &self.polyphase {
+                    Some(stage) => stage.output_frames(ratio),
+                    None => ratio,
+                }
+            }
+
+            /// Get the delay of the resampler, in output frames.
+            /// The antialiasing filter delays the signal by half its length,
+            /// and the polyphase stage of the two-stage mode does not add any delay.
+            fn output_delay(&self) -> f64 {
+                let delay = (self.filter.filter_len / 2) as f64 * self.filter.fft_size_out as f64
+                    / self.fft_size_in as f64;
+                match &self.polyphase {
+                    Some(stage) => stage.output_frames(delay),
+                    None => delay,
+                }
+            }
+
+            /// Reset the resampler to the state it had when it was created.
+            fn reset(&mut self) {
+                self.channels.iter_mut().for_each(|channel| channel.reset());
+                self.saved_frames = 0;
+                self.frames_needed = match &mut self.polyphase {
+                    Some(stage) => {
+                        stage.reset();
+                        two_stage_frames_needed(
+                            stage,
+                            self.chunk_size_out,
+                            self.fft_size_in,
+                            self.fft_size_out,
+                        )
+                    }
+                    None => self.input_frames_max(),
+                };
             }
 
             /// Resample a chunk of audio. The required input length is provided by
@@ -372,51 +1369,140 @@ macro_rules! resampler_FftFixedout {
             ///
             /// The function returns an error if the length of the input data is not
             /// equal to the number of channels defined when creating the instance,
-            /// and the number of audio frames given by "nbr_frames_needed".
-            fn process(&mut self, wave_in: &[Vec<$t>]) -> Res<Vec<Vec<$t>>> {
-                if wave_in.len() != self.nbr_channels {
-                    return Err(Box::new(ResamplerError::new(
-                        "Wrong number of channels in input",
-                    )));
-                }
-                if wave_in[0].len() != self.frames_needed {
-                    return Err(Box::new(ResamplerError::new(
-                        format!(
-                            "Wrong number of frames in input, expected {}, got {}",
-                            self.frames_needed,
-                            wave_in[0].len()
-                        )
-                        .as_str(),
-                    )));
-                }
+            /// and the number of audio frames given by "nbr_frames_needed",
+            /// or if the output buffers are shorter than the output size.
+            fn process_into_buffer<Vin: AsRef<[$t]>, Vout: AsMut<[$t]>>(
+                &mut self,
+                wave_in: &[Vin],
+                wave_out: &mut [Vout],
+                active_channels_mask: Option<&[bool]>,
+            ) -> ResampleResult<usize> {
+                validate_buffers(
+                    Some(wave_in),
+                    wave_out,
+                    active_channels_mask,
+                    self.nbr_channels,
+                    self.frames_needed,
+                    self.chunk_size_out,
+                    false,
+                )?;
+                self.resample_chunk(Some(wave_in), wave_out, active_channels_mask);
+                Ok(self.chunk_size_out)
+            }
 
-                let mut wave_out = self.output_buffers.clone();
-                let mut processed_samples = self.saved_frames * self.nbr_channels;
-                for n in 0..self.nbr_channels {
-                    for (in_chunk, out_chunk) in wave_in[n]
-                        .chunks(self.fft_size_in)
-                        .zip(wave_out[n][self.saved_frames..].chunks_mut(self.fft_size_out))
-                    {
-                        self.resampler
-                            .resample_unit(in_chunk, out_chunk, &mut self.overlaps[n]);
-                        processed_samples += self.fft_size_out;
-                    }
-                }
-                let processed_frames = processed_samples / self.nbr_channels;
+            /// Resample a chunk of audio that may be shorter than given by "nbr_frames_needed".
+            /// The input is padded with zeros to the needed length.
+            /// # Errors
+            ///
+            /// The function returns an error if the input data does not have the number of channels
+            /// defined when creating the instance, if it is longer than given by "nbr_frames_needed",
+            /// or if the output buffers are shorter than the output size.
+            fn process_partial_into_buffer<Vin: AsRef<[$t]>, Vout: AsMut<[$t]>>(
+                &mut self,
+                wave_in: Option<&[Vin]>,
+                wave_out: &mut [Vout],
+                active_channels_mask: Option<&[bool]>,
+            ) -> ResampleResult<usize> {
+                validate_buffers(
+                    wave_in,
+                    wave_out,
+                    active_channels_mask,
+                    self.nbr_channels,
+                    self.frames_needed,
+                    self.chunk_size_out,
+                    true,
+                )?;
+                self.resample_chunk(wave_in, wave_out, active_channels_mask);
+                Ok(self.chunk_size_out)
+            }
+        }
 
-                // save extra frames for next round
-                self.saved_frames = processed_frames - self.chunk_size_out;
-                if processed_frames > self.chunk_size_out {
-                    for n in 0..self.nbr_channels {
-                        self.output_buffers[n][0..self.saved_frames].copy_from_slice(
-                            &wave_out[n]
-                                [self.chunk_size_out..(self.chunk_size_out + self.saved_frames)],
+        impl FftFixedOut<$t> {
+            /// Resample a chunk of audio from input buffers that have already been validated.
+            /// Input channels that are shorter than the needed length are padded with zeros.
+            fn resample_chunk<Vin: AsRef<[$t]>, Vout: AsMut<[$t]>>(
+                &mut self,
+                wave_in: Option<&[Vin]>,
+                wave_out: &mut [Vout],
+                active_channels_mask: Option<&[bool]>,
+            ) {
+                let nbr_chunks = self.frames_needed / self.fft_size_in;
+                let fft_frames_out = nbr_chunks * self.fft_size_out;
+                let processed_frames = self.saved_frames + fft_frames_out;
+                let consumed = match &self.polyphase {
+                    Some(stage) => stage
+                        .frames_consumed(self.chunk_size_out, stage.buffered() + fft_frames_out),
+                    None => 0,
+                };
+                for (n, channel) in self.channels.iter_mut().enumerate() {
+                    if channel_is_active(active_channels_mask, n) {
+                        copy_and_pad(
+                            input_channel(wave_in, n),
+                            &mut channel.input[..self.frames_needed],
                         );
                     }
                 }
-                for n in 0..self.nbr_channels {
-                    wave_out[n].truncate(self.chunk_size_out);
+                let filter = &*self.filter;
+                let polyphase = self.polyphase.as_ref();
+                let chunk_size_out = self.chunk_size_out;
+                let (frames_needed, saved_frames) = (self.frames_needed, self.saved_frames);
+                let (fft_size_in, fft_size_out) = (self.fft_size_in, self.fft_size_out);
+                for_each_channel_with_scratch(
+                    &mut self.channels,
+                    &mut self.scratch,
+                    |n, channel, scratch| {
+                        if !channel_is_active(active_channels_mask, n) {
+                            return;
+                        }
+                        for (in_chunk, out_chunk) in channel.input[..frames_needed]
+                            .chunks(fft_size_in)
+                            .zip(channel.output[saved_frames..].chunks_mut(fft_size_out))
+                        {
+                            filter.resample_unit(
+                                scratch,
+                                in_chunk,
+                                out_chunk,
+                                &mut channel.history,
+                            );
+                        }
+                        if let Some(stage) = polyphase {
+                            channel.resample_polyphase(
+                                stage,
+                                fft_frames_out,
+                                chunk_size_out,
+                                consumed,
+                            );
+                        }
+                    },
+                );
+                if let Some(stage) = &mut self.polyphase {
+                    for (n, wav) in wave_out.iter_mut().enumerate() {
+                        if channel_is_active(active_channels_mask, n) {
+                            wav.as_mut()[..self.chunk_size_out]
+                                .copy_from_slice(&self.channels[n].output[..self.chunk_size_out]);
+                        }
+                    }
+                    let available = stage.buffered() + fft_frames_out;
+                    stage.advance(self.chunk_size_out, consumed, available);
+                    self.frames_needed = two_stage_frames_needed(
+                        stage,
+                        self.chunk_size_out,
+                        self.fft_size_in,
+                        self.fft_size_out,
+                    );
+                    return;
+                }
+                for (n, wav) in wave_out.iter_mut().enumerate() {
+                    if channel_is_active(active_channels_mask, n) {
+                        let output = &mut self.channels[n].output;
+                        wav.as_mut()[..self.chunk_size_out]
+                            .copy_from_slice(&output[..self.chunk_size_out]);
+                        // save extra frames for next round
+                        output.copy_within(self.chunk_size_out..processed_frames, 0);
+                    }
                 }
+                self.saved_frames = processed_frames - self.chunk_size_out;
+
                 //calculate number of needed frames from next round
                 let frames_needed_out = if self.chunk_size_out > self.saved_frames {
                     self.chunk_size_out - self.saved_frames
@@ -426,7 +1512,6 @@ macro_rules! resampler_FftFixedout {
                 let chunks_needed =
                     (frames_needed_out as f32 / self.fft_size_out as f32).ceil() as usize;
                 self.frames_needed = chunks_needed * self.fft_size_in;
-                Ok(wave_out)
             }
         }
     };
@@ -437,14 +1522,18 @@ resampler_FftFixedout!(f64);
 macro_rules! impl_fixedin {
     ($ft:ty) => {
         impl FftFixedIn<$ft> {
-            /// Create a new FftFixedOut
+            /// Create a new FftFixedIn
             ///
             /// Parameters are:
             /// - `fs_in`: Input sample rate.
             /// - `fs_out`: Output sample rate.
-            /// - `chunk_size_out`: length of output data in frames.
+            /// - `chunk_size_in`: length of input data in frames.
             /// - `sub_chunks`: desired number of subchunks for processing, actual number used may be different.
             /// - `nbr_channels`: number of channels in input/output.
+            ///
+            /// # Panics
+            ///
+            /// Panics if any of the parameters is invalid, see `try_new`.
             pub fn new(
                 fs_in: usize,
                 fs_out: usize,
@@ -452,23 +1541,227 @@ macro_rules! impl_fixedin {
                 sub_chunks: usize,
                 nbr_channels: usize,
             ) -> Self {
+                Self::try_new(fs_in, fs_out, chunk_size_in, sub_chunks, nbr_channels).unwrap()
+            }
+
+            /// Create a new FftFixedIn, checking that the parameters are valid.
+            /// Takes the same parameters as `new`.
+            ///
+            /// # Errors
+            ///
+            /// Returns an `InvalidParameter` error if any of the sample rates,
+            /// the chunk size or the number of channels is zero,
+            /// or if the number of sub chunks is zero or larger than the chunk size.
+            pub fn try_new(
+                fs_in: usize,
+                fs_out: usize,
+                chunk_size_in: usize,
+                sub_chunks: usize,
+                nbr_channels: usize,
+            ) -> ResampleResult<Self> {
+                Self::try_new_with_parameters(
+                    fs_in,
+                    fs_out,
+                    chunk_size_in,
+                    sub_chunks,
+                    nbr_channels,
+                    FftFilterParameters::default(),
+                )
+            }
+
+            /// Create a new FftFixedIn, with an antialiasing filter made from the given parameters.
+            /// Takes the same parameters as `new`, and the filter parameters, see `FftFilterParameters`.
+            ///
+            /// # Panics
+            ///
+            /// Panics if any of the parameters is invalid, see `try_new_with_parameters`.
+            pub fn new_with_parameters(
+                fs_in: usize,
+                fs_out: usize,
+                chunk_size_in: usize,
+                sub_chunks: usize,
+                nbr_channels: usize,
+                parameters: FftFilterParameters,
+            ) -> Self {
+                Self::try_new_with_parameters(fs_in, fs_out, chunk_size_in, sub_chunks, nbr_channels, parameters)
+                    .unwrap()
+            }
 
+            /// Create a new FftFixedIn with the given filter parameters, checking that the parameters are valid.
+            /// Takes the same parameters as `new_with_parameters`.
+            ///
+            /// # Errors
+            ///
+            /// Returns an `InvalidParameter` error if the filter parameters are invalid,
+            /// see `FftFilterParameters`, or for the same reasons as `try_new`.
+            pub fn try_new_with_parameters(
+                fs_in: usize,
+                fs_out: usize,
+                chunk_size_in: usize,
+                sub_chunks: usize,
+                nbr_channels: usize,
+                parameters: FftFilterParameters,
+            ) -> ResampleResult<Self> {
+                validate_fft_parameters(fs_in, fs_out, chunk_size_in, sub_chunks, nbr_channels)?;
+                let (fft_size_in, fft_size_out) =
+                    fixed_in_fft_sizes(fs_in, fs_out, chunk_size_in, sub_chunks);
+                validate_filter_parameters(&parameters)?;
+                let filter = Arc::new(FftFilter::<$ft>::new(fft_size_in, fft_size_out, &parameters));
+                Self::try_new_with_filter(fs_in, fs_out, chunk_size_in, sub_chunks, nbr_channels, filter)
+            }
 
-                let gcd = integer::gcd(fs_in, fs_out);
-                let min_chunk_in = fs_in/gcd;
-                let wanted_subsize = chunk_size_in/sub_chunks;
-                let fft_chunks = (wanted_subsize as f32 / min_chunk_in as f32).ceil() as usize;
-                let fft_size_out = fft_chunks * fs_out / gcd;
-                let fft_size_in = fft_chunks * fs_in / gcd;
+            /// Create a new FftFixedIn, using a filter shared with other resamplers.
+            /// Takes the same parameters as `new`, and the filter from another resampler,
+            /// that must have been created for the same FFT sizes, see `FftFilter`.
+            ///
+            /// # Panics
+            ///
+            /// Panics if any of the parameters is invalid, see `try_new_with_filter`.
+            pub fn new_with_filter(
+                fs_in: usize,
+                fs_out: usize,
+                chunk_size_in: usize,
+                sub_chunks: usize,
+                nbr_channels: usize,
+                filter: Arc<FftFilter<$ft>>,
+            ) -> Self {
+                Self::try_new_with_filter(fs_in, fs_out, chunk_size_in, sub_chunks, nbr_channels, filter)
+                    .unwrap()
+            }
 
-                let resampler = FftResampler::<$ft>::new(fft_size_in, fft_size_out);
+            /// Create a new FftFixedIn using a shared filter, checking that the parameters are valid.
+            /// Takes the same parameters as `new_with_filter`.
+            ///
+            /// # Errors
+            ///
+            /// Returns an `InvalidParameter` error if the filter was made for different FFT sizes,
+            /// or for the same reasons as `try_new`.
+            pub fn try_new_with_filter(
+                fs_in: usize,
+                fs_out: usize,
+                chunk_size_in: usize,
+                sub_chunks: usize,
+                nbr_channels: usize,
+                filter: Arc<FftFilter<$ft>>,
+            ) -> ResampleResult<Self> {
+                validate_fft_parameters(fs_in, fs_out, chunk_size_in, sub_chunks, nbr_channels)?;
+                let (fft_size_in, fft_size_out) =
+                    fixed_in_fft_sizes(fs_in, fs_out, chunk_size_in, sub_chunks);
+                validate_filter(&filter, fft_size_in, fft_size_out)?;
                 debug!(
-                    "Create new FftFixedOut, fs_in: {}, fs_out: {} chunk_size_in: {}, channels: {}, fft_size_in: {}, fft_size_out: {}",
+                    "Create new FftFixedIn, fs_in: {}, fs_out: {} chunk_size_in: {}, channels: {}, fft_size_in: {}, fft_size_out: {}",
                     fs_in, fs_out, chunk_size_in, nbr_channels, fft_size_in, fft_size_out
                 );
+                Ok(Self::from_parts(chunk_size_in, nbr_channels, fft_size_in, fft_size_out, filter, None))
+            }
+
+            /// Create a new FftFixedIn in two-stage mode, for rate pairs where the FFTs
+            /// of the normal mode would be very long.
+            ///
+            /// The FFT sizes of the normal mode must be multiples of `fs_in / gcd(fs_in, fs_out)`
+            /// input frames, which is 44100 frames for 44100 to 44101 Hz.
+            /// In two-stage mode, each FFT instead takes `chunk_size_in / sub_chunks` input frames,
+            /// rounded down, and upsamples them by two with the antialiasing filter
+            /// given by `parameters`. A polyphase filter of 48 taps then resamples the result
+            /// to the output rate. The time of each output frame is stepped with exact integer
+            /// arithmetic, so the resampling stays synchronous.
+            /// The output is returned as soon as the polyphase filter has the frames it needs,
+            /// so each chunk gives about `chunk_size_in * fs_out / fs_in` frames,
+            /// for example 1024 or 1025 frames for chunks of 1024 frames from 44100 to 44101 Hz,
+            /// with a slightly shorter first chunk.
+            ///
+            /// Parameters are:
+            /// - `fs_in`: Input sample rate.
+            /// - `fs_out`: Output sample rate.
+            /// - `chunk_size_in`: length of input data in frames.
+            /// - `sub_chunks`: desired number of subchunks for processing.
+            /// - `nbr_channels`: number of channels in input/output.
+            /// - `parameters`: parameters for the antialiasing filter of the FFT stage, see `FftFilterParameters`.
+            ///
+            /// # Panics
+            ///
+            /// Panics if any of the parameters is invalid, see `try_new_two_stage`.
+            pub fn new_two_stage(
+                fs_in: usize,
+                fs_out: usize,
+                chunk_size_in: usize,
+                sub_chunks: usize,
+                nbr_channels: usize,
+                parameters: FftFilterParameters,
+            ) -> Self {
+                Self::try_new_two_stage(fs_in, fs_out, chunk_size_in, sub_chunks, nbr_channels, parameters)
+                    .unwrap()
+            }
+
+            /// Create a new FftFixedIn in two-stage mode, checking that the parameters are valid.
+            /// Takes the same parameters as `new_two_stage`.
+            ///
+            /// # Errors
+            ///
+            /// Returns an `InvalidParameter` error for the same reasons as `try_new_with_parameters`.
+            pub fn try_new_two_stage(
+                fs_in: usize,
+                fs_out: usize,
+                chunk_size_in: usize,
+                sub_chunks: usize,
+                nbr_channels: usize,
+                parameters: FftFilterParameters,
+            ) -> ResampleResult<Self> {
+                validate_fft_parameters(fs_in, fs_out, chunk_size_in, sub_chunks, nbr_channels)?;
+                validate_filter_parameters(&parameters)?;
+                let (fft_size_in, fft_size_out) = two_stage_fft_sizes(chunk_size_in / sub_chunks);
+                debug!(
+                    "Create new FftFixedIn in two-stage mode, fs_in: {}, fs_out: {} chunk_size_in: {}, channels: {}, fft_size_in: {}",
+                    fs_in, fs_out, chunk_size_in, nbr_channels, fft_size_in
+                );
+                let filter = Arc::new(FftFilter::<$ft>::new_for_rates(
+                    fft_size_in,
+                    fft_size_out,
+                    fs_in,
+                    fs_out,
+                    &parameters,
+                ));
+                let polyphase = PolyphaseStage::new(2 * fs_in, fs_out);
+                Ok(Self::from_parts(
+                    chunk_size_in,
+                    nbr_channels,
+                    fft_size_in,
+                    fft_size_out,
+                    filter,
+                    Some(polyphase),
+                ))
+            }
 
-                let overlaps: Vec<Vec<$ft>> = vec![vec![0.0; fft_size_out]; nbr_channels];
-                let input_buffers: Vec<Vec<$ft>> = vec![vec![0.0; chunk_size_in+fft_size_out]; nbr_channels];
+            /// Create the buffers and assemble a new FftFixedIn from validated parts.
+            fn from_parts(
+                chunk_size_in: usize,
+                nbr_channels: usize,
+                fft_size_in: usize,
+                fft_size_out: usize,
+                filter: Arc<FftFilter<$ft>>,
+                polyphase: Option<PolyphaseStage<$ft>>,
+            ) -> Self {
+                let fft_frames_max = (chunk_size_in + fft_size_in - 1) / fft_size_in * fft_size_out;
+                let (output_len, intermediate_len) = match &polyphase {
+                    Some(stage) => (
+                        fft_frames_max.max(stage.output_frames_max(fft_frames_max)),
+                        PolyphaseStage::<$ft>::buffer_len(fft_frames_max),
+                    ),
+                    None => (fft_frames_max, 0),
+                };
+                let channels = (0..nbr_channels)
+                    .map(|_| {
+                        FftChannel::new(
+                            &filter,
+                            chunk_size_in + fft_size_in,
+                            output_len,
+                            intermediate_len,
+                        )
+                    })
+                    .collect();
+                let scratch = (0..nbr_scratch_buffers(nbr_channels))
+                    .map(|_| filter.make_scratch())
+                    .collect();
 
                 let saved_frames = 0;
 
@@ -477,12 +1770,19 @@ macro_rules! impl_fixedin {
                     chunk_size_in,
                     fft_size_in,
                     fft_size_out,
-                    overlaps,
-                    input_buffers,
+                    channels,
                     saved_frames,
-                    resampler,
+                    filter,
+                    scratch,
+                    polyphase,
                 }
             }
+
+            /// Get the antialiasing filter, for sharing with other resamplers.
+            /// In two-stage mode, this is the filter of the FFT stage.
+            pub fn filter(&self) -> Arc<FftFilter<$ft>> {
+                self.filter.clone()
+            }
         }
     }
 }
@@ -498,101 +1798,197 @@ macro_rules! resampler_FftFixedin {
             }
 
             /// Update the resample ratio. This is not supported by this resampler and always returns an error.
-            fn set_resample_ratio(&mut self, _new_ratio: f64) -> Res<()> {
-                Err(Box::new(ResamplerError::new(
-                    "Not possible to adjust a synchronous resampler)",
-                )))
+            fn set_resample_ratio(&mut self, _new_ratio: f64) -> ResampleResult<()> {
+                Err(ResampleError::SyncNotAdjustable)
             }
 
             /// Update the resample ratio relative to the original one.
             /// This is not supported by this resampler and always returns an error.
-            fn set_resample_ratio_relative(&mut self, _rel_ratio: f64) -> Res<()> {
-                Err(Box::new(ResamplerError::new(
-                    "Not possible to adjust a synchronous resampler)",
-                )))
+            fn set_resample_ratio_relative(&mut self, _rel_ratio: f64) -> ResampleResult<()> {
+                Err(ResampleError::SyncNotAdjustable)
             }
 
-            /// Resample a chunk of audio. The required input length is provided by
-            /// the "nbr_frames_needed" function, and the output length is fixed.
-            /// # Errors
-            ///
-            /// The function returns an error if the length of the input data is not
-            /// equal to the number of channels defined when creating the instance,
-            /// and the number of audio frames given by "nbr_frames_needed".
-            fn process(&mut self, wave_in: &[Vec<$t>]) -> Res<Vec<Vec<$t>>> {
-                if wave_in.len() != self.nbr_channels {
-                    return Err(Box::new(ResamplerError::new(
-                        "Wrong number of channels in input",
-                    )));
-                }
-                if wave_in[0].len() != self.chunk_size_in {
-                    return Err(Box::new(ResamplerError::new(
-                        format!(
-                            "Wrong number of frames in input, expected {}, got {}",
-                            self.chunk_size_in,
-                            wave_in[0].len()
-                        )
-                        .as_str(),
-                    )));
+            /// Query for the maximum number of frames that a call to "process" can require.
+            fn input_frames_max(&self) -> usize {
+                self.chunk_size_in
+            }
+
+            /// Query for the maximum number of frames that a call to "process" can return.
+            fn output_frames_max(&self) -> usize {
+                let fft_frames_max = (self.chunk_size_in + self.fft_size_in - 1) / self.fft_size_in
+                    * self.fft_size_out;
+                match &self.polyphase {
+                    Some(stage) => stage.output_frames_max(fft_frames_max),
+                    None => fft_frames_max,
                 }
+            }
 
-                // copy new samples to input buffer
-                let mut input_temp =
-                    vec![vec![0.0; self.saved_frames + self.chunk_size_in]; self.nbr_channels];
-                for n in 0..self.nbr_channels {
-                    for (input, buffer) in self.input_buffers[n]
-                        .iter()
-                        .take(self.saved_frames)
-                        .zip(input_temp[n].iter_mut())
-                    {
-                        *buffer = *input;
-                    }
+            /// Query for the number of channels this resampler was created for.
+            fn nbr_channels(&self) -> usize {
+                self.nbr_channels
+            }
+
+            /// Get the resample ratio, `fs_out / fs_in`.
+            fn resample_ratio(&self) -> f64 {
+                let ratio = self.fft_size_out as f64 / self.fft_size_in as f64;
+                match &self.polyphase {
+                    Some(stage) => stage.output_frames(ratio),
+                    None => ratio,
                 }
-                for n in 0..self.nbr_channels {
-                    for (input, buffer) in wave_in[n].iter().zip(
-                        input_temp[n]
-                            .iter_mut()
-                            .skip(self.saved_frames)
-                            .take(self.chunk_size_in),
-                    ) {
-                        *buffer = *input;
-                    }
+            }
+
+            /// Get the delay of the resampler, in output frames.
+            /// The antialiasing filter delays the signal by half its length,
+            /// and the polyphase stage of the two-stage mode does not add any delay.
+            fn output_delay(&self) -> f64 {
+                let delay = (self.filter.filter_len / 2) as f64 * self.filter.fft_size_out as f64
+                    / self.fft_size_in as f64;
+                match &self.polyphase {
+                    Some(stage) => stage.output_frames(delay),
+                    None => delay,
                 }
-                self.saved_frames += self.chunk_size_in;
-
-                let nbr_chunks_ready =
-                    (self.saved_frames as f32 / self.fft_size_in as f32).floor() as usize;
-                let mut wave_out =
-                    vec![vec![0.0; nbr_chunks_ready * self.fft_size_out]; self.nbr_channels];
-                for n in 0..self.nbr_channels {
-                    for (in_chunk, out_chunk) in input_temp[n]
-                        .chunks(self.fft_size_in)
-                        .take(nbr_chunks_ready)
-                        .zip(wave_out[n].chunks_mut(self.fft_size_out))
-                    {
-                        self.resampler
-                            .resample_unit(in_chunk, out_chunk, &mut self.overlaps[n]);
-                    }
+            }
+
+            /// Reset the resampler to the state it had when it was created.
+            fn reset(&mut self) {
+                self.channels.iter_mut().for_each(|channel| channel.reset());
+                self.saved_frames = 0;
+                if let Some(stage) = &mut self.polyphase {
+                    stage.reset();
                 }
+            }
 
-                // save extra frames for next round
-                let frames_in_used = nbr_chunks_ready * self.fft_size_in;
-                let extra = self.saved_frames - frames_in_used;
+            /// Resample a chunk of audio. The input length is fixed, and the output varies in length.
+            /// # Errors
+            ///
+            /// The function returns an error if the length of the input data is not
+            /// equal to the number of channels and chunk size defined when creating the instance,
+            /// or if the output buffers are shorter than given by "output_frames_max".
+            fn process_into_buffer<Vin: AsRef<[$t]>, Vout: AsMut<[$t]>>(
+                &mut self,
+                wave_in: &[Vin],
+                wave_out: &mut [Vout],
+                active_channels_mask: Option<&[bool]>,
+            ) -> ResampleResult<usize> {
+                validate_buffers(
+                    Some(wave_in),
+                    wave_out,
+                    active_channels_mask,
+                    self.nbr_channels,
+                    self.chunk_size_in,
+                    self.output_frames_max(),
+                    false,
+                )?;
+                Ok(self.resample_chunk(
+                    Some(wave_in),
+                    wave_out,
+                    active_channels_mask,
+                    self.chunk_size_in,
+                ))
+            }
 
-                if self.saved_frames > frames_in_used {
-                    for n in 0..self.nbr_channels {
-                        for (input, buffer) in input_temp[n]
-                            .iter()
-                            .skip(frames_in_used)
-                            .take(extra)
-                            .zip(self.input_buffers[n].iter_mut())
+            /// Resample a chunk of audio that may be shorter than the chunk size.
+            /// Only the given frames are consumed, and the output contains
+            /// the frames that correspond to them. If the channels have different lengths,
+            /// the shorter ones are padded with zeros to the length of the longest.
+            /// If `None` is given, a full chunk of zeros is processed.
+            /// # Errors
+            ///
+            /// The function returns an error if the input data does not have the number of channels
+            /// defined when creating the instance, if it is longer than the chunk size,
+            /// or if the output buffers are shorter than given by "output_frames_max".
+            fn process_partial_into_buffer<Vin: AsRef<[$t]>, Vout: AsMut<[$t]>>(
+                &mut self,
+                wave_in: Option<&[Vin]>,
+                wave_out: &mut [Vout],
+                active_channels_mask: Option<&[bool]>,
+            ) -> ResampleResult<usize> {
+                validate_buffers(
+                    wave_in,
+                    wave_out,
+                    active_channels_mask,
+                    self.nbr_channels,
+                    self.chunk_size_in,
+                    self.output_frames_max(),
+                    true,
+                )?;
+                let frames_in = nbr_input_frames(wave_in, active_channels_mask, self.chunk_size_in);
+                Ok(self.resample_chunk(wave_in, wave_out, active_channels_mask, frames_in))
+            }
+        }
+
+        impl FftFixedIn<$t> {
+            /// Resample `frames_in` frames of audio from input buffers that have already been validated.
+            /// Input channels that are shorter than `frames_in` are padded with zeros.
+            fn resample_chunk<Vin: AsRef<[$t]>, Vout: AsMut<[$t]>>(
+                &mut self,
+                wave_in: Option<&[Vin]>,
+                wave_out: &mut [Vout],
+                active_channels_mask: Option<&[bool]>,
+                frames_in: usize,
+            ) -> usize {
+                let available_frames = self.saved_frames + frames_in;
+                let nbr_chunks_ready = available_frames / self.fft_size_in;
+                let frames_in_used = nbr_chunks_ready * self.fft_size_in;
+                let fft_frames_out = nbr_chunks_ready * self.fft_size_out;
+                let (frames_out, consumed) = match &self.polyphase {
+                    Some(stage) => {
+                        let available = stage.buffered() + fft_frames_out;
+                        let frames_out = stage.nbr_output_frames(available);
+                        (frames_out, stage.frames_consumed(frames_out, available))
+                    }
+                    None => (fft_frames_out, 0),
+                };
+                for (n, channel) in self.channels.iter_mut().enumerate() {
+                    if channel_is_active(active_channels_mask, n) {
+                        // copy new samples to input buffer
+                        copy_and_pad(
+                            input_channel(wave_in, n),
+                            &mut channel.input[self.saved_frames..available_frames],
+                        );
+                    }
+                }
+                let filter = &*self.filter;
+                let polyphase = self.polyphase.as_ref();
+                let (fft_size_in, fft_size_out) = (self.fft_size_in, self.fft_size_out);
+                for_each_channel_with_scratch(
+                    &mut self.channels,
+                    &mut self.scratch,
+                    |n, channel, scratch| {
+                        if !channel_is_active(active_channels_mask, n) {
+                            return;
+                        }
+                        for (in_chunk, out_chunk) in channel.input[..frames_in_used]
+                            .chunks(fft_size_in)
+                            .zip(channel.output.chunks_mut(fft_size_out))
                         {
-                            *buffer = *input;
+                            filter.resample_unit(
+                                scratch,
+                                in_chunk,
+                                out_chunk,
+                                &mut channel.history,
+                            );
+                        }
+                        // save extra frames for next round
+                        channel
+                            .input
+                            .copy_within(frames_in_used..available_frames, 0);
+                        if let Some(stage) = polyphase {
+                            channel.resample_polyphase(stage, fft_frames_out, frames_out, consumed);
                         }
+                    },
+                );
+                for (n, wav) in wave_out.iter_mut().enumerate() {
+                    if channel_is_active(active_channels_mask, n) {
+                        wav.as_mut()[..frames_out]
+                            .copy_from_slice(&self.channels[n].output[..frames_out]);
                     }
                 }
-                self.saved_frames = extra;
-                Ok(wave_out)
+                self.saved_frames = available_frames - frames_in_used;
+                if let Some(stage) = &mut self.polyphase {
+                    stage.advance(frames_out, consumed, stage.buffered() + fft_frames_out);
+                }
+                frames_out
             }
         }
     };
@@ -602,12 +1998,42 @@ resampler_FftFixedin!(f64);
 
 #[cfg(test)]
 mod tests {
-    use crate::synchro::{FftFixedIn, FftFixedInOut, FftFixedOut, FftResampler};
-    use crate::Resampler;
+    use crate::synchro::{
+        FftFilter, FftFilterParameters, FftFixedIn, FftFixedInOut, FftFixedOut, FftHistory,
+    };
+    use crate::{ResampleError, Resampler, WindowFunction};
+    use std::sync::Arc;
 
     #[test]
     fn resample_unit() {
-        let mut resampler = FftResampler::<f64>::new(147, 1000);
+        let resampler = FftFilter::<f64>::new(147, 1000, &FftFilterParameters::default());
+        check_resample_unit(&resampler);
+    }
+
+    #[test]
+    fn resample_unit_kaiser() {
+        let params = FftFilterParameters {
+            window: WindowFunction::kaiser_from_attenuation(120.0),
+            ..Default::default()
+        };
+        let resampler = FftFilter::<f64>::new(147, 1000, &params);
+        check_resample_unit(&resampler);
+    }
+
+    #[test]
+    fn resample_unit_partitioned() {
+        let params = FftFilterParameters {
+            filter_len: Some(1000),
+            ..Default::default()
+        };
+        let resampler = FftFilter::<f64>::new(147, 294, &params);
+        assert_eq!(resampler.nbr_partitions(), 7);
+        check_resample_unit(&resampler);
+    }
+
+    /// Resample a short pulse, followed by zeros until the filter has passed it,
+    /// and check the sum and peak value of the result.
+    fn check_resample_unit(resampler: &FftFilter<f64>) {
         let mut wave_in = vec![0.0; 147];
 
         wave_in[0] = 0.3;
@@ -617,12 +2043,17 @@ mod tests {
         wave_in[4] = 0.7;
         wave_in[5] = 0.3;
 
-        let mut wave_out = vec![0.0; 1000];
-        let mut overlap = vec![0.0; 1000];
-        resampler.resample_unit(&wave_in, &mut wave_out, &mut overlap);
+        let fft_size_out = resampler.fft_size_out();
+        let mut wave_out = vec![0.0; fft_size_out * (resampler.nbr_partitions() + 1)];
+        let mut history = FftHistory::new(resampler);
+        let mut scratch = resampler.make_scratch();
+        for (n, chunk_out) in wave_out.chunks_mut(fft_size_out).enumerate() {
+            let chunk_in = if n == 0 { &wave_in[..] } else { &[] };
+            resampler.resample_unit(&mut scratch, chunk_in, chunk_out, &mut history);
+        }
         let vecsum = wave_out.iter().sum::<f64>();
-        let maxval = wave_out.iter().cloned().fold(0. / 0., f64::max);
-        assert!((vecsum - 4.0 * 1000.0 / 147.0).abs() < 1.0e-6);
+        let maxval = wave_out.iter().cloned().fold(std::f64::NAN, f64::max);
+        assert!((vecsum - 4.0 * fft_size_out as f64 / 147.0).abs() < 1.0e-6);
         assert!((maxval - 1.0).abs() < 0.1);
     }
 
@@ -680,4 +2111,361 @@ mod tests {
         assert_eq!(out.len(), 2);
         assert_eq!(out[0].len(), 1024);
     }
+
+    #[test]
+    fn sync_not_adjustable() {
+        let mut resampler = FftFixedIn::<f64>::new(44100, 48000, 1024, 2, 2);
+        assert_eq!(
+            resampler.set_resample_ratio(1.1),
+            Err(ResampleError::SyncNotAdjustable)
+        );
+        let mut resampler = FftFixedOut::<f64>::new(44100, 48000, 1024, 2, 2);
+        assert_eq!(
+            resampler.set_resample_ratio_relative(1.1),
+            Err(ResampleError::SyncNotAdjustable)
+        );
+        // the ramped variants fall back to the plain ones
+        assert_eq!(
+            resampler.set_resample_ratio_relative_ramped(1.1),
+            Err(ResampleError::SyncNotAdjustable)
+        );
+    }
+
+    #[test]
+    fn invalid_parameters() {
+        assert!(FftFixedInOut::<f64>::try_new(44100, 48000, 1024, 2).is_ok());
+        match FftFixedOut::<f64>::try_new(44100, 48000, 1024, 0, 2) {
+            Err(ResampleError::InvalidParameter { name, .. }) => assert_eq!(name, "sub_chunks"),
+            _ => panic!("expected an InvalidParameter error"),
+        }
+        match FftFixedIn::<f64>::try_new(44100, 0, 1024, 2, 2) {
+            Err(ResampleError::InvalidParameter { name, .. }) => assert_eq!(name, "fs_out"),
+            _ => panic!("expected an InvalidParameter error"),
+        }
+        match FftFixedInOut::<f32>::try_new(44100, 48000, 1024, 0) {
+            Err(ResampleError::InvalidParameter { name, .. }) => assert_eq!(name, "nbr_channels"),
+            _ => panic!("expected an InvalidParameter error"),
+        }
+    }
+
+    #[test]
+    fn shared_filter() {
+        let mut first = FftFixedIn::<f64>::new(44100, 48000, 1024, 2, 2);
+        let filter = first.filter();
+        assert_eq!(filter.fft_size_in(), 588);
+        assert_eq!(filter.fft_size_out(), 640);
+        let mut second =
+            FftFixedIn::<f64>::new_with_filter(44100, 48000, 1024, 2, 2, filter.clone());
+        assert_eq!(Arc::strong_count(&filter), 3);
+        let waves = vec![vec![0.5f64; 1024]; 2];
+        assert_eq!(
+            first.process(&waves).unwrap(),
+            second.process(&waves).unwrap()
+        );
+        // the filter can be used in other threads, and by other types with the same FFT sizes
+        let handle = std::thread::spawn(move || {
+            let mut fixed_out =
+                FftFixedOut::<f64>::new_with_filter(44100, 48000, 1024, 2, 2, filter);
+            let frames = fixed_out.nbr_frames_needed();
+            fixed_out.process(&vec![vec![0.0f64; frames]; 2]).unwrap()
+        });
+        assert_eq!(handle.join().unwrap()[0].len(), 1024);
+    }
+
+    #[test]
+    fn filter_mismatch() {
+        let filter = FftFixedIn::<f64>::new(44100, 48000, 1024, 2, 2).filter();
+        match FftFixedInOut::<f64>::try_new_with_filter(44100, 48000, 1024, 2, filter) {
+            Err(ResampleError::InvalidParameter { name, .. }) => assert_eq!(name, "filter"),
+            _ => panic!("expected an InvalidParameter error"),
+        }
+    }
+
+    /// Resample a sine of the given frequency from 48000 to 44100 Hz, in chunks of the given size,
+    /// and return the gain in dB, measured after the filter has settled.
+    fn tone_gain_db(
+        fs_in: usize,
+        fs_out: usize,
+        chunk_size: usize,
+        parameters: FftFilterParameters,
+        freq: f64,
+    ) -> f64 {
+        let mut resampler =
+            FftFixedIn::<f64>::new_with_parameters(fs_in, fs_out, chunk_size, 1, 1, parameters);
+        let mut output = Vec::new();
+        for chunk in 0..(40960 / chunk_size) {
+            let wave: Vec<f64> = (0..chunk_size)
+                .map(|n| {
+                    let t = (chunk * chunk_size + n) as f64 / fs_in as f64;
+                    (2.0 * std::f64::consts::PI * freq * t).sin()
+                })
+                .collect();
+            output.extend_from_slice(&resampler.process(&[wave]).unwrap()[0]);
+        }
+        let settled = &output[output.len() / 2..];
+        let rms = (settled.iter().map(|v| v * v).sum::<f64>() / settled.len() as f64).sqrt();
+        20.0 * (rms * 2.0f64.sqrt()).log10()
+    }
+
+    #[test]
+    fn stopband_attenuation() {
+        let params = FftFilterParameters::default();
+        for freq in [1000.0, 10000.0, 20000.0].iter() {
+            assert!(
+                tone_gain_db(48000, 44100, 1024, params, *freq).abs() < 0.1,
+                "{} Hz",
+                freq
+            );
+        }
+        // above the output Nyquist frequency of 22050 Hz
+        for freq in [22500.0, 23000.0, 23900.0].iter() {
+            assert!(
+                tone_gain_db(48000, 44100, 1024, params, *freq) < -120.0,
+                "{} Hz",
+                freq
+            );
+        }
+    }
+
+    #[test]
+    fn custom_cutoff() {
+        let params = FftFilterParameters {
+            f_cutoff: Some(0.5),
+            ..Default::default()
+        };
+        assert!(tone_gain_db(48000, 44100, 1024, params, 10000.0).abs() < 0.1);
+        assert!(tone_gain_db(48000, 44100, 1024, params, 16000.0) < -120.0);
+        assert!(
+            tone_gain_db(48000, 44100, 1024, FftFilterParameters::default(), 16000.0).abs() < 0.1
+        );
+    }
+
+    #[test]
+    fn short_filter() {
+        let params = FftFilterParameters {
+            filter_len: Some(64),
+            f_cutoff: Some(0.8),
+            window: WindowFunction::Blackman2,
+        };
+        let resampler = FftFixedIn::<f64>::new_with_parameters(48000, 44100, 1024, 2, 1, params);
+        let filter = resampler.filter();
+        assert_eq!(filter.filter_len(), 64);
+        assert_eq!(filter.fft_size_in(), 640);
+        assert!((filter.cutoff() - 0.8 * 44100.0 / 48000.0).abs() < 1.0e-6);
+        assert!((resampler.output_delay() - 32.0 * 588.0 / 640.0).abs() < 1.0e-9);
+        // a short filter has a wider transition band, but still attenuates the stop band
+        assert!(tone_gain_db(48000, 44100, 1024, params, 10000.0).abs() < 0.1);
+        assert!(tone_gain_db(48000, 44100, 1024, params, 23000.0) < -100.0);
+    }
+
+    #[test]
+    fn invalid_filter_parameters() {
+        let check = |params: FftFilterParameters, expected: &str| {
+            match FftFixedIn::<f32>::try_new_with_parameters(48000, 44100, 1024, 2, 1, params) {
+                Err(ResampleError::InvalidParameter { name, .. }) => assert_eq!(name, expected),
+                _ => panic!("expected an InvalidParameter error"),
+            }
+        };
+        check(
+            FftFilterParameters {
+                f_cutoff: Some(1.5),
+                ..Default::default()
+            },
+            "f_cutoff",
+        );
+        check(
+            FftFilterParameters {
+                filter_len: Some(0),
+                ..Default::default()
+            },
+            "filter_len",
+        );
+    }
+
+    #[test]
+    fn invalid_kaiser_beta() {
+        for beta in [-1.0, std::f64::NAN, std::f64::INFINITY].iter().copied() {
+            let params = FftFilterParameters {
+                window: WindowFunction::Kaiser { beta },
+                ..Default::default()
+            };
+            match FftFixedOut::<f64>::try_new_with_parameters(44100, 48000, 1024, 2, 1, params) {
+                Err(ResampleError::InvalidParameter { name, .. }) => assert_eq!(name, "window"),
+                _ => panic!("expected an InvalidParameter error"),
+            }
+        }
+    }
+
+    #[test]
+    fn long_filter_small_chunks() {
+        // the default filter is as short as the FFT input, 160 frames,
+        // which attenuates frequencies well below the output Nyquist frequency
+        let params = FftFilterParameters::default();
+        assert!(tone_gain_db(48000, 44100, 160, params, 21000.0) < -20.0);
+        let params = FftFilterParameters {
+            filter_len: Some(2048),
+            ..Default::default()
+        };
+        assert!(tone_gain_db(48000, 44100, 160, params, 21000.0).abs() < 0.1);
+        assert!(tone_gain_db(48000, 44100, 160, params, 23000.0) < -150.0);
+    }
+
+    #[test]
+    fn partitioned_filter_small_chunks() {
+        let params = FftFilterParameters {
+            filter_len: Some(1024),
+            ..Default::default()
+        };
+        let resampler = FftFixedIn::<f64>::new_with_parameters(96000, 48000, 64, 1, 1, params);
+        assert_eq!(resampler.filter().nbr_partitions(), 16);
+        assert!(tone_gain_db(96000, 48000, 64, params, 22000.0).abs() < 0.1);
+        for freq in [24500.0, 30000.0, 47000.0].iter() {
+            assert!(
+                tone_gain_db(96000, 48000, 64, params, *freq) < -140.0,
+                "{} Hz",
+                freq
+            );
+        }
+    }
+
+    #[test]
+    fn filter_independent_of_chunk_size() {
+        let params = FftFilterParameters {
+            filter_len: Some(1000),
+            f_cutoff: Some(0.9),
+            window: WindowFunction::BlackmanHarris2,
+        };
+        // the large chunks fit the filter in one FFT, the small ones split it into partitions,
+        // except for 48000 to 44100 Hz where longer FFTs are cheaper
+        for (fs_in, fs_out, small_size, large_size) in [
+            (48000, 44100, 160, 1280),
+            (48000, 96000, 64, 1024),
+            (96000, 48000, 64, 1024),
+        ]
+        .iter()
+        {
+            let mut small =
+                FftFixedIn::<f64>::new_with_parameters(*fs_in, *fs_out, *small_size, 1, 1, params);
+            let mut large =
+                FftFixedIn::<f64>::new_with_parameters(*fs_in, *fs_out, *large_size, 1, 1, params);
+            assert_eq!(
+                small.filter().nbr_partitions() > 1,
+                *fs_in != 48000 || *fs_out != 44100
+            );
+            assert_eq!(large.filter().nbr_partitions(), 1);
+            assert_eq!(small.output_delay(), large.output_delay());
+            let wave: Vec<f64> = (0..5120).map(|n| (0.05 * n as f64).sin()).collect();
+            let mut out_small = Vec::new();
+            for chunk in wave.chunks(*small_size) {
+                out_small.extend_from_slice(&small.process(&[chunk]).unwrap()[0]);
+            }
+            let mut out_large = Vec::new();
+            for chunk in wave.chunks(*large_size) {
+                out_large.extend_from_slice(&large.process(&[chunk]).unwrap()[0]);
+            }
+            assert_eq!(out_small.len(), out_large.len());
+            for (a, b) in out_small.iter().zip(out_large.iter()) {
+                assert!((a - b).abs() < 1.0e-9, "{} to {}", fs_in, fs_out);
+            }
+        }
+    }
+
+    /// Resample a sine in two-stage mode, and return the largest error compared to
+    /// the exact values delayed by the reported delay, and the number of output frames.
+    fn two_stage_sine_error(fs_in: usize, fs_out: usize, freq: f64) -> (f64, usize) {
+        let mut resampler = FftFixedIn::<f64>::new_two_stage(
+            fs_in,
+            fs_out,
+            1024,
+            2,
+            1,
+            FftFilterParameters::default(),
+        );
+        let delay = resampler.output_delay();
+        let mut output = Vec::new();
+        for chunk in 0..100 {
+            let wave: Vec<f64> = (0..1024)
+                .map(|n| {
+                    let t = (chunk * 1024 + n) as f64 / fs_in as f64;
+                    (2.0 * std::f64::consts::PI * freq * t).sin()
+                })
+                .collect();
+            output.extend_from_slice(&resampler.process(&[wave]).unwrap()[0]);
+        }
+        let error = output
+            .iter()
+            .enumerate()
+            .skip(2 * delay as usize + 1000)
+            .map(|(n, val)| {
+                let t = (n as f64 - delay) / fs_out as f64;
+                (val - (2.0 * std::f64::consts::PI * freq * t).sin()).abs()
+            })
+            .fold(0.0, f64::max);
+        (error, output.len())
+    }
+
+    #[test]
+    fn two_stage_accuracy() {
+        for (fs_in, fs_out) in [(44100, 44101), (48000, 44100), (44100, 96000)].iter() {
+            let (error, nbr_frames) = two_stage_sine_error(*fs_in, *fs_out, 18000.0);
+            assert!(error < 1.0e-8, "{} to {}", fs_in, fs_out);
+            let expected = 102400 * *fs_out / *fs_in;
+            assert!(nbr_frames <= expected && nbr_frames > expected - 100);
+        }
+    }
+
+    #[test]
+    fn make_resampler_fi_two_stage() {
+        let params = FftFilterParameters::default();
+        let mut resampler = FftFixedIn::<f32>::new_two_stage(44100, 44101, 1024, 2, 2, params);
+        assert_eq!(resampler.filter().fft_size_in(), 512);
+        let waves = vec![vec![0.0f32; 1024]; 2];
+        let first = resampler.process(&waves).unwrap()[0].len();
+        assert!(first < 1024);
+        for _ in 0..100 {
+            let out = resampler.process(&waves).unwrap();
+            assert!(out[0].len() == 1024 || out[0].len() == 1025);
+            assert!(out[0].len() <= resampler.output_frames_max());
+        }
+    }
+
+    #[test]
+    fn make_resampler_fo_two_stage() {
+        let params = FftFilterParameters::default();
+        let mut resampler = FftFixedOut::<f32>::new_two_stage(44100, 44101, 1024, 2, 2, params);
+        assert_eq!(resampler.filter().fft_size_in(), 512);
+        let mut total = 0;
+        for _ in 0..100 {
+            let frames = resampler.nbr_frames_needed();
+            assert_eq!(frames % 512, 0);
+            assert!(frames <= resampler.input_frames_max());
+            total += frames;
+            let waves = vec![vec![0.0f32; frames]; 2];
+            let out = resampler.process(&waves).unwrap();
+            assert_eq!(out[0].len(), 1024);
+        }
+        assert!((total as i64 - 102400).abs() <= 1024);
+    }
+
+    #[test]
+    fn reset_two_stage() {
+        let params = FftFilterParameters::default();
+        let mut resampler = FftFixedOut::<f64>::new_two_stage(48000, 44100, 1000, 1, 1, params);
+        let wave: Vec<f64> = (0..20000).map(|n| (0.05 * n as f64).sin()).collect();
+        let mut first = Vec::new();
+        let mut start = 0;
+        for _ in 0..5 {
+            let frames = resampler.nbr_frames_needed();
+            first.push(resampler.process(&[&wave[start..start + frames]]).unwrap());
+            start += frames;
+        }
+        resampler.reset();
+        start = 0;
+        for expected in first.iter() {
+            let frames = resampler.nbr_frames_needed();
+            let out = resampler.process(&[&wave[start..start + frames]]).unwrap();
+            assert_eq!(&out, expected);
+            start += frames;
+        }
+    }
 }
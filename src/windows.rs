@@ -1,7 +1,7 @@
 use num_traits::Float;
 
 /// Different window functions that can be used to window the sinc function.
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum WindowFunction {
     /// Blackman. Intermediate rolloff and intermediate attenuation.
     Blackman,
@@ -15,6 +15,28 @@ pub enum WindowFunction {
     Hann,
     /// Squared Hann, slower rolloff and higher attenuation than simple Hann
     Hann2,
+    /// Kaiser, with an adjustable trade-off between rolloff and attenuation.
+    /// A larger `beta` gives slower rolloff and higher attenuation.
+    /// A `beta` of 0 gives a rectangular window, and values around 10 give
+    /// an attenuation similar to Blackman-Harris.
+    /// Use `kaiser_from_attenuation` to get the `beta` for a wanted attenuation.
+    /// The `beta` must be a finite number, and not negative.
+    Kaiser { beta: f64 },
+}
+
+impl WindowFunction {
+    /// Get a Kaiser window with the stop band attenuation given in dB.
+    /// The `beta` value is calculated using the empirical formula given by Kaiser.
+    pub fn kaiser_from_attenuation(attenuation: f64) -> Self {
+        let beta = if attenuation > 50.0 {
+            0.1102 * (attenuation - 8.7)
+        } else if attenuation >= 21.0 {
+            0.5842 * (attenuation - 21.0).powf(0.4) + 0.07886 * (attenuation - 21.0)
+        } else {
+            0.0
+        };
+        WindowFunction::Kaiser { beta }
+    }
 }
 
 /// Helper function. Standard Blackman-Harris window
@@ -68,6 +90,37 @@ pub fn hann<T: Float>(npoints: usize) -> Vec<T> {
     window
 }
 
+/// Helper function. Modified Bessel function of the first kind, order zero.
+/// Calculated from its power series, that converges quickly for the values used here.
+fn bessel_i0(x: f64) -> f64 {
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    let half_x = x / 2.0;
+    let mut k = 1.0;
+    while term > 1.0e-16 * sum {
+        term *= (half_x / k) * (half_x / k);
+        sum += term;
+        k += 1.0;
+    }
+    sum
+}
+
+/// Kaiser window with the given `beta`
+pub fn kaiser<T: Float>(npoints: usize, beta: f64) -> Vec<T> {
+    trace!(
+        "Making a Kaiser windows with {} points, beta {}",
+        npoints,
+        beta
+    );
+    let mut window = vec![T::zero(); npoints];
+    let norm = bessel_i0(beta);
+    for (x, item) in window.iter_mut().enumerate() {
+        let pos = 2.0 * x as f64 / npoints as f64 - 1.0;
+        *item = T::from(bessel_i0(beta * (1.0 - pos * pos).sqrt()) / norm).unwrap();
+    }
+    window
+}
+
 /// Make the selected window function
 pub fn make_window<T: Float>(npoints: usize, windowfunc: WindowFunction) -> Vec<T> {
     let mut window = match windowfunc {
@@ -76,6 +129,7 @@ pub fn make_window<T: Float>(npoints: usize, windowfunc: WindowFunction) -> Vec<
         }
         WindowFunction::Blackman | WindowFunction::Blackman2 => blackman::<T>(npoints),
         WindowFunction::Hann | WindowFunction::Hann2 => hann::<T>(npoints),
+        WindowFunction::Kaiser { beta } => kaiser::<T>(npoints, beta),
     };
     match windowfunc {
         WindowFunction::Blackman2 | WindowFunction::BlackmanHarris2 | WindowFunction::Hann2 => {
@@ -86,10 +140,40 @@ pub fn make_window<T: Float>(npoints: usize, windowfunc: WindowFunction) -> Vec<
     window
 }
 
+/// Get the half width of the transition band of a sinc filter windowed by the given window,
+/// in units of the frequency resolution of the window. This is close to half the width
+/// of the main lobe of the window spectrum, with a small correction found empirically.
+fn transition_half_width(windowfunc: WindowFunction) -> f32 {
+    match windowfunc {
+        WindowFunction::Hann => 1.73,
+        WindowFunction::Hann2 => 2.70,
+        WindowFunction::Blackman => 2.73,
+        WindowFunction::Blackman2 => 4.74,
+        WindowFunction::BlackmanHarris => 3.73,
+        WindowFunction::BlackmanHarris2 => 6.74,
+        WindowFunction::Kaiser { beta } => {
+            let beta_pi = beta as f32 / std::f32::consts::PI;
+            (1.0 + beta_pi * beta_pi).sqrt() - 0.27
+        }
+    }
+}
+
+/// Calculate a suitable relative cutoff frequency for a sinc filter of length `sinc_len`,
+/// windowed with the given window function.
+/// The cutoff is placed as high as possible, while keeping the whole transition band
+/// of the filter below the Nyquist frequency.
+/// The result is an approximation, that is useful for sinc lengths from 32 and up.
+pub fn calculate_cutoff(sinc_len: usize, windowfunc: WindowFunction) -> f32 {
+    let cutoff = 1.0 - 2.0 * transition_half_width(windowfunc) / sinc_len as f32;
+    cutoff.max(0.0)
+}
+
 #[cfg(test)]
 mod tests {
+    use crate::windows::bessel_i0;
     use crate::windows::blackman;
     use crate::windows::blackman_harris;
+    use crate::windows::calculate_cutoff;
     use crate::windows::hann;
     use crate::windows::make_window;
     use crate::windows::WindowFunction;
@@ -129,4 +213,45 @@ mod tests {
         assert!(wnd[0] < 0.000001);
         assert!(wnd[15] < 0.1);
     }
+
+    #[test]
+    fn test_bessel_i0() {
+        assert_eq!(bessel_i0(0.0), 1.0);
+        assert!((bessel_i0(1.0) - 1.266_065_877_752_008_4).abs() < 1.0e-12);
+        assert!((bessel_i0(10.0) - 2_815.716_628_466_254).abs() < 1.0e-8);
+    }
+
+    #[test]
+    fn test_kaiser() {
+        let wnd = make_window::<f64>(16, WindowFunction::Kaiser { beta: 8.0 });
+        assert!((wnd[8] - 1.0).abs() < 0.000001);
+        assert!(wnd[0] < 0.01);
+        assert!((wnd[4] - wnd[12]).abs() < 0.000001);
+        let rect = make_window::<f64>(16, WindowFunction::Kaiser { beta: 0.0 });
+        assert!(rect.iter().all(|val| (val - 1.0).abs() < 0.000001));
+    }
+
+    #[test]
+    fn test_kaiser_from_attenuation() {
+        assert_eq!(
+            WindowFunction::kaiser_from_attenuation(10.0),
+            WindowFunction::Kaiser { beta: 0.0 }
+        );
+        match WindowFunction::kaiser_from_attenuation(100.0) {
+            WindowFunction::Kaiser { beta } => assert!((beta - 10.061_26).abs() < 0.00001),
+            _ => panic!("expected a Kaiser window"),
+        }
+        match WindowFunction::kaiser_from_attenuation(40.0) {
+            WindowFunction::Kaiser { beta } => assert!((beta - 3.395_321).abs() < 0.00001),
+            _ => panic!("expected a Kaiser window"),
+        }
+    }
+
+    #[test]
+    fn test_calculate_cutoff() {
+        assert!((calculate_cutoff(64, WindowFunction::Hann2) - 0.9156).abs() < 0.0001);
+        assert!((calculate_cutoff(128, WindowFunction::Blackman2) - 0.9259).abs() < 0.0001);
+        assert!((calculate_cutoff(256, WindowFunction::BlackmanHarris2) - 0.9473).abs() < 0.0001);
+        assert_eq!(calculate_cutoff(4, WindowFunction::BlackmanHarris2), 0.0);
+    }
 }